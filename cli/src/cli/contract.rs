@@ -19,10 +19,10 @@
 
 use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
 use client::Client;
+use ethereum_types::U256;
 use failure::Error;
 use serde_json::to_string_pretty;
 use tiny_keccak::keccak256;
-use types::TransactionReceipt;
 
 /// Returns Clap configuration
 pub fn get_cli<'a, 'b>() -> App<'a, 'b> {
@@ -58,6 +58,7 @@ pub fn get_cli<'a, 'b>() -> App<'a, 'b> {
                         .takes_value(true)
                         .default_value(::DEFAULT_GAS)
                         .help("Gas limit for calling the contract (default: 90,000)"),
+                    ::get_value_arg(),
                     ::get_wait_arg(),
                     Arg::with_name("address")
                         .required(true)
@@ -83,6 +84,7 @@ pub fn get_cli<'a, 'b>() -> App<'a, 'b> {
                         .long("permissions")
                         .takes_value(true)
                         .help("Permissions for the new contract"),
+                    ::get_value_arg(),
                     ::get_wait_arg(),
                 ]),
             SubCommand::with_name("list")
@@ -107,9 +109,10 @@ pub fn parse_cli<'a>(matches: (&'a str, Option<&'a ArgMatches>)) -> Result<(), E
                 .value_of("gas")
                 .expect("Default gas must exist!")
                 .parse::<u64>()?;
+            let value = ::parse_value_flag(m)?;
             let wait = ::parse_wait_flag(m)?;
 
-            do_call(client, from, address, data, chaining, gas, wait)?;
+            do_call(client, from, address, data, chaining, gas, value, wait)?;
         }
         ("create", Some(m)) => {
             let from = m.value_of("from").expect("From address is required!");
@@ -119,9 +122,10 @@ pub fn parse_cli<'a>(matches: (&'a str, Option<&'a ArgMatches>)) -> Result<(), E
                 .expect("Default gas must exist!")
                 .parse::<u64>()?;
             let permissions = m.value_of("permissions");
+            let value = ::parse_value_flag(m)?;
             let wait = ::parse_wait_flag(m)?;
 
-            do_create(client, from, init, gas, permissions, wait)?;
+            do_create(client, from, init, gas, permissions, value, wait)?;
         }
         ("list", Some(m)) => {
             let address = m.value_of("address").expect("Address is required!");
@@ -135,6 +139,7 @@ pub fn parse_cli<'a>(matches: (&'a str, Option<&'a ArgMatches>)) -> Result<(), E
 }
 
 /// Calls a contract
+#[allow(clippy::too_many_arguments)]
 pub fn do_call(
     client: &Client,
     from: &str,
@@ -142,26 +147,24 @@ pub fn do_call(
     data: &str,
     chaining: bool,
     gas: u64,
+    value: Option<U256>,
     wait: Option<u64>,
 ) -> Result<(), Error> {
-    let txn_id: String = client.send_rpc_transaction(
-        "eth_sendTransaction",
-        &vec![json!({
-            "from": format!("0x{}", from),
-            "data": format!("0x{}", data),
-            "to": format!("0x{}", address),
-            "gas": format!("0x{:x}", gas),
-            "chaining": chaining,
-        })],
-    )?;
+    let mut txn = json!({
+        "from": format!("0x{}", from),
+        "data": format!("0x{}", data),
+        "to": format!("0x{}", address),
+        "gas": format!("0x{:x}", gas),
+        "chaining": chaining,
+    });
+    if let Some(value) = value {
+        txn["value"] = json!(format!("{:#x}", value));
+    }
+    let txn_id: String = client.send_rpc_transaction("eth_sendTransaction", &vec![txn])?;
 
     let (gas_used, retval) = match wait {
         Some(w) => {
-            let receipt: TransactionReceipt = client.wait_for_rpc_transaction(
-                "eth_getTransactionReceipt",
-                &vec![txn_id.clone()],
-                w,
-            )?;
+            let receipt = client.wait_for_receipt(&txn_id, w)?;
 
             (
                 u64::from_str_radix(&receipt.gas_used[2..], 16)?,
@@ -190,29 +193,27 @@ pub fn do_create(
     init: &str,
     gas: u64,
     permissions: Option<&str>,
+    value: Option<U256>,
     wait: Option<u64>,
 ) -> Result<(), Error> {
-    let txn_id: String = client.send_rpc_transaction(
-        "eth_sendTransaction",
-        &vec![json!({
-            "from": format!("0x{}", from),
-            "data": format!("0x{}", init),
-            "gas": format!("0x{:x}", gas),
-            "permissions": permissions,
-        })],
-    )?;
+    let mut txn = json!({
+        "from": format!("0x{}", from),
+        "data": format!("0x{}", init),
+        "gas": format!("0x{:x}", gas),
+        "permissions": permissions,
+    });
+    if let Some(value) = value {
+        txn["value"] = json!(format!("{:#x}", value));
+    }
+    let txn_id: String = client.send_rpc_transaction("eth_sendTransaction", &vec![txn])?;
 
     let (gas_used, retval, address) = match wait {
         Some(w) => {
-            let receipt: TransactionReceipt = client.wait_for_rpc_transaction(
-                "eth_getTransactionReceipt",
-                &vec![txn_id.clone()],
-                w,
-            )?;
+            let receipt = client.wait_for_receipt(&txn_id, w)?;
             (
                 u64::from_str_radix(&receipt.gas_used[2..], 16)?,
                 receipt.return_value[2..].to_string(),
-                receipt.contract_address.to_string(),
+                receipt.contract_address.unwrap_or_default(),
             )
         }
         None => (0u64, "<Not retrieved>".into(), "<Not retrieved>".into()),
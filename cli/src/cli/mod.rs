@@ -23,3 +23,4 @@ pub mod contract;
 pub mod event;
 pub mod permissions;
 pub mod receipt;
+pub mod transaction;
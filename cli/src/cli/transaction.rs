@@ -0,0 +1,71 @@
+/*
+ * Copyright 2019 Cargill Incorporated
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! Show the status of transactions
+
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use client::Client;
+use failure::Error;
+use serde_json::to_string_pretty;
+use types::TransactionStatus;
+
+/// Returns Clap configuration
+pub fn get_cli<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("transaction")
+        .about("Manages seth transactions")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommands(vec![SubCommand::with_name("status")
+            .about("Shows whether a transaction is pending, committed, invalid or unknown")
+            .args(&[Arg::with_name("txn-id").required(true).help(
+                "Transaction ID of the transaction to show the status of",
+            )])])
+}
+
+/// Handles parsing Clap CLI matches
+pub fn parse_cli<'a>(matches: (&'a str, Option<&'a ArgMatches>)) -> Result<(), Error> {
+    let client = &::client::Client::new()?;
+
+    match matches {
+        ("status", Some(m)) => {
+            let txn_id = m.value_of("txn-id").expect("Transaction ID is required!");
+
+            do_status(client, txn_id)?;
+        }
+        _ => unreachable!(),
+    }
+
+    Ok(())
+}
+
+/// Shows the status of the given transaction
+pub fn do_status(client: &Client, txn_id: &str) -> Result<(), Error> {
+    let status: TransactionStatus =
+        client.send_rpc_transaction("seth_getTransactionStatus", &vec![format!("0x{}", txn_id)])?;
+
+    println!(
+        "{}",
+        to_string_pretty(&json!({
+            "Status": status.status,
+            "BatchID": status.batch_id,
+            "Error": status.error,
+            "BlockID": status.block_hash,
+            "BlockNumber": status.block_number,
+        }))?
+    );
+
+    Ok(())
+}
@@ -29,6 +29,7 @@ use serde::ser::Serialize;
 use std::thread::sleep;
 use std::time::Duration;
 use time;
+use types::TransactionReceipt;
 
 /// Client settings
 #[derive(Debug, Serialize, Deserialize)]
@@ -158,4 +159,26 @@ impl Client {
 
         Err(format_err!("Got a null response from the RPC server!"))
     }
+
+    /// Waits for the receipt of a transaction
+    ///
+    /// Fails as soon as the receipt shows that the validator found the transaction invalid,
+    /// instead of waiting for a receipt that will never come.
+    pub fn wait_for_receipt(&self, txn_id: &str, wait: u64) -> Result<TransactionReceipt, Error> {
+        let receipt: TransactionReceipt =
+            self.wait_for_rpc_transaction("eth_getTransactionReceipt", &vec![txn_id], wait)?;
+
+        match receipt.status {
+            Some(ref status) if status == "0x0" => Err(format_err!(
+                "Transaction {} is invalid: {}",
+                txn_id,
+                receipt
+                    .error
+                    .as_ref()
+                    .map(String::as_str)
+                    .unwrap_or("unknown reason")
+            )),
+            _ => Ok(receipt),
+        }
+    }
 }
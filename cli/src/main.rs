@@ -31,6 +31,7 @@
 extern crate clap;
 extern crate config;
 extern crate dirs;
+extern crate ethereum_types;
 #[macro_use]
 extern crate failure;
 extern crate jsonrpc_core;
@@ -49,6 +50,7 @@ pub mod client;
 pub mod types;
 
 use clap::{App, AppSettings, Arg, ArgMatches};
+use ethereum_types::U256;
 use failure::Error;
 
 const DEFAULT_GAS: &str = "90000";
@@ -89,6 +91,36 @@ fn parse_wait_flag(matches: &ArgMatches) -> Result<Option<u64>, Error> {
     }
 }
 
+/// Used by every command that sends a transaction that can transfer Wei
+fn get_value_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("value")
+        .short("v")
+        .long("value")
+        .takes_value(true)
+        .help("Number of Wei to transfer, in decimal or as 0x-prefixed hex")
+}
+
+/// Parses an optional 256-bit quantity, given in decimal or as 0x-prefixed hex
+fn parse_value_flag(matches: &ArgMatches) -> Result<Option<U256>, Error> {
+    let val = match matches.value_of("value") {
+        Some(val) => val,
+        None => return Ok(None),
+    };
+    let value = if val.starts_with("0x") {
+        if val.len() > 66 {
+            None
+        } else {
+            val[2..].parse::<U256>().ok()
+        }
+    } else {
+        U256::from_dec_str(val).ok()
+    };
+    match value {
+        Some(value) => Ok(Some(value)),
+        None => Err(format_err!("Bad value for value: `{}`", val)),
+    }
+}
+
 fn run() -> Result<(), Error> {
     // Define CLI
     let matches = App::new("seth")
@@ -103,6 +135,7 @@ fn run() -> Result<(), Error> {
             cli::event::get_cli(),
             cli::permissions::get_cli(),
             cli::receipt::get_cli(),
+            cli::transaction::get_cli(),
         ])
         .get_matches();
 
@@ -113,6 +146,7 @@ fn run() -> Result<(), Error> {
         ("event", Some(em)) => cli::event::parse_cli(em.subcommand())?,
         ("permissions", Some(pm)) => cli::permissions::parse_cli(pm.subcommand())?,
         ("receipt", Some(rm)) => cli::receipt::parse_cli(rm.subcommand())?,
+        ("transaction", Some(tm)) => cli::transaction::parse_cli(tm.subcommand())?,
         _ => unreachable!(),
     }
 
@@ -37,12 +37,66 @@ pub struct TransactionLog {
 #[serde(rename_all = "camelCase")]
 pub struct TransactionReceipt {
     pub transaction_hash: String,
-    pub transaction_index: String,
-    pub block_hash: String,
-    pub block_number: String,
+    /// Not set for invalid transactions, which are never committed
+    pub transaction_index: Option<String>,
+    pub block_hash: Option<String>,
+    pub block_number: Option<String>,
     pub cumulative_gas_used: String,
     pub gas_used: String,
-    pub contract_address: String,
+    /// Not set for invalid transactions
+    pub contract_address: Option<String>,
     pub logs: Vec<TransactionLog>,
     pub return_value: String,
+    /// `0x1` if the transaction was committed and `0x0` if it was invalid
+    pub status: Option<String>,
+    /// Why the validator found the transaction invalid
+    pub error: Option<String>,
+}
+
+/// Where a Seth transaction is in its lifecycle
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionStatus {
+    pub transaction_id: String,
+    /// Only known for committed transactions and ones submitted through the same seth-rpc
+    pub batch_id: Option<String>,
+    /// One of `PENDING`, `COMMITTED`, `INVALID` or `UNKNOWN`
+    pub status: String,
+    /// Why the validator found the transaction invalid
+    pub error: Option<String>,
+    pub block_hash: Option<String>,
+    pub block_number: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TransactionReceipt;
+    use serde_json;
+
+    #[test]
+    fn parse_invalid_transaction_receipt() {
+        let receipt: TransactionReceipt = serde_json::from_str(
+            r#"{
+                "transactionHash": "0xab",
+                "transactionIndex": null,
+                "blockHash": null,
+                "blockNumber": null,
+                "cumulativeGasUsed": "0x0",
+                "gasUsed": "0x0",
+                "contractAddress": null,
+                "returnValue": "0x",
+                "logs": [],
+                "status": "0x0",
+                "error": "Out of gas"
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(None, receipt.contract_address);
+        assert_eq!(Some("0x0"), receipt.status.as_ref().map(String::as_str));
+        assert_eq!(
+            Some("Out of gas"),
+            receipt.error.as_ref().map(String::as_str)
+        );
+    }
 }
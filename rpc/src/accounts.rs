@@ -88,6 +88,7 @@ impl From<SigningError> for Error {
     }
 }
 
+/// Returns the default key directory, `~/.sawtooth/keys`
 pub fn get_key_dir() -> Option<PathBuf> {
     let home = home_dir()?;
 
@@ -95,8 +96,12 @@ pub fn get_key_dir() -> Option<PathBuf> {
 }
 
 impl Account {
-    pub fn load_from_file(key_name: &str, password: &Option<String>) -> Result<Account, Error> {
-        let mut key_path = get_key_dir().ok_or(Error::DirNotFound)?;
+    pub fn load_from_file(
+        key_dir: Option<PathBuf>,
+        key_name: &str,
+        password: &Option<String>,
+    ) -> Result<Account, Error> {
+        let mut key_path = key_dir.ok_or(Error::DirNotFound)?;
         key_path.push(key_name);
         let pem = key_path.with_extension("pem");
 
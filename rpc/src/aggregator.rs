@@ -0,0 +1,141 @@
+/*
+ * Copyright 2019 Cargill Incorporated
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! Collects the batches submitted by concurrent requests so that they can be sent to the
+//! validator in a single `ClientBatchSubmitRequest`.
+//!
+//! There is no background thread: the first request of a window becomes its leader, waits for
+//! the window to close or fill up, and submits every batch collected in it. The result of each
+//! batch is sent back to the request that added it.
+
+use client::Error;
+use sawtooth_sdk::messages::batch::Batch;
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone)]
+pub struct AggregatorConfig {
+    /// How long the leader of a window waits for other batches
+    pub window: Duration,
+    /// The number of batches that closes the window early
+    pub max_batches: usize,
+}
+
+/// A batch waiting to be submitted, and where to send the result of submitting it
+pub struct PendingBatch {
+    pub batch: Batch,
+    pub result: Sender<Result<(), Error>>,
+}
+
+#[derive(Clone)]
+pub struct BatchAggregator {
+    config: AggregatorConfig,
+    pending: Arc<Mutex<Vec<PendingBatch>>>,
+    full: Arc<Condvar>,
+}
+
+impl BatchAggregator {
+    pub fn new(config: AggregatorConfig) -> Self {
+        BatchAggregator {
+            config,
+            pending: Arc::new(Mutex::new(Vec::new())),
+            full: Arc::new(Condvar::new()),
+        }
+    }
+
+    /// Adds the batch to the current window. Returns true if the window was empty, in which
+    /// case the caller leads the window and must call `close_window`.
+    pub fn add(&self, batch: PendingBatch) -> bool {
+        let mut pending = self.pending.lock().unwrap();
+        let leader = pending.is_empty();
+        pending.push(batch);
+        if pending.len() >= self.config.max_batches {
+            self.full.notify_all();
+        }
+        leader
+    }
+
+    /// Waits until the window times out or is full, and returns the batches collected in it.
+    /// Batches added afterwards start a new window.
+    pub fn close_window(&self) -> Vec<PendingBatch> {
+        let deadline = Instant::now() + self.config.window;
+        let mut pending = self.pending.lock().unwrap();
+        while pending.len() < self.config.max_batches {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            pending = self.full.wait_timeout(pending, deadline - now).unwrap().0;
+        }
+        pending.drain(..).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AggregatorConfig, BatchAggregator, PendingBatch};
+    use sawtooth_sdk::messages::batch::Batch;
+    use std::sync::mpsc::channel;
+    use std::thread;
+    use std::time::Duration;
+
+    fn pending_batch(batch_id: &str) -> PendingBatch {
+        let mut batch = Batch::new();
+        batch.set_header_signature(String::from(batch_id));
+        PendingBatch {
+            batch,
+            result: channel().0,
+        }
+    }
+
+    #[test]
+    fn first_batch_leads_the_window() {
+        let aggregator = BatchAggregator::new(AggregatorConfig {
+            window: Duration::from_millis(10),
+            max_batches: 10,
+        });
+        assert!(aggregator.add(pending_batch("a")));
+        assert!(!aggregator.add(pending_batch("b")));
+
+        let window: Vec<String> = aggregator
+            .close_window()
+            .into_iter()
+            .map(|pending| pending.batch.header_signature)
+            .collect();
+        assert_eq!(vec!["a", "b"], window);
+
+        // The next batch opens a new window
+        assert!(aggregator.add(pending_batch("c")));
+    }
+
+    #[test]
+    fn full_window_closes_early() {
+        let aggregator = BatchAggregator::new(AggregatorConfig {
+            window: Duration::from_secs(60),
+            max_batches: 2,
+        });
+        assert!(aggregator.add(pending_batch("a")));
+
+        let follower = aggregator.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            follower.add(pending_batch("b"));
+        });
+        assert_eq!(2, aggregator.close_window().len());
+    }
+}
@@ -0,0 +1,520 @@
+/*
+ * Copyright 2019 Cargill Incorporated
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! Authenticates requests by the credential in their `Authorization` header, and checks that the
+//! credential may call the requested method.
+//!
+//! A credential is either a static API key or a JWT signed with a configured key. Each one has a
+//! policy listing the namespaces and methods it may call. Authentication is disabled if no
+//! credential is configured.
+
+use config::{read_error, Error, NAMESPACES};
+use crypto::util::fixed_time_eq;
+use jsonrpc_core::{Error as RpcError, ErrorCode, Metadata};
+use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// The error code of requests without valid credentials
+pub const UNAUTHORIZED: i64 = -32001;
+/// The error code of requests for methods their credential may not call
+pub const FORBIDDEN: i64 = -32003;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    /// The namespaces and methods that requests without credentials may call
+    pub anonymous: Vec<String>,
+    pub api_keys: Vec<ApiKeyConfig>,
+    pub jwt_keys: Vec<JwtKeyConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ApiKeyConfig {
+    /// The name of the key in logs
+    pub name: String,
+    pub key: String,
+    /// The namespaces and methods the key may call
+    pub allow: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum JwtAlgorithm {
+    HS256,
+    RS256,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct JwtKeyConfig {
+    /// The name of the key in logs
+    pub name: String,
+    pub algorithm: JwtAlgorithm,
+    /// The file of the HS256 secret, or of the PEM public key that verifies RS256 signatures
+    pub key_file: PathBuf,
+    /// The namespaces and methods that tokens signed with the key may call
+    pub allow: Vec<String>,
+}
+
+impl AuthConfig {
+    /// Returns true if requests must be authenticated
+    pub fn enabled(&self) -> bool {
+        !self.api_keys.is_empty() || !self.jwt_keys.is_empty()
+    }
+
+    /// Checks that every policy only names known namespaces
+    pub fn validate(&self) -> Result<(), String> {
+        let policies = self
+            .api_keys
+            .iter()
+            .map(|key| &key.allow)
+            .chain(self.jwt_keys.iter().map(|key| &key.allow))
+            .chain(Some(&self.anonymous));
+        for allowed in policies.flat_map(|allow| allow.iter()) {
+            let namespace = allowed.split('_').next().unwrap_or("");
+            if !NAMESPACES.contains(&namespace) {
+                return Err(format!(
+                    "Unknown namespace in `{}`, expected one of {}",
+                    allowed,
+                    NAMESPACES.join(", ")
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The namespaces and methods a credential may call
+#[derive(Debug, Clone, PartialEq)]
+pub struct Policy {
+    allow: Vec<String>,
+}
+
+impl Policy {
+    pub fn new(allow: Vec<String>) -> Self {
+        Policy { allow }
+    }
+
+    pub fn allows(&self, method: &str) -> bool {
+        let namespace = method.split('_').next().unwrap_or("");
+        self.allow
+            .iter()
+            .any(|allowed| allowed == method || allowed == namespace)
+    }
+}
+
+/// Verifies one kind of credential
+pub trait Authenticator: Send + Sync {
+    /// Returns the name and policy of the credential, if it is valid
+    fn authenticate(&self, token: &str) -> Option<(&str, &Policy)>;
+}
+
+struct ApiKey {
+    name: String,
+    key: String,
+    policy: Policy,
+}
+
+/// Accepts the configured API keys
+pub struct ApiKeyAuthenticator {
+    keys: Vec<ApiKey>,
+}
+
+impl ApiKeyAuthenticator {
+    pub fn new(config: &[ApiKeyConfig]) -> Self {
+        ApiKeyAuthenticator {
+            keys: config
+                .iter()
+                .map(|key| ApiKey {
+                    name: key.name.clone(),
+                    key: key.key.clone(),
+                    policy: Policy::new(key.allow.clone()),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Authenticator for ApiKeyAuthenticator {
+    fn authenticate(&self, token: &str) -> Option<(&str, &Policy)> {
+        self.keys
+            .iter()
+            .find(|key| fixed_time_eq(key.key.as_bytes(), token.as_bytes()))
+            .map(|key| (key.name.as_str(), &key.policy))
+    }
+}
+
+#[derive(Deserialize)]
+struct Claims {
+    sub: Option<String>,
+}
+
+/// Accepts the JWTs signed with a configured key that haven't expired
+pub struct JwtAuthenticator {
+    name: String,
+    key: DecodingKey<'static>,
+    validation: Validation,
+    policy: Policy,
+}
+
+impl JwtAuthenticator {
+    pub fn load(config: &JwtKeyConfig) -> Result<Self, Error> {
+        let key_file =
+            fs::read(&config.key_file).map_err(|error| read_error(&config.key_file, error))?;
+        let (key, algorithm) = match config.algorithm {
+            JwtAlgorithm::HS256 => (
+                DecodingKey::from_secret(trim_line_end(&key_file)).into_static(),
+                Algorithm::HS256,
+            ),
+            JwtAlgorithm::RS256 => (
+                DecodingKey::from_rsa_pem(&key_file)
+                    .map_err(|error| {
+                        Error::ParseError(format!(
+                            "Invalid RSA public key in {}: {}",
+                            config.key_file.display(),
+                            error
+                        ))
+                    })?
+                    .into_static(),
+                Algorithm::RS256,
+            ),
+        };
+        Ok(JwtAuthenticator {
+            name: config.name.clone(),
+            key,
+            validation: Validation::new(algorithm),
+            policy: Policy::new(config.allow.clone()),
+        })
+    }
+}
+
+impl Authenticator for JwtAuthenticator {
+    fn authenticate(&self, token: &str) -> Option<(&str, &Policy)> {
+        match decode::<Claims>(token, &self.key, &self.validation) {
+            Ok(data) => {
+                debug!(
+                    "Token of {:?} verified with `{}`",
+                    data.claims.sub, self.name
+                );
+                Some((self.name.as_str(), &self.policy))
+            }
+            Err(_) => None,
+        }
+    }
+}
+
+/// Drops the newline that usually ends a secret file
+fn trim_line_end(bytes: &[u8]) -> &[u8] {
+    let end = bytes
+        .iter()
+        .rposition(|byte| *byte != b'\n' && *byte != b'\r')
+        .map_or(0, |last| last + 1);
+    &bytes[..end]
+}
+
+/// The credential a request was sent with, and the address of the client if it is known
+#[derive(Debug, Clone, Default)]
+pub struct Credentials {
+    token: Option<String>,
+    address: Option<String>,
+}
+
+impl Metadata for Credentials {}
+
+impl Credentials {
+    pub fn anonymous() -> Self {
+        Credentials::default()
+    }
+
+    /// Reads the credential from the value of an `Authorization` header, which is either
+    /// `Bearer <token>` or the token alone
+    pub fn from_authorization(header: Option<&str>) -> Self {
+        let token = header.map(|header| {
+            let header = header.trim();
+            match header.find(' ') {
+                Some(space) if header[..space].eq_ignore_ascii_case("bearer") => {
+                    String::from(header[space..].trim())
+                }
+                _ => String::from(header),
+            }
+        });
+        Credentials {
+            token,
+            address: None,
+        }
+    }
+
+    pub fn with_address(self, address: Option<String>) -> Self {
+        Credentials { address, ..self }
+    }
+
+    pub fn address(&self) -> Option<&str> {
+        self.address.as_ref().map(String::as_str)
+    }
+}
+
+/// Checks the credentials of requests against the configured authenticators
+#[derive(Clone)]
+pub struct AccessControl {
+    authenticators: Arc<Vec<Box<dyn Authenticator>>>,
+    anonymous: Policy,
+}
+
+impl AccessControl {
+    /// Creates an access control that accepts the credentials of any of the authenticators, and
+    /// lets requests without credentials call what the anonymous policy allows. Every request is
+    /// allowed if there are no authenticators.
+    pub fn new(authenticators: Vec<Box<dyn Authenticator>>, anonymous: Policy) -> Self {
+        AccessControl {
+            authenticators: Arc::new(authenticators),
+            anonymous,
+        }
+    }
+
+    pub fn from_config(config: &AuthConfig) -> Result<Self, Error> {
+        let mut authenticators: Vec<Box<dyn Authenticator>> = Vec::new();
+        if !config.api_keys.is_empty() {
+            authenticators.push(Box::new(ApiKeyAuthenticator::new(&config.api_keys)));
+        }
+        for key in &config.jwt_keys {
+            authenticators.push(Box::new(JwtAuthenticator::load(key)?));
+        }
+        Ok(AccessControl::new(
+            authenticators,
+            Policy::new(config.anonymous.clone()),
+        ))
+    }
+
+    /// Returns an error if the credentials may not call the method. Otherwise, returns the name
+    /// of the credential, unless the request is anonymous.
+    pub fn check(
+        &self,
+        method: &str,
+        credentials: &Credentials,
+    ) -> Result<Option<String>, RpcError> {
+        if self.authenticators.is_empty() {
+            return Ok(None);
+        }
+
+        let token = match credentials.token {
+            Some(ref token) => token,
+            None if self.anonymous.allows(method) => return Ok(None),
+            None => return Err(auth_error(UNAUTHORIZED, "Missing credentials")),
+        };
+
+        let (name, policy) = self
+            .authenticators
+            .iter()
+            .find_map(|authenticator| authenticator.authenticate(token))
+            .ok_or_else(|| {
+                warn!("Rejected request for {} with invalid credentials", method);
+                auth_error(UNAUTHORIZED, "Invalid credentials")
+            })?;
+
+        if policy.allows(method) {
+            debug!("`{}` called {}", name, method);
+            Ok(Some(String::from(name)))
+        } else {
+            warn!("`{}` may not call {}", name, method);
+            Err(auth_error(FORBIDDEN, "Method not allowed"))
+        }
+    }
+}
+
+fn auth_error(code: i64, message: &str) -> RpcError {
+    RpcError {
+        code: ErrorCode::ServerError(code),
+        message: String::from(message),
+        data: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        AccessControl, ApiKeyConfig, AuthConfig, Credentials, JwtAlgorithm, JwtKeyConfig, Policy,
+        FORBIDDEN, UNAUTHORIZED,
+    };
+    use jsonrpc_core::ErrorCode;
+    use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    #[derive(Serialize)]
+    struct Claims {
+        sub: String,
+        exp: u64,
+    }
+
+    fn data_file(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/data/jwt")
+            .join(name)
+    }
+
+    fn allow(allowed: &[&str]) -> Vec<String> {
+        allowed
+            .iter()
+            .map(|allowed| String::from(*allowed))
+            .collect()
+    }
+
+    fn token(algorithm: Algorithm, key: &EncodingKey, exp: u64) -> Credentials {
+        let claims = Claims {
+            sub: String::from("wallet"),
+            exp,
+        };
+        let token = encode(&Header::new(algorithm), &claims, key).unwrap();
+        Credentials::from_authorization(Some(format!("Bearer {}", token).as_str()))
+    }
+
+    fn in_an_hour() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+            + 3600
+    }
+
+    fn error_code<T>(result: Result<T, ::jsonrpc_core::Error>) -> ErrorCode {
+        result.unwrap_err().code
+    }
+
+    fn jwt_access(algorithm: JwtAlgorithm, key_file: &str) -> AccessControl {
+        AccessControl::from_config(&AuthConfig {
+            jwt_keys: vec![JwtKeyConfig {
+                name: String::from("issuer"),
+                algorithm,
+                key_file: data_file(key_file),
+                allow: allow(&["eth", "personal_sign"]),
+            }],
+            ..AuthConfig::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn policy_allows_namespaces_and_methods() {
+        let policy = Policy::new(allow(&["eth", "personal_listAccounts"]));
+        assert!(policy.allows("eth_call"));
+        assert!(policy.allows("personal_listAccounts"));
+        assert!(!policy.allows("personal_newAccount"));
+        assert!(!policy.allows("ethx_call"));
+    }
+
+    #[test]
+    fn allow_everything_without_credentials_configured() {
+        let access = AccessControl::from_config(&AuthConfig::default()).unwrap();
+        assert!(access
+            .check("personal_newAccount", &Credentials::anonymous())
+            .is_ok());
+    }
+
+    #[test]
+    fn check_api_keys() {
+        let access = AccessControl::from_config(&AuthConfig {
+            anonymous: allow(&["net"]),
+            api_keys: vec![ApiKeyConfig {
+                name: String::from("dashboard"),
+                key: String::from("secret-key"),
+                allow: allow(&["eth"]),
+            }],
+            ..AuthConfig::default()
+        })
+        .unwrap();
+
+        let key = Credentials::from_authorization(Some("Bearer secret-key"));
+        assert_eq!(
+            Some(String::from("dashboard")),
+            access.check("eth_getBalance", &key).unwrap()
+        );
+        assert!(access
+            .check(
+                "eth_getBalance",
+                &Credentials::from_authorization(Some("secret-key"))
+            )
+            .is_ok());
+        assert_eq!(
+            ErrorCode::ServerError(FORBIDDEN),
+            error_code(access.check("seth_setPermissions", &key))
+        );
+
+        let anonymous = Credentials::anonymous();
+        assert_eq!(None, access.check("net_version", &anonymous).unwrap());
+        assert_eq!(
+            ErrorCode::ServerError(UNAUTHORIZED),
+            error_code(access.check("eth_getBalance", &anonymous))
+        );
+
+        let wrong_key = Credentials::from_authorization(Some("Bearer secret-kez"));
+        assert_eq!(
+            ErrorCode::ServerError(UNAUTHORIZED),
+            error_code(access.check("net_version", &wrong_key))
+        );
+    }
+
+    #[test]
+    fn check_hs256_tokens() {
+        let access = jwt_access(JwtAlgorithm::HS256, "hs256.secret");
+        let secret = EncodingKey::from_secret(b"seth-rpc test secret");
+
+        let valid = token(Algorithm::HS256, &secret, in_an_hour());
+        assert!(access.check("eth_call", &valid).is_ok());
+        assert!(access.check("personal_sign", &valid).is_ok());
+        assert_eq!(
+            ErrorCode::ServerError(FORBIDDEN),
+            error_code(access.check("personal_newAccount", &valid))
+        );
+
+        let expired = token(Algorithm::HS256, &secret, 1);
+        assert!(access.check("eth_call", &expired).is_err());
+
+        let wrong_secret = EncodingKey::from_secret(b"another secret");
+        let forged = token(Algorithm::HS256, &wrong_secret, in_an_hour());
+        assert!(access.check("eth_call", &forged).is_err());
+    }
+
+    #[test]
+    fn check_rs256_tokens() {
+        let access = jwt_access(JwtAlgorithm::RS256, "rs256.pub.pem");
+        let private_key =
+            EncodingKey::from_rsa_pem(&fs::read(data_file("rs256.key")).unwrap()).unwrap();
+        let valid = token(Algorithm::RS256, &private_key, in_an_hour());
+        assert!(access.check("eth_call", &valid).is_ok());
+
+        // A token can't pick another algorithm than the one of the key
+        let public_key = fs::read(data_file("rs256.pub.pem")).unwrap();
+        let confused = token(
+            Algorithm::HS256,
+            &EncodingKey::from_secret(&public_key),
+            in_an_hour(),
+        );
+        assert!(access.check("eth_call", &confused).is_err());
+    }
+
+    #[test]
+    fn reject_unknown_namespaces() {
+        let config = AuthConfig {
+            anonymous: allow(&["web3_clientVersion"]),
+            ..AuthConfig::default()
+        };
+        assert!(config.validate().is_err());
+    }
+}
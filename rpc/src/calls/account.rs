@@ -15,6 +15,7 @@
  * ------------------------------------------------------------------------------
  */
 
+use ethereum_types::U256;
 use jsonrpc_core::{Error, Params, Value};
 
 use client::{BlockKey, BlockKeyParseError, ValidatorClient};
@@ -61,15 +62,10 @@ fn validate_account_address(address: &str) -> Result<String, Error> {
     }
 }
 
-fn validate_storage_address(address: &str) -> Result<String, Error> {
-    if address.len() < 4 || address.len() % 2 != 0 {
-        Err(Error::invalid_params(format!(
-            "Invalid storage position: {}",
-            address
-        )))
-    } else {
-        Ok(String::from(&address[2..]))
-    }
+fn validate_storage_position(position: String) -> Result<U256, Error> {
+    transform::u256_from_hex_value(&Value::String(position)).map_err(|error| {
+        Error::invalid_params(format!("Invalid storage position: {}", error.message))
+    })
 }
 
 pub fn get_balance<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
@@ -78,7 +74,12 @@ where
 {
     info!("eth_getBalance");
     let balance = get_account(params, client, |account| {
-        transform::num_to_hex(&account.balance)
+        transform::u256_from_fields(&account.balance_u256, account.balance.max(0) as u64)
+            .map(|balance| transform::num_to_hex(&balance))
+            .ok_or_else(|| {
+                error!("Malformed balance: {:?}", account.balance_u256);
+                Error::internal_error()
+            })
     });
 
     match balance {
@@ -103,14 +104,14 @@ where
 
     let key = validate_block_key(&block)?;
     let account_address = validate_account_address(&address)?;
-    let storage_address = validate_storage_address(&position)?;
+    let position = validate_storage_position(position)?;
 
-    match client.get_storage_at(&account_address, &storage_address, key) {
+    match client.get_storage_at(&account_address, &position, key) {
         Ok(Some(value)) => Ok(transform::hex_prefix(&transform::bytes_to_hex_str(&value))),
         Ok(None) => Ok(Value::Null),
         Err(error) => {
             error!("{}", error);
-            Err(Error::internal_error())
+            Err(error.or_unavailable(Error::internal_error()))
         }
     }
 }
@@ -121,7 +122,9 @@ where
 {
     info!("eth_getCode");
     let code = get_account(params, client, |account| {
-        transform::hex_prefix(&transform::bytes_to_hex_str(&account.code))
+        Ok(transform::hex_prefix(&transform::bytes_to_hex_str(
+            &account.code,
+        )))
     });
 
     match code {
@@ -135,8 +138,14 @@ where
     T: MessageSender,
 {
     info!("eth_getTransactionCount");
+    if let Ok((address, block)) = params.clone().parse::<(String, String)>() {
+        if block == "pending" {
+            return get_pending_transaction_count(&client, &address);
+        }
+    }
+
     let nonce = get_account(params, client, |account| {
-        transform::num_to_hex(&account.nonce)
+        Ok(transform::num_to_hex(&account.nonce))
     });
 
     // The transaction count of a non-existent address is 0, not null
@@ -146,10 +155,37 @@ where
     }
 }
 
+/// Returns the nonce the next transaction from the account will have, counting the transactions
+/// submitted through this client that aren't committed yet
+fn get_pending_transaction_count<T>(
+    client: &ValidatorClient<T>,
+    address: &str,
+) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    let address = validate_account_address(address)?;
+    let state_nonce = match client.get_account(&address, BlockKey::Latest) {
+        Ok(account) => account.map(|account| account.nonce).unwrap_or(0),
+        Err(error) => {
+            error!("{}", error);
+            return Err(error.or_unavailable(Error::internal_error()));
+        }
+    };
+
+    client
+        .get_pending_nonce(&address, state_nonce)
+        .map(|nonce| transform::num_to_hex(&nonce))
+        .map_err(|error| {
+            error!("Error getting pending nonce of `{}`: {}", address, error);
+            error.or_unavailable(Error::internal_error())
+        })
+}
+
 fn get_account<T, F>(params: Params, client: ValidatorClient<T>, f: F) -> Result<Value, Error>
 where
     T: MessageSender,
-    F: Fn(EvmStateAccount) -> Value,
+    F: Fn(EvmStateAccount) -> Result<Value, Error>,
 {
     info!("eth_getAccount");
     let (address, block): (String, String) = match params.parse() {
@@ -165,11 +201,11 @@ where
     let address = validate_account_address(&address)?;
 
     match client.get_account(&address, key) {
-        Ok(Some(account)) => Ok(f(account)),
+        Ok(Some(account)) => f(account),
         Ok(None) => Ok(Value::Null),
         Err(error) => {
             error!("{}", error);
-            Err(Error::internal_error())
+            Err(error.or_unavailable(Error::internal_error()))
         }
     }
 }
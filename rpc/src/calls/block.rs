@@ -19,7 +19,7 @@ use client::{BlockKey, Error as ClientError, ValidatorClient};
 use jsonrpc_core::{Error, Params, Value};
 use protobuf;
 use requests::RequestHandler;
-use sawtooth_sdk::messages::block::BlockHeader;
+use sawtooth_sdk::messages::block::{Block, BlockHeader};
 use sawtooth_sdk::messaging::stream::*;
 use serde_json::Map;
 use std::str::FromStr;
@@ -58,7 +58,7 @@ where
 
     let block = client.get_current_block().map_err(|err| {
         error!("Error requesting block: {:?}", err);
-        Error::internal_error()
+        err.or_unavailable(Error::internal_error())
     })?;
 
     let block_header: BlockHeader = match protobuf::parse_from_bytes(&block.header) {
@@ -204,7 +204,7 @@ where
             }
             _ => {
                 error!("{:?}", error);
-                return Err(Error::internal_error());
+                return Err(error.or_unavailable(Error::internal_error()));
             }
         },
     };
@@ -217,29 +217,11 @@ where
         }
     };
 
-    let mut bob = Map::new();
-    bob.insert(
-        String::from("number"),
-        transform::num_to_hex(&block_header.block_num),
-    );
-    bob.insert(
-        String::from("hash"),
-        transform::hex_prefix(&block.header_signature),
-    );
-    bob.insert(
-        String::from("parentHash"),
-        transform::hex_prefix(&block_header.previous_block_id),
-    );
-    bob.insert(
-        String::from("stateRoot"),
-        transform::hex_prefix(&block_header.state_root_hash),
-    );
-
     let receipts = match client.get_receipts_from_block(&block) {
         Ok(r) => r,
         Err(error) => {
             error!("Error getting receipts: {:?}", error);
-            return Err(Error::internal_error());
+            return Err(error.or_unavailable(Error::internal_error()));
         }
     };
 
@@ -252,7 +234,7 @@ where
                     Ok(t) => t,
                     Err(error) => {
                         error!("Error getting transactions: {:?}", error);
-                        return Err(Error::internal_error());
+                        return Err(error.or_unavailable(Error::internal_error()));
                     }
                 };
             transactions.push(make_txn_obj_no_block(&txn))
@@ -261,8 +243,36 @@ where
         }
         gas += receipt.gas_used;
     }
+    let mut bob = make_block_header_obj(&block, &block_header, gas);
     bob.insert(String::from("transactions"), Value::Array(transactions));
-    bob.insert(String::from("gasUsed"), transform::num_to_hex(&gas));
+
+    Ok(Value::Object(bob))
+}
+
+/// Returns the header fields of a block object, which is what `newHeads` subscribers receive
+pub fn make_block_header_obj(
+    block: &Block,
+    block_header: &BlockHeader,
+    gas_used: u64,
+) -> Map<String, Value> {
+    let mut bob = Map::new();
+    bob.insert(
+        String::from("number"),
+        transform::num_to_hex(&block_header.block_num),
+    );
+    bob.insert(
+        String::from("hash"),
+        transform::hex_prefix(&block.header_signature),
+    );
+    bob.insert(
+        String::from("parentHash"),
+        transform::hex_prefix(&block_header.previous_block_id),
+    );
+    bob.insert(
+        String::from("stateRoot"),
+        transform::hex_prefix(&block_header.state_root_hash),
+    );
+    bob.insert(String::from("gasUsed"), transform::num_to_hex(&gas_used));
 
     // No corollaries in Sawtooth
     bob.insert(String::from("nonce"), transform::zerobytes(8));
@@ -278,7 +288,7 @@ where
     bob.insert(String::from("gasLimit"), transform::zerobytes(0));
     bob.insert(String::from("uncles"), Value::Array(Vec::new()));
 
-    Ok(Value::Object(bob))
+    bob
 }
 
 /// Returns the number of transactions for the given block as a hex string
@@ -297,7 +307,7 @@ where
             }
             _ => {
                 error!("{:?}", error);
-                return Err(Error::internal_error());
+                return Err(error.or_unavailable(Error::internal_error()));
             }
         },
     };
@@ -0,0 +1,224 @@
+/*
+ * Copyright 2019 Cargill Incorporated
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+use client::{BlockKey, Error as ClientError, ValidatorClient};
+use evm::tracer::{
+    CallTracer, StructLogger, StructLoggerConfig, MAX_CAPTURED_BYTES, MAX_STRUCT_LOGS,
+};
+use evm::{self, Address, Backend, BlockEnv, Evm, State, ValidatorBackend};
+use jsonrpc_core::{Error, ErrorCode, Params, Value};
+use protobuf;
+use requests::RequestHandler;
+use sawtooth_sdk::messages::block::BlockHeader;
+use sawtooth_sdk::messages::transaction::TransactionHeader;
+use sawtooth_sdk::messaging::stream::MessageSender;
+use transaction::parse_call_params;
+use transactions::{Transaction, TransactionKey};
+use transform;
+
+pub fn get_method_list<T>() -> Vec<(String, RequestHandler<T>)>
+where
+    T: MessageSender,
+{
+    vec![
+        ("debug_traceCall".into(), trace_call),
+        ("debug_traceTransaction".into(), trace_transaction),
+    ]
+}
+
+/// Which tracer to trace a transaction with
+enum TraceConfig {
+    StructLogger(StructLoggerConfig),
+    CallTracer,
+}
+
+pub fn trace_transaction<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("debug_traceTransaction");
+    let usage = "Takes [txnHash: DATA(64), options: OBJECT]";
+    let mut args: Vec<Value> = params.parse().map_err(|_| Error::invalid_params(usage))?;
+    let config = match args.len() {
+        1 => parse_trace_config(None)?,
+        2 => parse_trace_config(args.pop())?,
+        _ => return Err(Error::invalid_params(usage)),
+    };
+    let txn_id = match args.remove(0) {
+        Value::String(txn_id) => txn_id
+            .get(2..)
+            .map(String::from)
+            .ok_or_else(|| Error::invalid_params("Invalid transaction hash, must have 0x"))?,
+        _ => return Err(Error::invalid_params(usage)),
+    };
+
+    let (txn, block) =
+        match client.get_transaction_and_block(&TransactionKey::Signature(txn_id.clone())) {
+            Ok((txn, Some(block))) => (txn, block),
+            Ok((_, None)) => {
+                return Err(Error::invalid_params("Transaction is not in a block yet"));
+            }
+            Err(ClientError::NoResource) => return Ok(Value::Null),
+            Err(error) => {
+                error!("Error getting transaction `{}`: {}", txn_id, error);
+                return Err(error.or_unavailable(Error::internal_error()));
+            }
+        };
+    let block_header: BlockHeader = protobuf::parse_from_bytes(&block.header).map_err(|error| {
+        error!("Error parsing block header: {}", error);
+        Error::internal_error()
+    })?;
+
+    // The block info the processor gave the EVM was written to state by the block itself, but
+    // accounts are read from the state the block was built on
+    let env = ValidatorBackend::new(&client, BlockKey::Signature(block.header_signature.clone()))
+        .and_then(|backend| BlockEnv::load(&backend))
+        .map_err(trace_error)?;
+    let backend = ValidatorBackend::new(
+        &client,
+        BlockKey::Signature(block_header.previous_block_id.clone()),
+    )
+    .map_err(trace_error)?;
+    let mut state = State::new(backend);
+
+    // Replay the Seth transactions that came before this one in the block
+    for preceding in block
+        .get_batches()
+        .iter()
+        .flat_map(|batch| batch.get_transactions().iter())
+        .take_while(|preceding| preceding.header_signature != txn_id)
+    {
+        let header: TransactionHeader =
+            protobuf::parse_from_bytes(&preceding.header).map_err(|error| {
+                error!("Error parsing transaction header: {}", error);
+                Error::internal_error()
+            })?;
+        if header.family_name != "seth" {
+            continue;
+        }
+        let preceding = Transaction::try_from(preceding.clone()).map_err(|error| {
+            error!("Error parsing transaction: {}", error);
+            Error::internal_error()
+        })?;
+        evm::apply(
+            &mut state,
+            &env,
+            sender_address(&preceding),
+            preceding.seth_transaction(),
+        )
+        .map_err(trace_error)?;
+    }
+
+    let txn = evm::Transaction::from_seth(sender_address(&txn), txn.seth_transaction())
+        .ok_or_else(|| {
+            Error::invalid_params("Only message calls and contract creations can be traced")
+        })?;
+    trace(&mut state, &env, &txn, &config)
+}
+
+pub fn trace_call<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("debug_traceCall");
+    let usage = "Takes [txn: OBJECT, block: QUANTITY|TAG, options: OBJECT]";
+    let mut args: Vec<Value> = params.parse().map_err(|_| Error::invalid_params(usage))?;
+    let config = match args.len() {
+        1 | 2 => parse_trace_config(None)?,
+        3 => parse_trace_config(args.pop())?,
+        _ => return Err(Error::invalid_params(usage)),
+    };
+    let (txn, block) = parse_call_params(Params::Array(args), &client)?;
+
+    let backend = ValidatorBackend::new(&client, block).map_err(trace_error)?;
+    let env = BlockEnv::load(&backend).map_err(trace_error)?;
+    let mut state = State::new(backend);
+    trace(&mut state, &env, &txn, &config)
+}
+
+/// Parses the tracing options. Without a `tracer`, the instructions are traced the way geth's
+/// struct logger does, and `callTracer` traces the calls made instead.
+fn parse_trace_config(options: Option<Value>) -> Result<TraceConfig, Error> {
+    let options = match options {
+        None | Some(Value::Null) => return Ok(TraceConfig::StructLogger(Default::default())),
+        Some(Value::Object(options)) => options,
+        Some(_) => return Err(Error::invalid_params("Trace options must be an object")),
+    };
+
+    match options.get("tracer") {
+        None => {}
+        Some(&Value::String(ref tracer)) if tracer == "callTracer" => {
+            return Ok(TraceConfig::CallTracer);
+        }
+        Some(_) => return Err(Error::invalid_params("Only `callTracer` is supported")),
+    }
+
+    let flag = |key: &str| options.get(key).and_then(Value::as_bool).unwrap_or(false);
+    Ok(TraceConfig::StructLogger(StructLoggerConfig {
+        disable_stack: flag("disableStack"),
+        disable_memory: flag("disableMemory"),
+        disable_storage: flag("disableStorage"),
+    }))
+}
+
+fn trace<B: Backend>(
+    state: &mut State<B>,
+    env: &BlockEnv,
+    txn: &evm::Transaction,
+    config: &TraceConfig,
+) -> Result<Value, Error> {
+    match *config {
+        TraceConfig::StructLogger(ref config) => {
+            let mut logger = StructLogger::new(config.clone());
+            let result = Evm::new(state, env)
+                .with_tracer(&mut logger)
+                .transact(txn)
+                .map_err(trace_error)?;
+            if logger.truncated() {
+                return Err(Error {
+                    code: ErrorCode::ServerError(-32000),
+                    message: format!(
+                        "The trace exceeds {} instructions or {} bytes; disable the stack, \
+                         memory or storage, or use callTracer",
+                        MAX_STRUCT_LOGS, MAX_CAPTURED_BYTES
+                    ),
+                    data: None,
+                });
+            }
+            Ok(logger.into_trace(&result))
+        }
+        TraceConfig::CallTracer => {
+            let mut tracer = CallTracer::new();
+            Evm::new(state, env)
+                .with_tracer(&mut tracer)
+                .transact(txn)
+                .map_err(trace_error)?;
+            Ok(tracer.into_trace())
+        }
+    }
+}
+
+fn sender_address(txn: &Transaction) -> Address {
+    transform::hex_str_to_bytes(&txn.from_addr())
+        .map(|address| Address::from_slice(&address))
+        .unwrap_or_else(Address::zero)
+}
+
+fn trace_error(error: evm::Error) -> Error {
+    error!("Failed to trace transaction: {}", error);
+    error.or_unavailable(Error::internal_error())
+}
@@ -15,7 +15,9 @@
  * ------------------------------------------------------------------------------
  */
 
+use evm::VmError;
 use jsonrpc_core::{Error, ErrorCode};
+use transform;
 
 pub fn not_implemented() -> Error {
     Error {
@@ -24,3 +26,46 @@ pub fn not_implemented() -> Error {
         data: None,
     }
 }
+
+/// The selector of `Error(string)`, which Solidity uses to encode revert reasons
+const REVERT_REASON_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+
+/// The error returned when a simulated call reverts. Like geth, the revert data is returned as
+/// the error data and a Solidity revert reason is included in the message.
+pub fn execution_reverted(output: &[u8]) -> Error {
+    let message = match decode_revert_reason(output) {
+        Some(reason) => format!("execution reverted: {}", reason),
+        None => String::from("execution reverted"),
+    };
+    Error {
+        code: ErrorCode::ServerError(3),
+        message,
+        data: Some(transform::hex_prefix(&transform::bytes_to_hex_str(output))),
+    }
+}
+
+/// The error returned when a simulated call fails without reverting, e.g. by running out of gas
+pub fn execution_failed(error: &VmError) -> Error {
+    Error {
+        code: ErrorCode::ServerError(-32000),
+        message: format!("{}", error),
+        data: None,
+    }
+}
+
+fn decode_revert_reason(output: &[u8]) -> Option<String> {
+    if output.len() < 68 || output[..4] != REVERT_REASON_SELECTOR {
+        return None;
+    }
+    // The string is encoded as an offset, a length and the padded bytes
+    if output[36..60].iter().any(|byte| *byte != 0) {
+        return None;
+    }
+    let len = output[60..68]
+        .iter()
+        .fold(0u64, |len, byte| (len << 8) | u64::from(*byte)) as usize;
+    68usize
+        .checked_add(len)
+        .and_then(|end| output.get(68..end))
+        .and_then(|reason| String::from_utf8(reason.to_vec()).ok())
+}
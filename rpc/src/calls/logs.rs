@@ -26,7 +26,7 @@ use sawtooth_sdk::messages::transaction::TransactionHeader;
 use sawtooth_sdk::messaging::stream::MessageSender;
 use serde_json::Map;
 use std::collections::HashMap;
-use transactions::SethLog;
+use transactions::{SethLog, SethReceipt};
 use transform;
 use transform::make_log_obj;
 
@@ -58,14 +58,7 @@ where
         .map_err(|_| Error::invalid_params("Takes [filter: OBJECT]"))?;
     let log_filter = LogFilter::from_map(&filter)?;
 
-    let current_block = client.get_current_block_number().map_err(|error| {
-        error!("Failed to get current block number: {}", error);
-        Error::internal_error()
-    })?;
-
-    let filter_id = client
-        .filters
-        .new_filter(Filter::Log(log_filter), current_block);
+    let filter_id = client.filters.new_filter(Filter::Log(log_filter));
 
     Ok(transform::hex_prefix(&filter_id_to_hex(filter_id)))
 }
@@ -75,11 +68,7 @@ where
     T: MessageSender,
 {
     info!("eth_newBlockFilter");
-    let current_block = client.get_current_block_number().map_err(|error| {
-        error!("Failed to get current block number: {}", error);
-        Error::internal_error()
-    })?;
-    let filter_id = client.filters.new_filter(Filter::Block, current_block);
+    let filter_id = client.filters.new_filter(Filter::Block);
     Ok(transform::hex_prefix(&filter_id_to_hex(filter_id)))
 }
 
@@ -91,13 +80,7 @@ where
     T: MessageSender,
 {
     info!("eth_newPendingTransactionFilter");
-    let current_block = client.get_current_block_number().map_err(|error| {
-        error!("Failed to get current block number: {}", error);
-        Error::internal_error()
-    })?;
-    let filter_id = client
-        .filters
-        .new_filter(Filter::Transaction, current_block);
+    let filter_id = client.filters.new_filter(Filter::Transaction);
     Ok(transform::hex_prefix(&filter_id_to_hex(filter_id)))
 }
 
@@ -135,61 +118,13 @@ where
             filter_id_from_hex(&s).map_err(|error| Error::invalid_params(format!("{}", error)))
         })?;
 
-    let FilterEntry {
-        filter,
-        last_block_sent,
-    } = client
+    // Changes are buffered by the block follower as blocks are committed
+    let changes = client
         .filters
-        .get_filter(filter_id)
-        .ok_or_else(|| Error::invalid_params(format!("Unknown filter id: {}", filter_id)))?;
-
-    let blocks = client.get_blocks_since(last_block_sent).map_err(|error| {
-        error!("Failed to get blocks: {}", error);
-        Error::internal_error()
-    })?;
-
-    let response = match filter {
-        Filter::Block => blocks
-            .iter()
-            .map(|&(_, ref block)| transform::hex_prefix(&block.header_signature))
-            .collect(),
-        Filter::Transaction => blocks
-            .iter()
-            .flat_map(|&(_, ref block)| {
-                block.get_batches().iter().flat_map(|batch| {
-                    batch
-                        .get_transactions()
-                        .iter()
-                        .filter(|txn| {
-                            let header: Result<TransactionHeader, _> =
-                                protobuf::parse_from_bytes(&txn.header);
-                            if let Ok(header) = header {
-                                header.family_name == "seth"
-                            } else {
-                                false
-                            }
-                        })
-                        .map(|txn| transform::hex_prefix(&txn.header_signature))
-                })
-            })
-            .collect(),
-        Filter::Log(log_filter) => {
-            let mut all_logs = Vec::new();
-            for &(_, ref block) in &blocks {
-                let logs = get_logs_from_block_and_filter(&client, block, &log_filter)?;
-                all_logs.extend(logs.into_iter());
-            }
-            all_logs
-        }
-    };
-
-    // NOTE: Updating is delayed until there are no more error sources that could cause an early
-    // return after upadting the filter
-    if let Some(&(block_num, _)) = blocks.last() {
-        client.filters.update_latest_block(filter_id, block_num);
-    }
+        .take_changes(filter_id)
+        .ok_or_else(|| Error::invalid_params(format!("Unknown filter id: {}", filter_id)))??;
 
-    Ok(Value::Array(response))
+    Ok(Value::Array(changes))
 }
 
 pub fn get_filter_logs<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
@@ -248,7 +183,7 @@ where
         (None, None) => {
             let block = client.get_current_block().map_err(|e| {
                 error!("Unable to get current block: {:?}", e);
-                Error::internal_error()
+                e.or_unavailable(Error::internal_error())
             })?;
 
             let logs = get_logs_from_block_and_filter(client, &block, &log_filter)?;
@@ -270,7 +205,7 @@ where
                     }
                     Err(error) => {
                         error!("{}", error);
-                        return Err(Error::internal_error());
+                        return Err(error.or_unavailable(Error::internal_error()));
                     }
                 }
                 block_index += 1;
@@ -291,7 +226,7 @@ where
                     }
                     Err(error) => {
                         error!("{}", error);
-                        return Err(Error::internal_error());
+                        return Err(error.or_unavailable(Error::internal_error()));
                     }
                 }
             }
@@ -302,6 +237,29 @@ where
     }
 }
 
+/// Returns the ids of all seth transactions in the block
+pub fn get_seth_transaction_ids(block: &Block) -> Vec<Value> {
+    block
+        .get_batches()
+        .iter()
+        .flat_map(|batch| {
+            batch
+                .get_transactions()
+                .iter()
+                .filter(|txn| {
+                    let header: Result<TransactionHeader, _> =
+                        protobuf::parse_from_bytes(&txn.header);
+                    if let Ok(header) = header {
+                        header.family_name == "seth"
+                    } else {
+                        false
+                    }
+                })
+                .map(|txn| transform::hex_prefix(&txn.header_signature))
+        })
+        .collect()
+}
+
 fn get_logs_from_block_and_filter<T>(
     client: &ValidatorClient<T>,
     block: &Block,
@@ -310,33 +268,42 @@ fn get_logs_from_block_and_filter<T>(
 where
     T: MessageSender,
 {
-    let block_header: BlockHeader = protobuf::parse_from_bytes(&block.header).map_err(|e| {
-        error!("Error parsing block header: {:?}", e);
-        Error::internal_error()
-    })?;
-
     // Get receipts (which have logs in them)
     let receipts = client.get_receipts_from_block(&block).map_err(|error| {
         error!("Unable to get receipts for current block: {}", error);
+        error.or_unavailable(Error::internal_error())
+    })?;
+
+    get_logs_from_receipts_and_filter(block, &receipts, log_filter)
+}
+
+/// Returns the log objects of the given block's receipts that pass the filter
+pub fn get_logs_from_receipts_and_filter(
+    block: &Block,
+    receipts: &HashMap<String, SethReceipt>,
+    log_filter: &LogFilter,
+) -> Result<Vec<Value>, Error> {
+    let block_header: BlockHeader = protobuf::parse_from_bytes(&block.header).map_err(|e| {
+        error!("Error parsing block header: {:?}", e);
         Error::internal_error()
     })?;
 
-    warn!("LogFilter: {:?}", log_filter);
+    debug!("LogFilter: {:?}", log_filter);
     // Filter logs
-    let logs: HashMap<String, Vec<SethLog>> = receipts
-        .into_iter()
+    let logs: HashMap<&String, Vec<&SethLog>> = receipts
+        .iter()
         .map(|(txn_id, receipt)| {
-            warn!("Logs: {:?}", receipt.logs);
-            let logs: Vec<SethLog> = receipt
+            debug!("Logs: {:?}", receipt.logs);
+            let logs: Vec<&SethLog> = receipt
                 .logs
-                .into_iter()
-                .filter(|log: &SethLog| log_filter.contains(&log, None))
+                .iter()
+                .filter(|log| log_filter.contains(log, None))
                 .collect();
             (txn_id, logs)
         })
         .filter(|&(_, ref logs)| !logs.is_empty())
         .collect();
-    warn!("Filtered Logs: {:?}", logs);
+    debug!("Filtered Logs: {:?}", logs);
 
     // Contextual data for logs
     let block_id = block.get_header_signature();
@@ -351,7 +318,7 @@ where
     for (txn_id, logs) in logs {
         let index = transactions
             .iter()
-            .position(|txn| txn.header_signature == txn_id)
+            .position(|txn| &txn.header_signature == txn_id)
             .ok_or_else(|| {
                 error!(
                     "Failed to find index of txn `{}` in block `{}`",
@@ -359,8 +326,8 @@ where
                 );
                 Error::internal_error()
             })?;
-        for log in &logs {
-            let log_obj = make_log_obj(log, &txn_id, index as u64, block_id, block_num);
+        for log in logs {
+            let log_obj = make_log_obj(log, txn_id, index as u64, block_id, block_num);
             log_objects.push(log_obj);
         }
     }
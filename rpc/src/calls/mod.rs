@@ -18,9 +18,11 @@
 
 pub mod account;
 pub mod block;
+pub mod debug;
 pub mod error;
 pub mod logs;
 pub mod network;
 pub mod personal;
+pub mod pubsub;
 pub mod seth;
 pub mod transaction;
@@ -19,11 +19,10 @@ use jsonrpc_core::{Error, Params, Value};
 
 use client::ValidatorClient;
 use requests::RequestHandler;
+use transform;
 
 use sawtooth_sdk::messaging::stream::MessageSender;
 
-const SAWTOOTH_NET_VERSION: &str = "19";
-
 pub fn get_method_list<T>() -> Vec<(String, RequestHandler<T>)>
 where
     T: MessageSender,
@@ -32,16 +31,37 @@ where
         ("net_version".into(), version),
         ("net_peerCount".into(), peer_count),
         ("net_listening".into(), listening),
+        ("eth_chainId".into(), chain_id),
     ]
 }
 
-// Version refers to the particular network this JSON-RPC client is connected to
-pub fn version<T>(_params: Params, _client: ValidatorClient<T>) -> Result<Value, Error>
+// Version refers to the particular network this JSON-RPC client is connected to, which is
+// identified by its chain id
+pub fn version<T>(_params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
 where
     T: MessageSender,
 {
     info!("net_version");
-    Ok(Value::String(String::from(SAWTOOTH_NET_VERSION)))
+    get_chain_id(&client).map(|chain_id| Value::String(chain_id.to_string()))
+}
+
+/// Returns the chain id that EIP-155 transactions must be signed for, as a quantity
+pub fn chain_id<T>(_params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("eth_chainId");
+    get_chain_id(&client).map(|chain_id| transform::num_to_hex(&chain_id))
+}
+
+pub fn get_chain_id<T>(client: &ValidatorClient<T>) -> Result<u64, Error>
+where
+    T: MessageSender,
+{
+    client.chain_id().map_err(|error| {
+        error!("{}", error);
+        error.or_unavailable(Error::internal_error())
+    })
 }
 
 // Return the number of actual Sawtooth peers
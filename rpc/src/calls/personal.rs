@@ -15,9 +15,9 @@
  * ------------------------------------------------------------------------------
  */
 
-use accounts::{get_key_dir, Account};
+use accounts::Account;
 use client::BlockKey;
-use client::ValidatorClient;
+use client::{TransactionOptions, ValidatorClient};
 use jsonrpc_core::{Error, Params, Value};
 use messages::seth::{
     CreateExternalAccountTxn, EvmPermissions, SethTransaction as SethTransactionPb,
@@ -113,7 +113,10 @@ where
     let account = Account::load_from_str(&pem_bytes, password)
         .map_err(|err| fail!("Error generating key", err))?;
 
-    let mut filename = get_key_dir().ok_or_else(Error::internal_error)?;
+    let mut filename = client
+        .config()
+        .key_dir()
+        .ok_or_else(Error::internal_error)?;
     filename.push(account.address());
 
     fs::write(filename.with_extension("pem"), pem_bytes)
@@ -130,7 +133,7 @@ where
     let nonce = match client.get_account(&sender, BlockKey::Latest) {
         Ok(Some(account)) => Ok(account.nonce),
         Ok(None) => Ok(0),
-        Err(err) => Err(fail!("Couldn't get account", err)),
+        Err(err) => Err(err.or_unavailable(fail!("Couldn't get account", err))),
     }?;
 
     // Create and send the transaction in for processing
@@ -161,8 +164,9 @@ where
         .send_transaction(
             &sender,
             &SethTransaction::try_from(txn).ok_or_else(Error::internal_error)?,
+            &TransactionOptions::default(),
         )
-        .map_err(|err| fail!("Error sending transaction", err))?;
+        .map_err(|err| err.or_unavailable(fail!("Error sending transaction", err)))?;
 
     client
         .unlock_account(&account, Some(0))
@@ -221,7 +225,10 @@ where
     let account = Account::load_from_str(&pem_str, &password)
         .map_err(|err| fail!("Error loading account from key", err))?;
 
-    let mut filename = get_key_dir().ok_or_else(Error::internal_error)?;
+    let mut filename = client
+        .config()
+        .key_dir()
+        .ok_or_else(Error::internal_error)?;
     filename.push(account.address());
 
     fs::write(filename.with_extension("pem"), pem_str)
@@ -241,8 +248,9 @@ where
         .send_transaction(
             &account.address(),
             &SethTransaction::try_from(txn).ok_or_else(Error::internal_error)?,
+            &TransactionOptions::default(),
         )
-        .map_err(|err| fail!("Error sending transaction", err))?;
+        .map_err(|err| err.or_unavailable(fail!("Error sending transaction", err)))?;
 
     client
         .unlock_account(&account, Some(0))
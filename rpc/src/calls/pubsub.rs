@@ -0,0 +1,141 @@
+/*
+ * Copyright 2019 Cargill Incorporated
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+use block::make_block_header_obj;
+use client::ValidatorClient;
+use filters::LogFilter;
+use jsonrpc_core::{Error, Params, Value};
+use jsonrpc_pubsub::{Subscriber, SubscriptionId};
+use logs::{get_logs_from_receipts_and_filter, get_seth_transaction_ids};
+use sawtooth_sdk::messages::block::{Block, BlockHeader};
+use sawtooth_sdk::messaging::stream::MessageSender;
+use serde_json::Map;
+use std::collections::HashMap;
+use subscriptions::Subscription;
+use transactions::SethReceipt;
+
+/// Endpoint that creates a new subscription on the current WebSocket connection.
+///
+/// https://geth.ethereum.org/docs/rpc/pubsub
+/// Supports `newHeads`, `logs` and `newPendingTransactions`. Seth transactions are not
+/// observable before they are committed, so `newPendingTransactions` reports the seth
+/// transactions of each new block.
+pub fn subscribe<T>(params: Params, client: &ValidatorClient<T>, subscriber: Subscriber)
+where
+    T: MessageSender,
+{
+    info!("eth_subscribe");
+    let subscription = match parse_subscription(params) {
+        Ok(subscription) => subscription,
+        Err(error) => {
+            let _ = subscriber.reject(error);
+            return;
+        }
+    };
+
+    let id = client.subscriptions.next_id();
+    if let Ok(sink) = subscriber.assign_id(id.clone()) {
+        client
+            .subscriptions
+            .add_subscription(id, subscription, sink);
+    }
+}
+
+/// Endpoint that cancels a subscription. Also called when the connection is closed.
+pub fn unsubscribe<T>(id: SubscriptionId, client: &ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("eth_unsubscribe");
+    Ok(Value::Bool(
+        client.subscriptions.remove_subscription(&id).is_some(),
+    ))
+}
+
+fn parse_subscription(params: Params) -> Result<Subscription, Error> {
+    let usage = "Takes [kind: STRING, filter: OBJECT]";
+    let mut args: Vec<Value> = params.parse().map_err(|_| Error::invalid_params(usage))?;
+    let filter = match args.len() {
+        1 => None,
+        2 => Some(args.remove(1)),
+        _ => return Err(Error::invalid_params(usage)),
+    };
+
+    match (args[0].as_str(), filter) {
+        (Some("newHeads"), None) => Ok(Subscription::NewHeads),
+        (Some("newPendingTransactions"), None) => Ok(Subscription::NewPendingTransactions),
+        (Some("logs"), Some(Value::Object(filter))) => {
+            LogFilter::from_map(&filter).map(Subscription::Logs)
+        }
+        (Some("logs"), None) => LogFilter::from_map(&Map::new()).map(Subscription::Logs),
+        (Some("logs"), Some(_)) => Err(Error::invalid_params("Invalid log filter")),
+        (Some(kind), _) => Err(Error::invalid_params(format!(
+            "Unsupported subscription: {}",
+            kind
+        ))),
+        (None, _) => Err(Error::invalid_params(usage)),
+    }
+}
+
+/// Sends the notifications for a newly committed block to every interested subscriber
+pub fn notify_subscribers<T>(
+    client: &ValidatorClient<T>,
+    block: &Block,
+    block_header: &BlockHeader,
+    receipts: &HashMap<String, SethReceipt>,
+) where
+    T: MessageSender,
+{
+    let subscriptions = client.subscriptions.get_subscriptions();
+
+    for (id, subscription) in subscriptions {
+        match subscription {
+            Subscription::NewHeads => {
+                let gas_used = receipts.values().map(|receipt| receipt.gas_used).sum();
+                let header = make_block_header_obj(block, block_header, gas_used);
+                client.subscriptions.notify(&id, Value::Object(header));
+            }
+            Subscription::Logs(log_filter) => {
+                let logs = match get_logs_from_receipts_and_filter(block, receipts, &log_filter) {
+                    Ok(logs) => logs,
+                    Err(error) => {
+                        // A subscription that would silently miss logs is ended instead
+                        error!(
+                            "Failed to get the logs of block {} for subscription {:?}, ending \
+                             it: {:?}",
+                            block.header_signature, id, error
+                        );
+                        client.subscriptions.remove_subscription(&id);
+                        continue;
+                    }
+                };
+                for log in logs {
+                    if !client.subscriptions.notify(&id, log) {
+                        break;
+                    }
+                }
+            }
+            Subscription::NewPendingTransactions => {
+                for txn_id in get_seth_transaction_ids(block) {
+                    if !client.subscriptions.notify(&id, txn_id) {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
@@ -15,18 +15,31 @@
  * ------------------------------------------------------------------------------
  */
 
-use client::{BlockKey, ValidatorClient};
+use client::{
+    invalid_transaction_message, BlockKey, Error as ClientError, TransactionOptions,
+    ValidatorClient, FAMILY_NAME, FAMILY_VERSION,
+};
 use jsonrpc_core::{Error, Params, Value};
 use messages::seth::EvmPermissions;
 use messages::seth::SetPermissionsTxn;
 use messages::seth::SethTransaction as SethTransactionPb;
 use messages::seth::SethTransaction_TransactionType;
+use protobuf;
 use requests::RequestHandler;
+use sawtooth_sdk::messages::batch::BatchList;
+use sawtooth_sdk::messages::block::BlockHeader;
+use sawtooth_sdk::messages::client_batch_submit::ClientBatchStatus_Status;
+use sawtooth_sdk::messages::transaction::{Transaction as TransactionPb, TransactionHeader};
 use sawtooth_sdk::messaging::stream::MessageSender;
-use std::time::{SystemTime, UNIX_EPOCH};
-use transactions::SethTransaction;
+use serde_json::Map;
+use transaction::{get_state_nonce, reserve_nonces, submit_transaction, TransactionObject};
+use transactions::{SethTransaction, TransactionKey};
 use transform;
 
+/// How many seconds `seth_sendTransactionAndWait` waits for when the transaction has no `wait`,
+/// unless the configured maximum is shorter
+const DEFAULT_WAIT: u64 = 60;
+
 pub fn get_method_list<T>() -> Vec<(String, RequestHandler<T>)>
 where
     T: MessageSender,
@@ -34,6 +47,13 @@ where
     vec![
         ("seth_getPermissions".into(), get_permissions),
         ("seth_setPermissions".into(), set_permissions),
+        ("seth_sendBatch".into(), send_batch),
+        ("seth_sendRawBatch".into(), send_raw_batch),
+        ("seth_getTransactionStatus".into(), get_transaction_status),
+        (
+            "seth_sendTransactionAndWait".into(),
+            send_transaction_and_wait,
+        ),
     ]
 }
 
@@ -49,7 +69,7 @@ where
 
     let account = client
         .get_account(&address, BlockKey::Latest)
-        .map_err(|err| fail!("Couldn't get key", err))?;
+        .map_err(|err| err.or_unavailable(fail!("Couldn't get key", err)))?;
 
     match account {
         Some(a) => Ok(Value::String(format!("{}", a.get_permissions()))),
@@ -68,37 +88,338 @@ where
     let (address, permissions): (String, String) =
         params.parse().map_err(|_| Error::invalid_params(usage))?;
 
-    let nonce = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .map_err(|err| fail!("Time has gone backwards", err))?
-        .as_secs();
+    let mut inner = SetPermissionsTxn::new();
+    inner
+        .set_to(transform::hex_str_to_bytes(&address).ok_or_else(|| Error::invalid_params(usage))?);
+    inner.set_permissions(
+        permissions
+            .parse::<EvmPermissions>()
+            .map_err(|err| fail!("Couldn't parse permissions", err))?,
+    );
+
+    let account = client
+        .unlocked_account()
+        .ok_or_else(|| fail!("Couldn't unlock account"))?;
+    let state_nonce = match client.get_account(account.address(), BlockKey::Latest) {
+        Ok(Some(state_account)) => state_account.nonce,
+        Ok(None) => return Err(fail!("Unlocked account doesn't exist")),
+        Err(err) => return Err(err.or_unavailable(fail!("Couldn't get unlocked account", err))),
+    };
+    let nonce = client
+        .reserve_nonces(account.address(), state_nonce, 1)
+        .map_err(|err| err.or_unavailable(fail!("Couldn't reserve nonce", err)))?;
+    inner.set_nonce(nonce);
 
     // Create and send the transaction in for processing
     let mut txn = SethTransactionPb::new();
     txn.set_transaction_type(SethTransaction_TransactionType::SET_PERMISSIONS);
-    txn.set_set_permissions({
-        let mut inner = SetPermissionsTxn::new();
-        inner.set_nonce(nonce);
-        inner.set_to(
-            transform::hex_str_to_bytes(&address).ok_or_else(|| Error::invalid_params(usage))?,
-        );
-        inner.set_permissions(
-            permissions
-                .parse::<EvmPermissions>()
-                .map_err(|err| fail!("Couldn't parse permissions", err))?,
-        );
-        inner
-    });
+    txn.set_set_permissions(inner);
 
     client
         .send_transaction(
-            &client
-                .unlocked_account()
-                .ok_or_else(|| fail!("Couldn't unlock account"))?
-                .public_key(),
+            account.address(),
             &SethTransaction::try_from(txn).ok_or_else(|| fail!("Couldn't create transaction"))?,
+            &TransactionOptions::default(),
         )
-        .map_err(|err| fail!("Couldn't send transaction", err))?;
+        .map_err(|err| {
+            client.release_nonces(account.address(), nonce, 1);
+            err.or_unavailable(fail!("Couldn't send transaction", err))
+        })?;
 
     Ok(Value::Bool(true))
 }
+
+/// Submits batches that were signed by the client. Every transaction in them must be a valid
+/// Seth transaction; the validator checks the signatures.
+pub fn send_raw_batch<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("seth_sendRawBatch");
+
+    let usage = "Takes [batchList: DATA]";
+
+    let (data,): (String,) = params.parse().map_err(|_| Error::invalid_params(usage))?;
+    let bytes = data
+        .get(2..)
+        .and_then(transform::hex_str_to_bytes)
+        .ok_or_else(|| Error::invalid_params("Invalid batch list, must be hex with 0x"))?;
+    let mut batch_list: BatchList = protobuf::parse_from_bytes(&bytes)
+        .map_err(|_| Error::invalid_params("Couldn't parse batch list"))?;
+
+    let mut txn_ids = Vec::new();
+    for batch in batch_list.get_batches() {
+        for txn in batch.get_transactions() {
+            check_seth_transaction(txn)?;
+            txn_ids.push(transform::hex_prefix(&txn.header_signature));
+        }
+    }
+    if txn_ids.is_empty() {
+        return Err(Error::invalid_params("Batch list has no transactions"));
+    }
+
+    client
+        .submit_batches(batch_list.take_batches().into_vec())
+        .map_err(|error| match error {
+            ClientError::InvalidTransaction => {
+                Error::invalid_params("The validator rejected the batch list")
+            }
+            error => error.or_unavailable(fail!("Couldn't submit batch list", error)),
+        })?;
+
+    Ok(Value::Array(txn_ids))
+}
+
+/// Checks that the transaction is for the Seth family and has a valid payload
+fn check_seth_transaction(txn: &TransactionPb) -> Result<(), Error> {
+    let header: TransactionHeader = protobuf::parse_from_bytes(&txn.header).map_err(|_| {
+        Error::invalid_params(format!(
+            "Couldn't parse header of transaction {}",
+            txn.header_signature
+        ))
+    })?;
+    if transform::hex_str_to_bytes(&header.signer_public_key).is_none() {
+        return Err(Error::invalid_params(format!(
+            "Transaction {} has an invalid signer public key",
+            txn.header_signature
+        )));
+    }
+    if header.family_name != FAMILY_NAME || header.family_version != FAMILY_VERSION {
+        return Err(Error::invalid_params(format!(
+            "Transaction {} is not a {} {} transaction",
+            txn.header_signature, FAMILY_NAME, FAMILY_VERSION
+        )));
+    }
+
+    protobuf::parse_from_bytes::<SethTransactionPb>(&txn.payload)
+        .ok()
+        .and_then(SethTransaction::try_from)
+        .map(|_| ())
+        .ok_or_else(|| {
+            Error::invalid_params(format!(
+                "Transaction {} has an invalid payload",
+                txn.header_signature
+            ))
+        })
+}
+
+/// Returns where a transaction is in its lifecycle. Committed transactions are found in their
+/// block. Otherwise, the status of the batch the transaction was submitted in is asked for, which
+/// is only known for transactions submitted through this seth-rpc since it was started.
+pub fn get_transaction_status<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("seth_getTransactionStatus");
+
+    let usage = "Takes [txnHash: DATA(64)]";
+
+    let (txn_id,): (String,) = params.parse().map_err(|_| Error::invalid_params(usage))?;
+    let txn_id = txn_id
+        .get(2..)
+        .map(|txn_id| client.resolve_transaction_id(txn_id))
+        .ok_or_else(|| Error::invalid_params("Invalid transaction hash, must have 0x"))?;
+
+    let mut status = Map::new();
+    status.insert(
+        String::from("transactionId"),
+        transform::hex_prefix(&txn_id),
+    );
+
+    let block = match client.get_transaction_and_block(&TransactionKey::Signature(txn_id.clone())) {
+        Ok((_, block)) => block,
+        Err(ClientError::NoResource) => None,
+        Err(error) => {
+            error!("Error getting transaction `{}`: {}", txn_id, error);
+            return Err(error.or_unavailable(Error::internal_error()));
+        }
+    };
+
+    if let Some(block) = block {
+        let block_header: BlockHeader =
+            protobuf::parse_from_bytes(&block.header).map_err(|error| {
+                error!("Error parsing block header: {}", error);
+                Error::internal_error()
+            })?;
+        let batch_id = block
+            .get_batches()
+            .iter()
+            .find(|batch| {
+                batch
+                    .get_transactions()
+                    .iter()
+                    .any(|txn| txn.header_signature == txn_id)
+            })
+            .map(|batch| transform::hex_prefix(&batch.header_signature))
+            .unwrap_or(Value::Null);
+
+        status.insert(String::from("batchId"), batch_id);
+        status.insert(
+            String::from("status"),
+            Value::String(String::from("COMMITTED")),
+        );
+        status.insert(String::from("error"), Value::Null);
+        status.insert(
+            String::from("blockHash"),
+            transform::hex_prefix(&block.header_signature),
+        );
+        status.insert(
+            String::from("blockNumber"),
+            transform::num_to_hex(&block_header.block_num),
+        );
+        return Ok(Value::Object(status));
+    }
+
+    let batch_id = client.get_batch_id(&txn_id);
+    let batch_status = match batch_id {
+        Some(ref batch_id) => match client.get_batch_status(batch_id, None) {
+            Ok(batch_status) => Some(batch_status),
+            Err(ClientError::NoResource) => None,
+            Err(error) => {
+                error!("Error getting status of batch `{}`: {}", batch_id, error);
+                return Err(error.or_unavailable(Error::internal_error()));
+            }
+        },
+        None => None,
+    };
+
+    let (name, error) = match batch_status {
+        Some(ref batch_status) => match batch_status.status {
+            ClientBatchStatus_Status::COMMITTED => ("COMMITTED", None),
+            ClientBatchStatus_Status::INVALID => (
+                "INVALID",
+                Some(invalid_transaction_message(batch_status, &txn_id)),
+            ),
+            ClientBatchStatus_Status::PENDING => ("PENDING", None),
+            ClientBatchStatus_Status::UNKNOWN | ClientBatchStatus_Status::STATUS_UNSET => {
+                ("UNKNOWN", None)
+            }
+        },
+        None => ("UNKNOWN", None),
+    };
+
+    status.insert(
+        String::from("batchId"),
+        batch_id
+            .map(|batch_id| transform::hex_prefix(&batch_id))
+            .unwrap_or(Value::Null),
+    );
+    status.insert(String::from("status"), Value::String(String::from(name)));
+    status.insert(
+        String::from("error"),
+        error.map(Value::String).unwrap_or(Value::Null),
+    );
+    // A batch can be committed before its block can be retrieved
+    status.insert(String::from("blockHash"), Value::Null);
+    status.insert(String::from("blockNumber"), Value::Null);
+
+    Ok(Value::Object(status))
+}
+
+/// Like `eth_sendTransaction` with `wait` set, returning the receipt of the transaction once it
+/// is committed or found invalid
+pub fn send_transaction_and_wait<T>(
+    params: Params,
+    client: ValidatorClient<T>,
+) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("seth_sendTransactionAndWait");
+
+    let usage = "Takes [txn: OBJECT]";
+
+    let (mut txn,): (Map<String, Value>,) =
+        params.parse().map_err(|_| Error::invalid_params(usage))?;
+    if !txn.contains_key("wait") {
+        let wait = DEFAULT_WAIT.min(client.config().max_wait);
+        txn.insert(String::from("wait"), transform::num_to_hex(&wait));
+    }
+
+    submit_transaction(&client, &txn)
+}
+
+/// Sends several transactions from the same account in a single batch, so that either all of
+/// them are committed or none are. The transactions get consecutive nonces, in order.
+pub fn send_batch<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("seth_sendBatch");
+
+    let usage = "Takes [txns: ARRAY]";
+
+    let (txns,): (Vec<Map<String, Value>>,) =
+        params.parse().map_err(|_| Error::invalid_params(usage))?;
+    if txns.iter().any(|txn| txn.contains_key("wait")) {
+        return Err(Error::invalid_params(
+            "Transactions in a batch can't set `wait`",
+        ));
+    }
+    let objects = txns
+        .iter()
+        .map(|txn| TransactionObject::parse(txn, client.config()))
+        .collect::<Result<Vec<TransactionObject>, Error>>()?;
+
+    let from = match objects.first() {
+        Some(object) => object.from.to_lowercase(),
+        None => return Err(Error::invalid_params("Batch has no transactions")),
+    };
+    if objects
+        .iter()
+        .any(|object| object.from.to_lowercase() != from)
+    {
+        return Err(Error::invalid_params(
+            "All transactions must be from the same account",
+        ));
+    }
+    if objects.iter().any(|object| object.nonce.is_some()) {
+        return Err(Error::invalid_params(
+            "Transactions in a batch get consecutive nonces and can't set `nonce`",
+        ));
+    }
+
+    // The options apply to the whole batch, so the access lists and dependencies of its
+    // transactions are merged
+    let mut options = TransactionOptions::default();
+    for object in &objects {
+        options.chaining |= object.options.chaining;
+        options
+            .access_list
+            .extend(object.options.access_list.iter().cloned());
+        options
+            .dependencies
+            .extend(object.options.dependencies.iter().cloned());
+    }
+    options.access_list.sort();
+    options.access_list.dedup();
+    options.dependencies.sort();
+    options.dependencies.dedup();
+
+    let count = objects.len() as u64;
+    let state_nonce = get_state_nonce(&client, &from)?;
+    let first_nonce = reserve_nonces(&client, &from, state_nonce, count)?;
+    let txns: Vec<SethTransaction> = objects
+        .into_iter()
+        .zip(first_nonce..)
+        .map(|(object, nonce)| object.into_seth_transaction(nonce))
+        .collect();
+
+    let (txn_ids, batch_id) = client.send_batch(&from, &txns, &options).map_err(|error| {
+        client.release_nonces(&from, first_nonce, count);
+        error.or_unavailable(fail!("Couldn't send batch", error))
+    })?;
+
+    let mut result = Map::new();
+    result.insert(
+        String::from("transactionIds"),
+        Value::Array(
+            txn_ids
+                .iter()
+                .map(|txn_id| transform::hex_prefix(txn_id))
+                .collect(),
+        ),
+    );
+    result.insert(String::from("batchId"), transform::hex_prefix(&batch_id));
+    Ok(Value::Object(result))
+}
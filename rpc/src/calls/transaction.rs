@@ -15,22 +15,34 @@
  * ------------------------------------------------------------------------------
  */
 
-use client::{BlockKey, Error as ClientError, ValidatorClient};
+use client::{
+    invalid_transaction_message, BlockKey, BlockKeyParseError, Error as ClientError,
+    TransactionOptions, ValidatorClient,
+};
+use config::RpcConfig;
 use error;
+use ethereum_types::U256;
+use evm::{self, Address, Outcome, VmError};
 use jsonrpc_core::{Error, ErrorCode, Params, Value};
 use messages::seth::{
-    CreateContractAccountTxn as CreateContractAccountTxnPb, MessageCallTxn as MessageCallTxnPb,
+    CreateContractAccountTxn as CreateContractAccountTxnPb, EvmPermissions,
+    MessageCallTxn as MessageCallTxnPb,
 };
+use network;
 use protobuf;
+use raw_transaction::RawTransaction;
 use requests::RequestHandler;
 use sawtooth_sdk::messages::block::BlockHeader;
+use sawtooth_sdk::messages::client_batch_submit::ClientBatchStatus_Status;
 use sawtooth_sdk::messaging::stream::MessageSender;
 use serde_json::Map;
 use std::str::FromStr;
 use tiny_keccak;
 use transactions::{SethTransaction, TransactionKey};
 use transform;
-use transform::{make_txn_obj, make_txn_obj_no_block, make_txn_receipt_obj};
+use transform::{
+    make_invalid_txn_receipt_obj, make_txn_obj, make_txn_obj_no_block, make_txn_receipt_obj,
+};
 
 pub fn get_method_list<T>() -> Vec<(String, RequestHandler<T>)>
 where
@@ -65,42 +77,444 @@ where
     let (txn,): (Map<String, Value>,) = params
         .parse()
         .map_err(|_| Error::invalid_params("Takes [txn: OBJECT]"))?;
+    submit_transaction(&client, &txn)
+}
 
-    // Required arguments
-    let from = transform::get_string_from_map(&txn, "from")
-        .map_err(|_| Error::new(ErrorCode::ParseError))
-        .and_then(|f| f.ok_or_else(|| Error::invalid_params("`from` not set")))?;
-    let data = transform::get_bytes_from_map(&txn, "data")
-        .map_err(|_| Error::new(ErrorCode::ParseError))
-        .and_then(|f| f.ok_or_else(|| Error::invalid_params("`data` not set")))?;
-    let txn_count = match client.get_account(&from, BlockKey::Latest) {
-        Ok(Some(a)) => a.nonce,
-        Ok(None) => {
-            return Err(Error::invalid_params("Invalid `from` address"));
+/// The fields of a transaction object that Ethereum defines
+const STANDARD_FIELDS: &[&str] = &[
+    "from",
+    "to",
+    "gas",
+    "gasPrice",
+    "maxFeePerGas",
+    "maxPriorityFeePerGas",
+    "value",
+    "data",
+    "input",
+    "nonce",
+    "type",
+    "chainId",
+    "accessList",
+];
+
+/// The highest transaction type, of EIP-1559 transactions. Legacy and access list transactions
+/// have types 0 and 1.
+const MAX_TRANSACTION_TYPE: u64 = 2;
+
+/// The number of hex characters of a Sawtooth transaction id, which is a signature
+const TRANSACTION_ID_LENGTH: usize = 128;
+
+/// The fields Seth adds to transaction objects
+const EXTENSION_FIELDS: &[&str] = &["wait", "dependencies", "permissions", "chaining"];
+
+/// The fields of a transaction object, as passed to `eth_sendTransaction`
+pub struct TransactionObject {
+    pub from: String,
+    /// The called contract, or `None` to create a contract
+    pub to: Option<Vec<u8>>,
+    pub data: Vec<u8>,
+    pub gas: u64,
+    pub gas_price: u64,
+    pub value: u64,
+    pub nonce: Option<u64>,
+    /// The permissions of the created contract
+    pub permissions: Option<EvmPermissions>,
+    /// The access list, the transactions this one depends on, and whether it chains contracts
+    pub options: TransactionOptions,
+}
+
+impl TransactionObject {
+    /// Parses a transaction object, using the configured defaults for the gas limit and price
+    pub fn parse(txn: &Map<String, Value>, config: &RpcConfig) -> Result<Self, Error> {
+        // Fields that would otherwise be ignored most likely mean the caller expects something
+        // that won't happen
+        if let Some(key) = txn.keys().find(|key| {
+            !STANDARD_FIELDS.contains(&key.as_str()) && !EXTENSION_FIELDS.contains(&key.as_str())
+        }) {
+            return Err(Error::invalid_params(format!(
+                "Unknown transaction field `{}`",
+                key
+            )));
         }
+
+        // Required arguments
+        let from = transform::get_string_from_map(txn, "from")
+            .map_err(|_| Error::new(ErrorCode::ParseError))
+            .and_then(|f| f.ok_or_else(|| Error::invalid_params("`from` not set")))?;
+        let data = get_data(txn)?.ok_or_else(|| Error::invalid_params("`data` not set"))?;
+
+        // Optional Arguments
+        let txn_type = transform::get_u64_from_map(txn, "type")
+            .map_err(|_| Error::new(ErrorCode::ParseError))?;
+        if let Some(txn_type) = txn_type.filter(|txn_type| *txn_type > MAX_TRANSACTION_TYPE) {
+            return Err(Error::invalid_params(format!(
+                "Unsupported transaction type {:#x}",
+                txn_type
+            )));
+        }
+        let to = get_bytes(txn, "to")?;
+        let gas = transform::get_u64_from_map(txn, "gas")
+            .map(|g| g.unwrap_or(config.default_gas))
+            .map_err(|_| Error::new(ErrorCode::ParseError))?;
+        let gas_price = get_u256(txn, "gasPrice")?;
+        let max_fee_per_gas = get_u256(txn, "maxFeePerGas")?;
+        let max_priority_fee_per_gas = get_u256(txn, "maxPriorityFeePerGas")?;
+        if gas_price.is_some() && (max_fee_per_gas.is_some() || max_priority_fee_per_gas.is_some())
+        {
+            return Err(Error::invalid_params(
+                "`gasPrice` can't be set with `maxFeePerGas` or `maxPriorityFeePerGas`",
+            ));
+        }
+        // Seth has no base fee, so the price of an EIP-1559 transaction is its fee cap, and the
+        // priority fee is ignored
+        let gas_price = match (gas_price, max_fee_per_gas) {
+            (Some(gas_price), _) => transform::u256_to_u64(gas_price, "gasPrice")?,
+            (None, Some(max_fee_per_gas)) => {
+                transform::u256_to_u64(max_fee_per_gas, "maxFeePerGas")?
+            }
+            (None, None) => config.default_gas_price,
+        };
+        let value = match get_u256(txn, "value")? {
+            Some(value) => transform::u256_to_u64(value, "value")?,
+            None => 0,
+        };
+        let nonce = transform::get_u64_from_map(txn, "nonce")
+            .map_err(|_| Error::new(ErrorCode::ParseError))?;
+        let permissions = match txn.get("permissions") {
+            None | Some(&Value::Null) => None,
+            Some(&Value::String(ref permissions)) => {
+                Some(permissions.parse::<EvmPermissions>().map_err(|error| {
+                    Error::invalid_params(format!("Invalid permissions: {}", error))
+                })?)
+            }
+            Some(_) => return Err(Error::invalid_params("`permissions` must be a string")),
+        };
+        if permissions.is_some() && to.is_some() {
+            return Err(Error::invalid_params(
+                "`permissions` can only be set when creating a contract",
+            ));
+        }
+        let chaining = match txn.get("chaining") {
+            None | Some(&Value::Null) => false,
+            Some(&Value::Bool(chaining)) => chaining,
+            Some(_) => return Err(Error::invalid_params("`chaining` must be a boolean")),
+        };
+        let options = TransactionOptions {
+            access_list: parse_access_list(txn)?,
+            dependencies: parse_dependencies(txn)?,
+            chaining,
+        };
+
+        Ok(TransactionObject {
+            from,
+            to,
+            data,
+            gas,
+            gas_price,
+            value,
+            nonce,
+            permissions,
+            options,
+        })
+    }
+
+    pub fn into_seth_transaction(self, nonce: u64) -> SethTransaction {
+        let mut txn = make_call_or_create(
+            self.to,
+            self.data,
+            self.gas,
+            self.gas_price,
+            self.value,
+            nonce,
+        );
+        if let (Some(permissions), &mut SethTransaction::CreateContractAccount(ref mut inner)) =
+            (self.permissions, &mut txn)
+        {
+            inner.set_permissions(permissions);
+        }
+        txn
+    }
+}
+
+/// Returns the data of a transaction object, which newer clients send as `input`
+fn get_data(txn: &Map<String, Value>) -> Result<Option<Vec<u8>>, Error> {
+    match (get_bytes(txn, "data")?, get_bytes(txn, "input")?) {
+        (Some(ref data), Some(ref input)) if data != input => {
+            Err(Error::invalid_params("`data` and `input` differ"))
+        }
+        (Some(data), _) | (None, Some(data)) => Ok(Some(data)),
+        (None, None) => Ok(None),
+    }
+}
+
+fn get_bytes(txn: &Map<String, Value>, key: &str) -> Result<Option<Vec<u8>>, Error> {
+    transform::get_bytes_from_map(txn, key).map_err(|_| Error::new(ErrorCode::ParseError))
+}
+
+fn get_u256(txn: &Map<String, Value>, key: &str) -> Result<Option<U256>, Error> {
+    transform::get_u256_from_map(txn, key)
+}
+
+/// Returns the nonce of the sending account in state
+pub fn get_state_nonce<T>(client: &ValidatorClient<T>, from: &str) -> Result<u64, Error>
+where
+    T: MessageSender,
+{
+    match client.get_account(from, BlockKey::Latest) {
+        Ok(Some(a)) => Ok(a.nonce),
+        Ok(None) => Err(Error::invalid_params("Invalid `from` address")),
         Err(e) => {
             error!("{}", e);
-            return Err(Error::internal_error());
+            Err(e.or_unavailable(Error::internal_error()))
         }
+    }
+}
+
+/// Signs and submits the transaction object. If it has a `wait` field, waits up to that many
+/// seconds for the transaction to be committed or found invalid and returns its receipt instead
+/// of its id.
+pub fn submit_transaction<T>(
+    client: &ValidatorClient<T>,
+    txn: &Map<String, Value>,
+) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    let object = TransactionObject::parse(txn, client.config())?;
+    let wait =
+        transform::get_u64_from_map(txn, "wait").map_err(|_| Error::new(ErrorCode::ParseError))?;
+    let max_wait = client.config().max_wait;
+    if let Some(wait) = wait.filter(|wait| *wait > max_wait) {
+        return Err(Error::invalid_params(format!(
+            "`wait` of {} seconds is longer than the maximum of {}",
+            wait, max_wait
+        )));
+    }
+    let state_nonce = get_state_nonce(client, &object.from)?;
+
+    // Without an explicit nonce, the next one is reserved so that transactions sent before the
+    // previous ones are committed don't reuse it
+    let (nonce, reserved) = match object.nonce {
+        Some(nonce) => (nonce, false),
+        None => (reserve_nonces(client, &object.from, state_nonce, 1)?, true),
     };
 
-    // Optional Arguments
-    let to =
-        transform::get_bytes_from_map(&txn, "to").map_err(|_| Error::new(ErrorCode::ParseError))?;
-    let gas = transform::get_u64_from_map(&txn, "gas")
-        .map(|g| g.unwrap_or(90_000))
-        .map_err(|_| Error::new(ErrorCode::ParseError))?;
-    let gas_price = transform::get_u64_from_map(&txn, "gasPrice")
-        .map(|g| g.unwrap_or(10_000_000_000_000))
-        .map_err(|_| Error::new(ErrorCode::ParseError))?;
-    let value = transform::get_u64_from_map(&txn, "value")
-        .map(|g| g.unwrap_or(0))
-        .map_err(|_| Error::new(ErrorCode::ParseError))?;
-    let nonce = transform::get_u64_from_map(&txn, "nonce")
-        .map(|g| g.unwrap_or(txn_count))
-        .map_err(|_| Error::new(ErrorCode::ParseError))?;
-
-    let txn = if let Some(to) = to {
+    let from = object.from.clone();
+    let options = object.options.clone();
+    let txn = object.into_seth_transaction(nonce);
+
+    let txn_signature = client
+        .send_transaction(&from, &txn, &options)
+        .map_err(|error| {
+            error!("{:?}", error);
+            if reserved {
+                client.release_nonces(&from, nonce, 1);
+            }
+            error.or_unavailable(Error::internal_error())
+        })?;
+
+    match wait {
+        Some(wait) => wait_for_receipt(client, txn_signature, wait),
+        None => Ok(transform::hex_prefix(&txn_signature)),
+    }
+}
+
+/// Reserves the nonces for the next `count` transactions from the account, given its nonce in
+/// state, and returns the first one
+pub fn reserve_nonces<T>(
+    client: &ValidatorClient<T>,
+    address: &str,
+    state_nonce: u64,
+    count: u64,
+) -> Result<u64, Error>
+where
+    T: MessageSender,
+{
+    client
+        .reserve_nonces(address, state_nonce, count)
+        .map_err(|error| {
+            error!("Error reserving nonce for `{}`: {}", address, error);
+            error.or_unavailable(Error::internal_error())
+        })
+}
+
+/// Waits for the batch of a transaction submitted through this client to be committed or found
+/// invalid, then returns the receipt of the transaction
+fn wait_for_receipt<T>(
+    client: &ValidatorClient<T>,
+    txn_id: String,
+    wait: u64,
+) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    // The batch is only forgotten if many transactions were submitted since
+    let batch_id = match client.get_batch_id(&txn_id) {
+        Some(batch_id) => batch_id,
+        None => return get_receipt(client, txn_id),
+    };
+    let timeout = wait.min(u64::from(u32::max_value())) as u32;
+    let status = client
+        .get_batch_status(&batch_id, Some(timeout))
+        .map_err(|error| {
+            error!("Error waiting for batch `{}`: {}", batch_id, error);
+            error.or_unavailable(Error::internal_error())
+        })?;
+
+    match status.status {
+        ClientBatchStatus_Status::COMMITTED => get_receipt(client, txn_id),
+        ClientBatchStatus_Status::INVALID => Ok(make_invalid_txn_receipt_obj(
+            &txn_id,
+            &invalid_transaction_message(&status, &txn_id),
+        )),
+        _ => Err(Error {
+            code: ErrorCode::ServerError(-32000),
+            message: format!(
+                "Transaction 0x{} was not committed within {} seconds",
+                txn_id, wait
+            ),
+            data: None,
+        }),
+    }
+}
+
+/// Sends a signed Ethereum transaction. Seth only knows senders by their Sawtooth key, which
+/// the Ethereum signer doesn't have, so the Seth transaction is signed and sent by the relayer
+/// account instead. The Ethereum transaction hash can be used to look the transaction up.
+pub fn send_raw_transaction<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    info!("eth_sendRawTransaction");
+    let (data,): (String,) = params
+        .parse()
+        .map_err(|_| Error::invalid_params("Takes [data: DATA]"))?;
+    let bytes = data
+        .get(2..)
+        .and_then(transform::hex_str_to_bytes)
+        .ok_or_else(|| Error::invalid_params("Invalid transaction data, must be hex with 0x"))?;
+    let raw_txn = RawTransaction::decode(&bytes)
+        .map_err(|error| Error::invalid_params(format!("Invalid transaction: {}", error)))?;
+
+    // Legacy transactions aren't signed for any chain, so they are accepted by every chain
+    if let Some(chain_id) = raw_txn.chain_id {
+        let expected = network::get_chain_id(&client)?;
+        if chain_id != expected {
+            return Err(Error::invalid_params(format!(
+                "Invalid chain id {}, expected {}",
+                chain_id, expected
+            )));
+        }
+    }
+    // The relayer would pay the value, so anyone could spend its balance
+    if !raw_txn.value.is_zero() {
+        return Err(Error::invalid_params(
+            "Relayed transactions can't transfer value",
+        ));
+    }
+    let gas_price = transform::u256_to_u64(raw_txn.gas_price, "gasPrice")?;
+    if !client.relayed_restored() {
+        return Err(Error {
+            code: ErrorCode::ServerError(-32000),
+            message: String::from(
+                "The transactions relayed before seth-rpc started are still being read",
+            ),
+            data: None,
+        });
+    }
+
+    // The relayer signs every relayed transaction, so the signer's nonce is what keeps a signed
+    // transaction from being replayed
+    let eth_txn_hash = transform::bytes_to_hex_str(raw_txn.hash.as_bytes());
+    if client.is_relayed(&eth_txn_hash) {
+        return Err(Error {
+            code: ErrorCode::ServerError(-32000),
+            message: format!("Transaction 0x{} was already sent", eth_txn_hash),
+            data: None,
+        });
+    }
+    let signer = transform::bytes_to_hex_str(raw_txn.from.as_bytes());
+    let signer_nonce = raw_txn.nonce;
+    client
+        .claim_signer_nonce(&signer, signer_nonce)
+        .map_err(|expected| Error {
+            code: ErrorCode::ServerError(-32000),
+            message: format!("Invalid nonce {}, expected {}", signer_nonce, expected),
+            data: None,
+        })?;
+    let result = relay_transaction(&client, &eth_txn_hash, &signer, raw_txn, gas_price);
+    if result.is_err() {
+        client.release_signer_nonce(&signer, signer_nonce);
+    }
+    result
+}
+
+/// Sends a Seth transaction signed by the relayer with the contents of the raw transaction
+fn relay_transaction<T>(
+    client: &ValidatorClient<T>,
+    eth_txn_hash: &str,
+    signer: &str,
+    raw_txn: RawTransaction,
+    gas_price: u64,
+) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    let relayer = client.relayer().ok_or_else(|| Error {
+        code: ErrorCode::ServerError(-32000),
+        message: String::from("No relayer account is configured"),
+        data: None,
+    })?;
+    let state_nonce = match client.get_account(relayer.address(), BlockKey::Latest) {
+        Ok(Some(account)) => account.nonce,
+        Ok(None) => {
+            return Err(Error {
+                code: ErrorCode::ServerError(-32000),
+                message: String::from("Relayer account does not exist"),
+                data: None,
+            });
+        }
+        Err(error) => {
+            error!("{}", error);
+            return Err(error.or_unavailable(Error::internal_error()));
+        }
+    };
+
+    let nonce = reserve_nonces(client, relayer.address(), state_nonce, 1)?;
+
+    debug!(
+        "Relaying transaction {} signed by {:?} with nonce {}",
+        eth_txn_hash, raw_txn.from, raw_txn.nonce
+    );
+    let txn = make_call_or_create(
+        raw_txn.to.map(|to| to.as_bytes().to_vec()),
+        raw_txn.data,
+        raw_txn.gas,
+        gas_price,
+        0,
+        nonce,
+    );
+    client
+        .send_relayed_transaction(eth_txn_hash, signer, raw_txn.nonce, &txn)
+        .map_err(|error| {
+            error!("{:?}", error);
+            client.release_nonces(relayer.address(), nonce, 1);
+            error.or_unavailable(Error::internal_error())
+        })?;
+
+    Ok(transform::hex_prefix(eth_txn_hash))
+}
+
+/// Makes a message call, or a contract creation if `to` isn't set
+fn make_call_or_create(
+    to: Option<Vec<u8>>,
+    data: Vec<u8>,
+    gas: u64,
+    gas_price: u64,
+    value: u64,
+    nonce: u64,
+) -> SethTransaction {
+    if let Some(to) = to {
         // Message Call
         let mut txn = MessageCallTxnPb::new();
         txn.set_to(to);
@@ -119,23 +533,7 @@ where
         txn.set_value(value);
         txn.set_nonce(nonce);
         SethTransaction::CreateContractAccount(txn)
-    };
-
-    let txn_signature = client.send_transaction(&from, &txn).map_err(|error| {
-        error!("{:?}", error);
-        Error::internal_error()
-    })?;
-
-    Ok(transform::hex_prefix(&txn_signature))
-}
-
-pub fn send_raw_transaction<T>(_params: Params, _client: ValidatorClient<T>) -> Result<Value, Error>
-where
-    T: MessageSender,
-{
-    info!("eth_sendRawTransaction");
-    // Implementing this requires substantial modification to the seth transaction family
-    Err(error::not_implemented())
+    }
 }
 
 pub fn get_transaction_by_hash<T>(
@@ -161,7 +559,8 @@ where
         }
     };
 
-    get_transaction(client, &TransactionKey::Signature(txn_hash))
+    let txn_id = client.resolve_transaction_id(&txn_hash);
+    get_transaction(client, &TransactionKey::Signature(txn_id))
 }
 
 pub fn get_transaction_by_block_hash_and_index<T>(
@@ -257,7 +656,7 @@ where
             }
             _ => {
                 error!("{:?}", error);
-                return Err(Error::internal_error());
+                return Err(error.or_unavailable(Error::internal_error()));
             }
         },
     };
@@ -324,9 +723,18 @@ where
                 .map(String::from)
                 .ok_or_else(|| Error::invalid_params("Invalid transaction hash, must have 0x"))
         })?;
+    let txn_id = client.resolve_transaction_id(&txn_id);
+    get_receipt(&client, txn_id)
+}
+
+/// Returns the receipt of the transaction, or null if it doesn't have one yet
+fn get_receipt<T>(client: &ValidatorClient<T>, txn_id: String) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
     let receipt = match client.get_receipts(&[txn_id.clone()]) {
         Err(ClientError::NoResource) => {
-            return Ok(Value::Null);
+            return get_invalid_txn_receipt(client, &txn_id);
         }
         Ok(mut map) => match map.remove(&txn_id) {
             Some(r) => r,
@@ -337,7 +745,7 @@ where
         },
         Err(error) => {
             error!("Error getting receipt for txn `{}`: {}", txn_id, error);
-            return Err(Error::internal_error());
+            return Err(error.or_unavailable(Error::internal_error()));
         }
     };
     let block = client
@@ -347,7 +755,7 @@ where
                 "Error getting block and transaction for txn `{}`: {}",
                 txn_id, error
             );
-            Error::internal_error()
+            error.or_unavailable(Error::internal_error())
         })
         .and_then(|(_, block_option)| {
             block_option.ok_or_else(|| {
@@ -380,6 +788,34 @@ where
     ))
 }
 
+/// Returns a failed receipt if the transaction was submitted through this client in a batch that
+/// the validator found invalid, since the validator never stores a receipt for it
+fn get_invalid_txn_receipt<T>(client: &ValidatorClient<T>, txn_id: &str) -> Result<Value, Error>
+where
+    T: MessageSender,
+{
+    let batch_id = match client.get_batch_id(txn_id) {
+        Some(batch_id) => batch_id,
+        None => return Ok(Value::Null),
+    };
+    let status = match client.get_batch_status(&batch_id, None) {
+        Ok(status) => status,
+        Err(ClientError::NoResource) => return Ok(Value::Null),
+        Err(error) => {
+            error!("Error getting status of batch `{}`: {}", batch_id, error);
+            return Err(error.or_unavailable(Error::internal_error()));
+        }
+    };
+    if status.status != ClientBatchStatus_Status::INVALID {
+        return Ok(Value::Null);
+    }
+
+    Ok(make_invalid_txn_receipt_obj(
+        txn_id,
+        &invalid_transaction_message(&status, txn_id),
+    ))
+}
+
 pub fn gas_price<T>(_params: Params, _client: ValidatorClient<T>) -> Result<Value, Error>
 where
     T: MessageSender,
@@ -388,13 +824,28 @@ where
     Ok(Value::String(format!("{:#x}", 0)))
 }
 
-pub fn estimate_gas<T>(_params: Params, _client: ValidatorClient<T>) -> Result<Value, Error>
+pub fn estimate_gas<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
 where
     T: MessageSender,
 {
     info!("eth_estimateGas");
-    // Implementing this requires running the EVM, which is not possible within the RPC.
-    Err(error::not_implemented())
+    let (txn, block) = parse_call_params(params, &client)?;
+
+    let (gas, result) = evm::simulate_estimate_gas(&client, block, &txn).map_err(|error| {
+        error!("Failed to estimate gas: {}", error);
+        error.or_unavailable(Error::internal_error())
+    })?;
+
+    match result.outcome {
+        Outcome::Success => Ok(transform::num_to_hex(&gas)),
+        Outcome::Revert => Err(error::execution_reverted(&result.output)),
+        Outcome::Error(VmError::OutOfGas) => Err(Error {
+            code: ErrorCode::ServerError(-32000),
+            message: format!("gas required exceeds allowance ({})", gas),
+            data: None,
+        }),
+        Outcome::Error(vm_error) => Err(error::execution_failed(&vm_error)),
+    }
 }
 
 pub fn sign<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
@@ -449,13 +900,137 @@ where
     Ok(transform::hex_prefix(&signature))
 }
 
-pub fn call<T>(_: Params, _: ValidatorClient<T>) -> Result<Value, Error>
+pub fn call<T>(params: Params, client: ValidatorClient<T>) -> Result<Value, Error>
 where
     T: MessageSender,
 {
     info!("eth_call");
-    // Implementing this requires running the EVM, which is not possible within the RPC.
-    Err(error::not_implemented())
+    let (txn, block) = parse_call_params(params, &client)?;
+
+    let result = evm::simulate(&client, block, &txn).map_err(|error| {
+        error!("Failed to simulate call: {}", error);
+        error.or_unavailable(Error::internal_error())
+    })?;
+
+    match result.outcome {
+        Outcome::Success => Ok(transform::hex_prefix(&transform::bytes_to_hex_str(
+            &result.output,
+        ))),
+        Outcome::Revert => Err(error::execution_reverted(&result.output)),
+        Outcome::Error(vm_error) => Err(error::execution_failed(&vm_error)),
+    }
+}
+
+/// Parses the `[txn: OBJECT, block: QUANTITY|TAG]` params of calls that simulate a transaction.
+/// The block defaults to the latest block, and the sender to the unlocked account.
+pub fn parse_call_params<T>(
+    params: Params,
+    client: &ValidatorClient<T>,
+) -> Result<(evm::Transaction, BlockKey), Error>
+where
+    T: MessageSender,
+{
+    let usage = "Takes [txn: OBJECT, block: QUANTITY|TAG]";
+    let mut args: Vec<Value> = params.parse().map_err(|_| Error::invalid_params(usage))?;
+    let block = match args.len() {
+        1 => BlockKey::Latest,
+        2 => match args.remove(1) {
+            Value::String(block) => match block.parse() {
+                Ok(block) => block,
+                Err(BlockKeyParseError::Invalid) => {
+                    return Err(Error::invalid_params("Failed to parse block number"));
+                }
+                Err(BlockKeyParseError::Unsupported) => return Err(error::not_implemented()),
+            },
+            _ => return Err(Error::invalid_params(usage)),
+        },
+        _ => return Err(Error::invalid_params(usage)),
+    };
+    let txn = match args.remove(0) {
+        Value::Object(txn) => txn,
+        _ => return Err(Error::invalid_params(usage)),
+    };
+
+    let from = match get_address_from_map(&txn, "from")? {
+        Some(from) => from,
+        None => client
+            .unlocked_account()
+            .and_then(|account| transform::hex_str_to_bytes(account.address()))
+            .map(|address| Address::from_slice(&address))
+            .unwrap_or_else(Address::zero),
+    };
+    let to = get_address_from_map(&txn, "to")?;
+    let data = get_data(&txn)?.unwrap_or_else(Vec::new);
+    let gas = transform::get_u64_from_map(&txn, "gas")
+        .map_err(|_| Error::new(ErrorCode::ParseError))?
+        .unwrap_or(evm::gas::GAS_LIMIT);
+    let gas_price = get_u256(&txn, "gasPrice")?.unwrap_or_else(U256::zero);
+    let value = get_u256(&txn, "value")?.unwrap_or_else(U256::zero);
+
+    Ok((
+        evm::Transaction {
+            from,
+            to,
+            data,
+            gas,
+            gas_price,
+            value,
+        },
+        block,
+    ))
+}
+
+/// Parses the addresses of an EIP-2930 access list. Storage keys are ignored, since the storage of
+/// a contract is in the same state entry as the contract.
+fn parse_access_list(txn: &Map<String, Value>) -> Result<Vec<Address>, Error> {
+    let entries = match txn.get("accessList") {
+        None | Some(&Value::Null) => return Ok(Vec::new()),
+        Some(&Value::Array(ref entries)) => entries,
+        Some(_) => return Err(Error::invalid_params("`accessList` must be an array")),
+    };
+    entries
+        .iter()
+        .map(|entry| match *entry {
+            Value::Object(ref entry) => get_address_from_map(entry, "address")?
+                .ok_or_else(|| Error::invalid_params("Access list entries must have an `address`")),
+            _ => Err(Error::invalid_params("Access list entries must be objects")),
+        })
+        .collect()
+}
+
+/// Parses the ids of the transactions that must be committed before this one
+fn parse_dependencies(txn: &Map<String, Value>) -> Result<Vec<String>, Error> {
+    let txn_ids = match txn.get("dependencies") {
+        None | Some(&Value::Null) => return Ok(Vec::new()),
+        Some(&Value::Array(ref txn_ids)) => txn_ids,
+        Some(_) => return Err(Error::invalid_params("`dependencies` must be an array")),
+    };
+    txn_ids
+        .iter()
+        .map(|txn_id| match txn_id.as_str() {
+            Some(txn_id) if is_transaction_id(txn_id) => Ok(txn_id[2..].to_lowercase()),
+            _ => Err(Error::invalid_params(format!(
+                "Dependencies must be transaction ids of {} hex characters with 0x",
+                TRANSACTION_ID_LENGTH
+            ))),
+        })
+        .collect()
+}
+
+/// Returns true if the string is a Sawtooth transaction id with 0x
+fn is_transaction_id(txn_id: &str) -> bool {
+    txn_id.starts_with("0x")
+        && txn_id.len() == 2 + TRANSACTION_ID_LENGTH
+        && txn_id[2..].chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn get_address_from_map(txn: &Map<String, Value>, key: &str) -> Result<Option<Address>, Error> {
+    match transform::get_bytes_from_map(txn, key) {
+        Ok(Some(ref address)) if address.len() == 20 => Ok(Some(Address::from_slice(address))),
+        Ok(Some(_)) => Err(Error::invalid_params(format!("Invalid `{}` address", key))),
+        Ok(None) => Ok(None),
+        Err(_) => Err(Error::new(ErrorCode::ParseError)),
+    }
 }
 
 // Always return false
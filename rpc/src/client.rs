@@ -16,21 +16,37 @@
  */
 
 use accounts::{Account, Error as AccountError};
+use aggregator::{BatchAggregator, PendingBatch};
+use config::RpcConfig;
+use connection::{self, Backoff, ValidatorConnection};
 use crypto::digest::Digest;
 use crypto::sha2::Sha512;
+use ethereum_types::U256;
+use evm::{self, Address};
 use filters::FilterManager;
+use jsonrpc_core::Error as RpcError;
+use messages::block_info::{BlockInfo, BlockInfoConfig};
 use messages::seth::{EvmEntry, EvmStateAccount, EvmStorage};
+use metrics;
+use nonces::{NonceManager, SignerNonces};
 use protobuf;
+use recent::RecentTransactions;
 use sawtooth_sdk::messages::batch::{Batch, BatchHeader};
 use sawtooth_sdk::messages::block::Block;
 use sawtooth_sdk::messages::block::BlockHeader;
 use sawtooth_sdk::messages::client_batch_submit::{
-    ClientBatchSubmitRequest, ClientBatchSubmitResponse, ClientBatchSubmitResponse_Status,
+    ClientBatchStatus, ClientBatchStatusRequest, ClientBatchStatusResponse,
+    ClientBatchStatusResponse_Status, ClientBatchStatus_Status, ClientBatchSubmitRequest,
+    ClientBatchSubmitResponse, ClientBatchSubmitResponse_Status,
 };
 use sawtooth_sdk::messages::client_block::{
     ClientBlockGetByIdRequest, ClientBlockGetByNumRequest, ClientBlockGetByTransactionIdRequest,
     ClientBlockGetResponse, ClientBlockGetResponse_Status, ClientBlockListRequest,
-    ClientBlockListResponse,
+    ClientBlockListResponse, ClientBlockListResponse_Status,
+};
+use sawtooth_sdk::messages::client_event::{
+    ClientEventsSubscribeRequest, ClientEventsSubscribeResponse,
+    ClientEventsSubscribeResponse_Status,
 };
 use sawtooth_sdk::messages::client_list_control::ClientPagingControls;
 use sawtooth_sdk::messages::client_peers::{
@@ -45,6 +61,8 @@ use sawtooth_sdk::messages::client_state::{
 use sawtooth_sdk::messages::client_transaction::{
     ClientTransactionGetRequest, ClientTransactionGetResponse, ClientTransactionGetResponse_Status,
 };
+use sawtooth_sdk::messages::events::EventSubscription;
+use sawtooth_sdk::messages::setting::Setting;
 use sawtooth_sdk::messages::transaction::{Transaction as TransactionPb, TransactionHeader};
 use sawtooth_sdk::messages::validator::Message_MessageType;
 use sawtooth_sdk::messaging::stream::*;
@@ -52,11 +70,14 @@ use std::collections::HashMap;
 use std::error::Error as StdError;
 use std::fmt::{Display, Formatter, Result as FmtResult};
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::channel;
 use std::sync::Arc;
 use std::sync::RwLock;
+use std::time::Duration;
+use subscriptions::SubscriptionManager;
 use transactions::{SethReceipt, SethTransaction, Transaction, TransactionKey};
 use transform;
-use uuid;
 
 #[derive(Clone)]
 pub enum BlockKey {
@@ -89,26 +110,92 @@ impl FromStr for BlockKey {
     }
 }
 
+/// The transaction family handled by the Seth transaction processor
+pub const FAMILY_NAME: &str = "seth";
+pub const FAMILY_VERSION: &str = "1.0";
+
 const SETH_NS: &str = "a68b06";
 const BLOCK_INFO_NS: &str = "00b10c";
+const BLOCK_INFO_CONFIG_ADDRESS: &str =
+    "00b10c0100000000000000000000000000000000000000000000000000000000000000";
+/// How many blocks after the latest one in the block info a transaction may be committed and
+/// still read the block info it needs
+const BLOCK_INFO_LOOKAHEAD: u64 = 256;
+/// How many blocks before the latest one contracts can read the info of, with BLOCKHASH
+const BLOCKHASH_DEPTH: u64 = 256;
+/// The account whose permissions apply to accounts that don't set their own
+const GLOBAL_PERMISSIONS_ADDRESS: &str = "0000000000000000000000000000000000000000";
+/// The state address of the `seth.chain_id` Sawtooth setting
+const CHAIN_ID_SETTING_ADDRESS: &str =
+    "000000cabfd247765caac949db4d1169be858ee3b0c44298fc1c14e3b0c44298fc1c14";
+/// The key of the Sawtooth setting that holds the chain id
+const CHAIN_ID_SETTING: &str = "seth.chain_id";
+
+/// How many submitted and relayed transactions are remembered. Older ones are forgotten, so that
+/// the memory seth-rpc uses doesn't grow for as long as it runs.
+const REMEMBERED_TRANSACTIONS: usize = 100_000;
+
+/// How many Ethereum accounts the nonces of relayed transactions are remembered for
+const REMEMBERED_SIGNERS: usize = 100_000;
+
+/// The prefix of the header nonce of relayed transactions. It is followed by the Ethereum
+/// transaction hash, signer and nonce, separated by colons, so that they can be read back from
+/// the chain when seth-rpc restarts.
+const RELAYED_NONCE_PREFIX: &str = "relayed:";
+
+/// What a transaction declares in its header, besides what seth-rpc works out itself
+#[derive(Debug, Clone, Default)]
+pub struct TransactionOptions {
+    /// Other accounts the transaction accesses, such as the contracts called by the contract
+    pub access_list: Vec<Address>,
+    /// The ids of transactions that must be committed before this one
+    pub dependencies: Vec<String>,
+    /// Lets the contract call or create any contract, by declaring the whole Seth namespace as
+    /// the inputs and outputs of the transaction
+    pub chaining: bool,
+}
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Error {
     ValidatorError,
     NoResource,
-    CommunicationError(String),
+    ValidatorUnavailable(String),
     ParseError(String),
     AccountLoadError,
     SigningError,
     InvalidTransaction,
 }
 
+impl Error {
+    /// Returns the name of the variant
+    pub fn kind(&self) -> &'static str {
+        match *self {
+            Error::ValidatorError => "ValidatorError",
+            Error::NoResource => "NoResource",
+            Error::ValidatorUnavailable(_) => "ValidatorUnavailable",
+            Error::ParseError(_) => "ParseError",
+            Error::AccountLoadError => "AccountLoadError",
+            Error::SigningError => "SigningError",
+            Error::InvalidTransaction => "InvalidTransaction",
+        }
+    }
+
+    /// Returns the error to fail a JSON-RPC request with because of this error: `error`, unless
+    /// the validator was unavailable, which clients are told so they can retry
+    pub fn or_unavailable(&self, error: RpcError) -> RpcError {
+        match *self {
+            Error::ValidatorUnavailable(_) => connection::unavailable_error(),
+            _ => error,
+        }
+    }
+}
+
 impl StdError for Error {
     fn description(&self) -> &str {
         match *self {
             Error::ValidatorError => "Validator returned internal error",
             Error::NoResource => "Resource not found",
-            Error::CommunicationError(ref msg) => msg,
+            Error::ValidatorUnavailable(ref msg) => msg,
             Error::ParseError(ref msg) => msg,
             Error::AccountLoadError => "Account loading failed",
             Error::SigningError => "Signing failed",
@@ -126,7 +213,7 @@ impl Display for Error {
         match *self {
             Error::ValidatorError => write!(f, "ValidatorError"),
             Error::NoResource => write!(f, "NoResource"),
-            Error::CommunicationError(ref msg) => write!(f, "CommunicationError: {}", msg),
+            Error::ValidatorUnavailable(ref msg) => write!(f, "ValidatorUnavailable: {}", msg),
             Error::ParseError(ref msg) => write!(f, "ParseError: {}", msg),
             Error::AccountLoadError => write!(f, "AccountLoadError"),
             Error::SigningError => write!(f, "SigningError"),
@@ -135,18 +222,6 @@ impl Display for Error {
     }
 }
 
-impl From<SendError> for Error {
-    fn from(error: SendError) -> Self {
-        Error::CommunicationError(format!("Failed to send msg: {:?}", error))
-    }
-}
-
-impl From<ReceiveError> for Error {
-    fn from(error: ReceiveError) -> Self {
-        Error::CommunicationError(format!("Failed to receive message: {:?}", error))
-    }
-}
-
 impl From<AccountError> for Error {
     fn from(error: AccountError) -> Self {
         match error {
@@ -161,8 +236,8 @@ impl From<AccountError> for Error {
 
 #[derive(Clone)]
 pub struct ValidatorClient<S: MessageSender> {
-    /// The ZMQ message sender
-    sender: Arc<RwLock<S>>,
+    /// The connection to the validator
+    connection: Arc<ValidatorConnection<S>>,
 
     /// The list of accounts that this client has loaded into memory
     loaded_accounts: Arc<RwLock<Vec<Account>>>,
@@ -170,20 +245,69 @@ pub struct ValidatorClient<S: MessageSender> {
     /// The current loaded and unlocked account that can be used for sending transactions
     unlocked_account: Arc<RwLock<Option<Account>>>,
 
+    /// The account that signs the Seth transactions relayed for raw Ethereum transactions, whose
+    /// signers don't have a Sawtooth key
+    relayer: Option<Account>,
+
+    /// The Sawtooth transaction ids of the most recently relayed transactions, by Ethereum
+    /// transaction hash. Replays of older ones are still rejected by their signer's nonce.
+    relayed_transactions: RecentTransactions,
+
+    /// The next nonce of each Ethereum account whose transactions are relayed
+    signer_nonces: SignerNonces,
+
+    /// Whether the transactions relayed before seth-rpc started have been read from the chain
+    relayed_restored: Arc<AtomicBool>,
+
+    /// The ids of the batches that the transactions most recently submitted through this client
+    /// were in, by transaction id. Older transactions are looked up in the blocks instead.
+    submitted_batches: RecentTransactions,
+
+    /// Tracks the nonces of submitted transactions that aren't committed yet
+    nonces: NonceManager,
+
+    /// Collects the batches of concurrent requests into one submit request, if enabled
+    aggregator: Option<BatchAggregator>,
+
+    /// The settings of seth-rpc
+    config: Arc<RpcConfig>,
+
     /// Manages filters
     pub filters: FilterManager,
+
+    /// Manages WebSocket subscriptions
+    pub subscriptions: SubscriptionManager,
 }
 
 impl<S: MessageSender> ValidatorClient<S> {
-    pub fn new(sender: S, accounts: Vec<Account>) -> Self {
+    pub fn new(
+        connection: ValidatorConnection<S>,
+        accounts: Vec<Account>,
+        relayer: Option<Account>,
+        aggregator: Option<BatchAggregator>,
+        config: RpcConfig,
+    ) -> Self {
         ValidatorClient {
-            sender: Arc::new(RwLock::new(sender)),
+            connection: Arc::new(connection),
             loaded_accounts: Arc::new(RwLock::new(accounts)),
             unlocked_account: Arc::new(RwLock::new(None)),
+            relayed_transactions: RecentTransactions::new(REMEMBERED_TRANSACTIONS),
+            signer_nonces: SignerNonces::new(REMEMBERED_SIGNERS),
+            relayed_restored: Arc::new(AtomicBool::new(relayer.is_none())),
+            relayer,
+            submitted_batches: RecentTransactions::new(REMEMBERED_TRANSACTIONS),
+            nonces: NonceManager::new(),
+            aggregator,
+            config: Arc::new(config),
             filters: FilterManager::new(),
+            subscriptions: SubscriptionManager::new(),
         }
     }
 
+    pub fn config(&self) -> &RpcConfig {
+        &self.config
+    }
+
     pub fn loaded_accounts(&self) -> Arc<RwLock<Vec<Account>>> {
         self.loaded_accounts.clone()
     }
@@ -192,6 +316,10 @@ impl<S: MessageSender> ValidatorClient<S> {
         self.unlocked_account.read().unwrap().clone()
     }
 
+    pub fn relayer(&self) -> Option<&Account> {
+        self.relayer.as_ref()
+    }
+
     /// Unlocks the given account, adding it to `self.loaded_accounts` if necessary
     pub fn unlock_account(&self, account: &Account, _duration: Option<u64>) -> Result<(), Error> {
         let mut loaded_accounts = self.loaded_accounts.write().unwrap();
@@ -226,7 +354,7 @@ impl<S: MessageSender> ValidatorClient<S> {
         // If the given address wasn't found in the list of loaded accounts, try
         // loading it and then unlocking it. The key file must be named with the
         // specified address
-        let account = Account::load_from_file(address, &password)?;
+        let account = Account::load_from_file(self.config.key_dir(), address, &password)?;
         loaded_accounts.push(account.clone());
         *unlocked_account = Some(account);
 
@@ -236,95 +364,474 @@ impl<S: MessageSender> ValidatorClient<S> {
         )))
     }
 
-    pub fn request<T, U>(&self, msg_type: Message_MessageType, msg: &T) -> Result<U, String>
+    /// Replaces the connection to the validator after it dropped, and returns the receiver of
+    /// the new one
+    pub fn reconnect(&self) -> MessageReceiver {
+        self.connection.reconnect()
+    }
+
+    /// Returns the waits between attempts to reconnect to the validator
+    pub fn reconnect_backoff(&self) -> Backoff {
+        self.connection.backoff()
+    }
+
+    pub fn send_request<T, U>(&self, msg_type: Message_MessageType, msg: &T) -> Result<U, Error>
     where
         T: protobuf::Message,
         U: protobuf::Message,
     {
-        let msg_bytes = match protobuf::Message::write_to_bytes(msg) {
-            Ok(b) => b,
-            Err(error) => {
-                return Err(format!("Error serializing request: {:?}", error));
-            }
-        };
+        self.send_waiting_request(msg_type, msg, None)
+    }
 
-        let correlation_id = uuid::Uuid::new_v4().to_string();
+    /// Sends a request that the validator may hold for up to `wait` before responding
+    fn send_waiting_request<T, U>(
+        &self,
+        msg_type: Message_MessageType,
+        msg: &T,
+        wait: Option<Duration>,
+    ) -> Result<U, Error>
+    where
+        T: protobuf::Message,
+        U: protobuf::Message,
+    {
+        let msg_bytes = protobuf::Message::write_to_bytes(msg).map_err(|error| {
+            Error::ParseError(format!("Error serializing request: {:?}", error))
+        })?;
 
-        let sender = self.sender.write().unwrap();
+        let result = self
+            .connection
+            .request(msg_type, &msg_bytes, wait)
+            .and_then(|response_msg| {
+                protobuf::parse_from_bytes(&response_msg.content).map_err(|error| {
+                    Error::ParseError(format!("Error parsing response: {:?}", error))
+                })
+            });
+        if let Err(ref error) = result {
+            metrics::client_error(error);
+        }
+        result
+    }
 
-        let mut future = match sender.send(msg_type, &correlation_id, &msg_bytes) {
-            Ok(f) => f,
-            Err(error) => {
-                return Err(format!("Error unwrapping future: {:?}", error));
-            }
+    /// Signs the transaction from the account and submits it
+    pub fn send_transaction(
+        &self,
+        from: &str,
+        txn: &SethTransaction,
+        options: &TransactionOptions,
+    ) -> Result<String, Error> {
+        let (batch, txn_signature) = self.make_batch(from, txn, options)?;
+        self.submit_batch(batch)?;
+        Ok(txn_signature)
+    }
+
+    /// Signs the transaction with the relayer account and submits it, remembering the hash,
+    /// signer and nonce of the Ethereum transaction it was made for
+    pub fn send_relayed_transaction(
+        &self,
+        eth_txn_hash: &str,
+        signer: &str,
+        signer_nonce: u64,
+        txn: &SethTransaction,
+    ) -> Result<String, Error> {
+        let relayer = self.relayer.as_ref().ok_or(Error::AccountLoadError)?;
+        let txn = sign_transaction(
+            relayer,
+            txn,
+            self.transaction_addresses(relayer, txn, &TransactionOptions::default())?,
+            self.transaction_dependencies(relayer, txn, &[]),
+            format!(
+                "{}{}:{}:{}",
+                RELAYED_NONCE_PREFIX, eth_txn_hash, signer, signer_nonce
+            ),
+        )?;
+        let txn_signature = txn.header_signature.clone();
+        self.submit_batch(sign_batch(relayer, vec![txn])?)?;
+
+        self.relayed_transactions
+            .insert(String::from(eth_txn_hash), txn_signature.clone());
+        Ok(txn_signature)
+    }
+
+    /// Reads the transactions the relayer relayed from the chain, so that they can't be replayed
+    /// after seth-rpc restarts
+    pub fn restore_relayed_transactions(&self) -> Result<(), Error> {
+        let relayer = match self.relayer {
+            Some(ref relayer) => relayer,
+            None => return Ok(()),
         };
 
-        let response_msg = match future.get() {
-            Ok(m) => m,
-            Err(error) => {
-                return Err(format!("Error getting future: {:?}", error));
+        // The blocks are read from the head back, and their transactions restored oldest first
+        let mut relayed = Vec::new();
+        let mut block = self.get_current_block()?;
+        loop {
+            let header: BlockHeader =
+                protobuf::parse_from_bytes(&block.header).map_err(|error| {
+                    Error::ParseError(format!("Error parsing block header: {:?}", error))
+                })?;
+            let mut block_relayed = Vec::new();
+            for batch in block.get_batches() {
+                for txn in batch.get_transactions() {
+                    let txn_header: TransactionHeader = protobuf::parse_from_bytes(&txn.header)
+                        .map_err(|error| {
+                            Error::ParseError(format!(
+                                "Error parsing transaction header: {:?}",
+                                error
+                            ))
+                        })?;
+                    if txn_header.signer_public_key != relayer.public_key() {
+                        continue;
+                    }
+                    if let Some(eth_txn) = parse_relayed_nonce(&txn_header.nonce) {
+                        block_relayed.push((eth_txn, txn.header_signature.clone()));
+                    }
+                }
             }
-        };
+            relayed.push(block_relayed);
 
-        let response: U = match protobuf::parse_from_bytes(&response_msg.content) {
-            Ok(r) => r,
-            Err(error) => {
-                return Err(format!("Error parsing response: {:?}", error));
+            if header.block_num == 0 {
+                break;
             }
-        };
+            block = self.get_block(BlockKey::Signature(header.previous_block_id))?;
+        }
 
-        Ok(response)
+        for ((eth_txn_hash, signer, signer_nonce), txn_id) in relayed.into_iter().rev().flatten() {
+            self.signer_nonces.restore(&signer, signer_nonce);
+            self.relayed_transactions.insert(eth_txn_hash, txn_id);
+        }
+        self.relayed_restored.store(true, Ordering::SeqCst);
+        Ok(())
     }
 
-    pub fn send_request<T, U>(&self, msg_type: Message_MessageType, msg: &T) -> Result<U, Error>
-    where
-        T: protobuf::Message,
-        U: protobuf::Message,
-    {
-        let msg_bytes = protobuf::Message::write_to_bytes(msg).map_err(|error| {
-            Error::ParseError(format!("Error serializing request: {:?}", error))
-        })?;
+    /// Returns true once the transactions relayed before seth-rpc started have been restored
+    pub fn relayed_restored(&self) -> bool {
+        self.relayed_restored.load(Ordering::SeqCst)
+    }
+
+    /// Returns true if the Ethereum transaction was already relayed
+    pub fn is_relayed(&self, eth_txn_hash: &str) -> bool {
+        self.relayed_transactions.contains_key(eth_txn_hash)
+    }
+
+    /// Claims the nonce of a raw transaction for its Ethereum signer, which must be the signer's
+    /// next nonce if it is known. Returns the expected nonce otherwise.
+    pub fn claim_signer_nonce(&self, signer: &str, nonce: u64) -> Result<(), u64> {
+        self.signer_nonces.claim(signer, nonce)
+    }
+
+    /// Gives a claimed nonce back to the signer after its transaction couldn't be relayed
+    pub fn release_signer_nonce(&self, signer: &str, nonce: u64) {
+        self.signer_nonces.release(signer, nonce)
+    }
+
+    /// Returns the Sawtooth transaction id of a recently relayed transaction given its Ethereum
+    /// transaction hash. Any other id is returned as is.
+    pub fn resolve_transaction_id(&self, txn_id: &str) -> String {
+        self.relayed_transactions
+            .get(txn_id)
+            .unwrap_or_else(|| String::from(txn_id))
+    }
+
+    /// Submits the batch, together with the batches of other requests if aggregation is enabled
+    pub fn submit_batch(&self, batch: Batch) -> Result<(), Error> {
+        let aggregator = match self.aggregator {
+            Some(ref aggregator) => aggregator,
+            None => return self.submit_batches(vec![batch]),
+        };
 
-        let correlation_id = uuid::Uuid::new_v4().to_string();
+        let (sender, receiver) = channel();
+        let leader = aggregator.add(PendingBatch {
+            batch,
+            result: sender,
+        });
+        if leader {
+            self.submit_aggregated(aggregator.close_window());
+        }
+        receiver.recv().unwrap_or(Err(Error::ValidatorError))
+    }
 
-        let sender = self.sender.write().unwrap();
-        let mut future = sender.send(msg_type, &correlation_id, &msg_bytes)?;
-        let response_msg = future.get()?;
-        protobuf::parse_from_bytes(&response_msg.content)
-            .map_err(|error| Error::ParseError(format!("Error parsing response: {:?}", error)))
+    /// Submits batches collected from several requests and sends each request its result. The
+    /// validator rejects the whole request if one batch is invalid, so in that case the batches
+    /// are resubmitted one by one to find out which ones are valid.
+    fn submit_aggregated(&self, pending: Vec<PendingBatch>) {
+        debug!("Submitting {} aggregated batches", pending.len());
+        let batches = pending
+            .iter()
+            .map(|pending| pending.batch.clone())
+            .collect();
+        match self.submit_batches(batches) {
+            Err(Error::InvalidTransaction) if pending.len() > 1 => {
+                for pending in pending {
+                    let result = self.submit_batches(vec![pending.batch]);
+                    pending.result.send(result).ok();
+                }
+            }
+            result => {
+                for pending in pending {
+                    pending.result.send(result.clone()).ok();
+                }
+            }
+        }
     }
 
-    pub fn send_transaction(&self, from: &str, txn: &SethTransaction) -> Result<String, Error> {
-        let (batch, txn_signature) = self.make_batch(from, txn)?;
+    /// Submits the batches to the validator in a single request
+    pub fn submit_batches(&self, batches: Vec<Batch>) -> Result<(), Error> {
+        let batch_ids: Vec<(String, String)> = batches
+            .iter()
+            .flat_map(|batch| {
+                batch
+                    .get_transactions()
+                    .iter()
+                    .map(move |txn| (txn.header_signature.clone(), batch.header_signature.clone()))
+            })
+            .collect();
+        // Transactions that aren't Seth transactions are rejected by the validator anyway
+        let nonces: Vec<(String, u64, String, String)> = batches
+            .iter()
+            .flat_map(|batch| {
+                batch.get_transactions().iter().filter_map(move |txn| {
+                    Transaction::try_from(txn.clone()).ok().map(|txn| {
+                        (
+                            txn.from_addr(),
+                            txn.nonce(),
+                            txn.hash(),
+                            batch.header_signature.clone(),
+                        )
+                    })
+                })
+            })
+            .collect();
 
         let mut request = ClientBatchSubmitRequest::new();
-        request.set_batches(protobuf::RepeatedField::from_vec(vec![batch]));
+        request.set_batches(protobuf::RepeatedField::from_vec(batches));
 
         let response: ClientBatchSubmitResponse =
             self.send_request(Message_MessageType::CLIENT_BATCH_SUBMIT_REQUEST, &request)?;
 
+        metrics::batch_submitted(response.status);
         match response.status {
             ClientBatchSubmitResponse_Status::STATUS_UNSET => Err(Error::ValidatorError),
-            ClientBatchSubmitResponse_Status::OK => Ok(txn_signature),
+            ClientBatchSubmitResponse_Status::OK => {
+                self.submitted_batches.extend(batch_ids);
+                for (address, nonce, txn_id, batch_id) in nonces {
+                    self.nonces.submitted(&address, nonce, &txn_id, &batch_id);
+                }
+                Ok(())
+            }
             ClientBatchSubmitResponse_Status::INTERNAL_ERROR => Err(Error::ValidatorError),
             ClientBatchSubmitResponse_Status::INVALID_BATCH => Err(Error::InvalidTransaction),
             ClientBatchSubmitResponse_Status::QUEUE_FULL => Err(Error::ValidatorError),
         }
     }
 
-    pub fn make_batch(&self, from: &str, txn: &SethTransaction) -> Result<(Batch, String), Error> {
-        let payload = protobuf::Message::write_to_bytes(&txn.to_pb()).map_err(|error| {
-            Error::ParseError(format!("Error serializing payload: {:?}", error))
-        })?;
+    /// Returns the nonce the next transaction from the account should have, after the
+    /// transactions from it that were submitted through this client and aren't committed yet
+    pub fn get_pending_nonce(&self, address: &str, state_nonce: u64) -> Result<u64, Error> {
+        let address = address.to_lowercase();
+        self.resync_nonces(&address, state_nonce)?;
+        Ok(self.nonces.next(&address, state_nonce))
+    }
+
+    /// Reserves `count` consecutive nonces for transactions from the account and returns the
+    /// first one. The nonces must be released if the transactions can't be submitted.
+    pub fn reserve_nonces(
+        &self,
+        address: &str,
+        state_nonce: u64,
+        count: u64,
+    ) -> Result<u64, Error> {
+        let address = address.to_lowercase();
+        self.resync_nonces(&address, state_nonce)?;
+        Ok(self.nonces.reserve(&address, state_nonce, count))
+    }
+
+    pub fn release_nonces(&self, address: &str, first: u64, count: u64) {
+        self.nonces.release(&address.to_lowercase(), first, count)
+    }
+
+    /// Forgets the nonces of committed transactions. The transaction with the lowest nonce still
+    /// in flight should be pending; if its batch is invalid or the validator doesn't know about
+    /// it, it and the transactions after it will never be committed, so their nonces are reused.
+    fn resync_nonces(&self, address: &str, state_nonce: u64) -> Result<(), Error> {
+        let (nonce, batch_id) = match self.nonces.resync(address, state_nonce) {
+            Some((nonce, Some(batch_id))) => (nonce, batch_id),
+            _ => return Ok(()),
+        };
+        let lost = match self.get_batch_status(&batch_id, None) {
+            Ok(status) => match status.status {
+                ClientBatchStatus_Status::INVALID | ClientBatchStatus_Status::UNKNOWN => true,
+                _ => false,
+            },
+            Err(Error::NoResource) => true,
+            Err(error) => return Err(error),
+        };
+        if lost {
+            debug!("Releasing nonces of {} from {}", address, nonce);
+            self.nonces.release_from(address, nonce);
+        }
+        Ok(())
+    }
+
+    /// Returns the id of the batch the transaction was submitted in, if it was recently submitted
+    /// through this client
+    pub fn get_batch_id(&self, txn_id: &str) -> Option<String> {
+        self.submitted_batches.get(txn_id)
+    }
+
+    /// Returns the status of the batch. If `wait` is set, the validator waits up to that many
+    /// seconds for the batch to be committed or found invalid before responding.
+    pub fn get_batch_status(
+        &self,
+        batch_id: &str,
+        wait: Option<u32>,
+    ) -> Result<ClientBatchStatus, Error> {
+        let mut request = ClientBatchStatusRequest::new();
+        request.set_batch_ids(protobuf::RepeatedField::from_vec(vec![String::from(
+            batch_id,
+        )]));
+        if let Some(timeout) = wait {
+            request.set_wait(true);
+            request.set_timeout(timeout);
+        }
+        let mut response: ClientBatchStatusResponse = self.send_waiting_request(
+            Message_MessageType::CLIENT_BATCH_STATUS_REQUEST,
+            &request,
+            wait.map(|timeout| Duration::from_secs(u64::from(timeout))),
+        )?;
 
+        match response.status {
+            ClientBatchStatusResponse_Status::OK => {
+                response.batch_statuses.pop().ok_or(Error::NoResource)
+            }
+            ClientBatchStatusResponse_Status::NO_RESOURCE => Err(Error::NoResource),
+            ClientBatchStatusResponse_Status::STATUS_UNSET
+            | ClientBatchStatusResponse_Status::INTERNAL_ERROR
+            | ClientBatchStatusResponse_Status::INVALID_ID => Err(Error::ValidatorError),
+        }
+    }
+
+    /// Signs the transactions from the account and submits them, in order, in a single batch,
+    /// so that either all of them are committed or none are. Every transaction may access the
+    /// accounts in the access list, and the batch depends on the given transactions. Returns the
+    /// ids of the transactions and the id of the batch.
+    pub fn send_batch(
+        &self,
+        from: &str,
+        txns: &[SethTransaction],
+        options: &TransactionOptions,
+    ) -> Result<(Vec<String>, String), Error> {
+        let account = match txns.first() {
+            Some(txn) => self.signing_account(from, txn)?,
+            None => return Err(Error::ParseError(String::from("Batch has no transactions"))),
+        };
+        let txns = txns
+            .iter()
+            .enumerate()
+            .map(|(index, txn)| {
+                // The transactions in a batch are applied in order, so only the first one needs
+                // dependencies
+                let dependencies = match index {
+                    0 => self.transaction_dependencies(&account, txn, &options.dependencies),
+                    _ => Vec::new(),
+                };
+                sign_transaction(
+                    &account,
+                    txn,
+                    self.transaction_addresses(&account, txn, options)?,
+                    dependencies,
+                    String::new(),
+                )
+            })
+            .collect::<Result<Vec<TransactionPb>, Error>>()?;
+        let txn_ids = txns
+            .iter()
+            .map(|txn| txn.header_signature.clone())
+            .collect();
+
+        let batch = sign_batch(&account, txns)?;
+        let batch_id = batch.header_signature.clone();
+        self.submit_batch(batch)?;
+        Ok((txn_ids, batch_id))
+    }
+
+    pub fn make_batch(
+        &self,
+        from: &str,
+        txn: &SethTransaction,
+        options: &TransactionOptions,
+    ) -> Result<(Batch, String), Error> {
+        let account = self.signing_account(from, txn)?;
+        let addresses = self.transaction_addresses(&account, txn, options)?;
+        let dependencies = self.transaction_dependencies(&account, txn, &options.dependencies);
+        let txn = sign_transaction(&account, txn, addresses, dependencies, String::new())?;
+        let txn_signature = txn.header_signature.clone();
+        Ok((sign_batch(&account, vec![txn])?, txn_signature))
+    }
+
+    /// Returns the state addresses to declare as the inputs and outputs of a transaction signed
+    /// by the account. The latest block in the block info is read if the transaction executes
+    /// code.
+    fn transaction_addresses(
+        &self,
+        account: &Account,
+        txn: &SethTransaction,
+        options: &TransactionOptions,
+    ) -> Result<TransactionAddresses, Error> {
+        if self.config.wide_inputs || options.chaining {
+            let namespaces = vec![String::from(SETH_NS), String::from(BLOCK_INFO_NS)];
+            return Ok(TransactionAddresses {
+                inputs: namespaces.clone(),
+                outputs: namespaces,
+            });
+        }
+        let sender = transform::hex_str_to_bytes(account.address())
+            .map(|address| Address::from_slice(&address))
+            .unwrap_or_else(Address::zero);
+        let latest_block = match *txn {
+            SethTransaction::CreateContractAccount(_) | SethTransaction::MessageCall(_) => Some(
+                self.get_block_info_config(None)?
+                    .map_or(0, |config| config.latest_block),
+            ),
+            SethTransaction::CreateExternalAccount(_) | SethTransaction::SetPermissions(_) => None,
+        };
+        Ok(TransactionAddresses::new(
+            &sender,
+            txn,
+            &options.access_list,
+            latest_block,
+        ))
+    }
+
+    /// Returns the transactions a transaction signed by the account depends on: the given ones,
+    /// and the in-flight transaction from the account with the previous nonce, so that the
+    /// validator doesn't apply them out of order
+    fn transaction_dependencies(
+        &self,
+        account: &Account,
+        txn: &SethTransaction,
+        dependencies: &[String],
+    ) -> Vec<String> {
+        let mut dependencies = dependencies.to_vec();
+        let previous = txn.nonce().checked_sub(1).and_then(|nonce| {
+            self.nonces
+                .transaction_id(&account.address().to_lowercase(), nonce)
+        });
+        if let Some(previous) = previous {
+            if !dependencies.contains(&previous) {
+                dependencies.push(previous);
+            }
+        }
+        dependencies
+    }
+
+    /// Returns the account that signs a transaction from the given address
+    fn signing_account(&self, from: &str, txn: &SethTransaction) -> Result<Account, Error> {
         let unlocked_account = self.unlocked_account.read().unwrap().clone();
         let account = match (unlocked_account, txn) {
             (Some(ref acc), SethTransaction::CreateExternalAccount(ref txnpb)) => {
                 match (txnpb.to.len(), from == acc.address()) {
                     // The transaction is setting up a new account without a moderator
-                    (0, _) => {
-                        Account::load_from_file(from, &None).map_err(|_| Error::AccountLoadError)?
-                    }
+                    (0, _) => Account::load_from_file(self.config.key_dir(), from, &None)
+                        .map_err(|_| Error::AccountLoadError)?,
                     (_, true) => acc.clone(),
                     (_, false) => {
                         error!("Account with address `{}` not found.", from);
@@ -343,7 +850,8 @@ impl<S: MessageSender> ValidatorClient<S> {
             }
             (None, SethTransaction::CreateExternalAccount(ref txnpb)) => {
                 if txnpb.to.is_empty() {
-                    Account::load_from_file(from, &None).map_err(|_| Error::AccountLoadError)?
+                    Account::load_from_file(self.config.key_dir(), from, &None)
+                        .map_err(|_| Error::AccountLoadError)?
                 } else {
                     error!("Account with address `{}` not found.", from);
                     return Err(Error::AccountLoadError);
@@ -355,60 +863,13 @@ impl<S: MessageSender> ValidatorClient<S> {
             }
         };
 
-        let mut txn_header = TransactionHeader::new();
-        txn_header.set_batcher_public_key(String::from(account.public_key()));
-        txn_header.set_family_name(String::from("seth"));
-        txn_header.set_family_version(String::from("1.0"));
-        txn_header.set_inputs(protobuf::RepeatedField::from_vec(vec![
-            String::from(SETH_NS),
-            String::from(BLOCK_INFO_NS),
-        ]));
-        txn_header.set_outputs(protobuf::RepeatedField::from_vec(vec![
-            String::from(SETH_NS),
-            String::from(BLOCK_INFO_NS),
-        ]));
-
-        let mut sha = Sha512::new();
-        sha.input(&payload);
-        let hash = sha.result_str();
-        txn_header.set_payload_sha512(hash);
-
-        txn_header.set_signer_public_key(String::from(account.public_key()));
-        let txn_header_bytes = protobuf::Message::write_to_bytes(&txn_header).map_err(|error| {
-            Error::ParseError(format!("Error serializing transaction header: {:?}", error))
-        })?;
-
-        let txn_signature = account.sign(&txn_header_bytes)?;
-
-        let mut txn = TransactionPb::new();
-        txn.set_header(txn_header_bytes);
-        txn.set_header_signature(txn_signature.clone());
-        txn.set_payload(payload);
-
-        let mut batch_header = BatchHeader::new();
-        batch_header.set_signer_public_key(String::from(account.public_key()));
-        batch_header.set_transaction_ids(protobuf::RepeatedField::from_vec(vec![
-            txn_signature.clone()
-        ]));
-        let batch_header_bytes =
-            protobuf::Message::write_to_bytes(&batch_header).map_err(|error| {
-                Error::ParseError(format!("Error serializing batch header: {:?}", error))
-            })?;
-
-        let batch_signature = account.sign(&batch_header_bytes)?;
-
-        let mut batch = Batch::new();
-        batch.set_header(batch_header_bytes);
-        batch.set_header_signature(batch_signature);
-        batch.set_transactions(protobuf::RepeatedField::from_vec(vec![txn]));
-
-        Ok((batch, txn_signature))
+        Ok(account)
     }
 
     pub fn get_receipts_from_block(
         &self,
         block: &Block,
-    ) -> Result<HashMap<String, SethReceipt>, String> {
+    ) -> Result<HashMap<String, SethReceipt>, Error> {
         let batches = &block.batches;
         let mut transactions = Vec::new();
         for batch in batches.iter() {
@@ -425,15 +886,7 @@ impl<S: MessageSender> ValidatorClient<S> {
             }
         }
 
-        let receipts = self
-            .get_receipts(&transactions)
-            .map_err(|error| match error {
-                Error::ValidatorError => String::from("Received internal error from validator"),
-                Error::NoResource => String::from("Missing receipt"),
-                _ => String::from("Unknown error"),
-            })?;
-
-        Ok(receipts)
+        self.get_receipts(&transactions)
     }
 
     pub fn get_receipts(
@@ -574,81 +1027,117 @@ impl<S: MessageSender> ValidatorClient<S> {
         &self,
         account_address: &str,
         block: BlockKey,
-    ) -> Result<Option<EvmEntry>, String> {
-        let address = String::from(SETH_NS) + account_address + "000000000000000000000000";
-        let mut request = ClientStateGetRequest::new();
-        request.set_address(address);
-        match block {
-            BlockKey::Latest => {}
-            BlockKey::Earliest => {
-                match self.block_id_to_state_root(String::from("0000000000000000")) {
-                    Ok(state_root) => {
-                        request.set_state_root(state_root);
-                    }
-                    Err(error) => {
-                        return Err(format!("{:?}", error));
-                    }
-                }
-            }
-            BlockKey::Signature(block_id) => match self.block_id_to_state_root(block_id) {
-                Ok(state_root) => {
-                    request.set_state_root(state_root);
-                }
-                Err(error) => {
-                    return Err(format!("{:?}", error));
-                }
+    ) -> Result<Option<EvmEntry>, Error> {
+        let state_root = match block {
+            BlockKey::Latest => None,
+            block => Some(self.get_state_root(block)?),
+        };
+        self.get_entry_at_root(account_address, state_root)
+    }
+
+    /// Gets the entry of the account at the given state root, or in the latest state if no
+    /// state root is given
+    pub fn get_entry_at_root(
+        &self,
+        account_address: &str,
+        state_root: Option<String>,
+    ) -> Result<Option<EvmEntry>, Error> {
+        match self.get_state(&account_state_address(account_address), state_root)? {
+            Some(state_data) => match protobuf::parse_from_bytes(&state_data) {
+                Ok(e) => Ok(Some(e)),
+                Err(error) => Err(Error::ParseError(format!(
+                    "Failed to deserialize EVM entry: {:?}",
+                    error
+                ))),
             },
-            BlockKey::Number(block_num) => match self.block_num_to_state_root(block_num) {
-                Ok(state_root) => {
-                    request.set_state_root(state_root);
-                }
-                Err(error) => {
-                    return Err(format!("{:?}", error));
-                }
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_block_info_config(
+        &self,
+        state_root: Option<String>,
+    ) -> Result<Option<BlockInfoConfig>, Error> {
+        match self.get_state(BLOCK_INFO_CONFIG_ADDRESS, state_root)? {
+            Some(state_data) => match protobuf::parse_from_bytes(&state_data) {
+                Ok(config) => Ok(Some(config)),
+                Err(error) => Err(Error::ParseError(format!(
+                    "Failed to deserialize block info config: {:?}",
+                    error
+                ))),
             },
-            BlockKey::Transaction(transaction_id) => {
-                match self.transaction_to_state_root(transaction_id) {
-                    Ok(state_root) => {
-                        request.set_state_root(state_root);
-                    }
-                    Err(error) => {
-                        return Err(format!("{:?}", error));
-                    }
-                }
-            }
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the chain id, which is read from the `seth.chain_id` Sawtooth setting if seth-rpc
+    /// is configured to, so that every seth-rpc of the network agrees on it. The configured chain
+    /// id is used if the setting isn't set.
+    pub fn chain_id(&self) -> Result<u64, Error> {
+        if !self.config.chain_id_from_settings {
+            return Ok(self.config.chain_id);
+        }
+        let setting: Setting = match self.get_state(CHAIN_ID_SETTING_ADDRESS, None)? {
+            Some(state_data) => protobuf::parse_from_bytes(&state_data).map_err(|error| {
+                Error::ParseError(format!("Failed to deserialize setting: {:?}", error))
+            })?,
+            None => return Ok(self.config.chain_id),
+        };
+        match setting
+            .entries
+            .iter()
+            .find(|entry| entry.key == CHAIN_ID_SETTING)
+        {
+            Some(entry) => entry.value.parse().map_err(|_| {
+                Error::ParseError(format!(
+                    "Invalid value for setting {}: {}",
+                    CHAIN_ID_SETTING, entry.value
+                ))
+            }),
+            None => Ok(self.config.chain_id),
+        }
+    }
+
+    pub fn get_block_info(
+        &self,
+        block_num: u64,
+        state_root: Option<String>,
+    ) -> Result<Option<BlockInfo>, Error> {
+        let address = format!("{}00{:062x}", BLOCK_INFO_NS, block_num);
+        match self.get_state(&address, state_root)? {
+            Some(state_data) => match protobuf::parse_from_bytes(&state_data) {
+                Ok(info) => Ok(Some(info)),
+                Err(error) => Err(Error::ParseError(format!(
+                    "Failed to deserialize block info: {:?}",
+                    error
+                ))),
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Gets the data stored at the state address
+    fn get_state(
+        &self,
+        address: &str,
+        state_root: Option<String>,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        let mut request = ClientStateGetRequest::new();
+        request.set_address(String::from(address));
+        if let Some(state_root) = state_root {
+            request.set_state_root(state_root);
         }
 
         let response: ClientStateGetResponse =
-            self.request(Message_MessageType::CLIENT_STATE_GET_REQUEST, &request)?;
+            self.send_request(Message_MessageType::CLIENT_STATE_GET_REQUEST, &request)?;
 
-        let state_data = match response.status {
-            ClientStateGetResponse_Status::STATUS_UNSET => {
-                return Err(String::from("Internal error"));
-            }
-            ClientStateGetResponse_Status::OK => response.value,
-            ClientStateGetResponse_Status::NO_RESOURCE => {
-                return Ok(None);
-            }
-            ClientStateGetResponse_Status::INTERNAL_ERROR => {
-                return Err(String::from("Internal error"));
-            }
-            ClientStateGetResponse_Status::NOT_READY => {
-                return Err(String::from("Validator isn't ready"));
-            }
-            ClientStateGetResponse_Status::NO_ROOT => {
-                return Err(String::from("No root"));
-            }
-            ClientStateGetResponse_Status::INVALID_ADDRESS => {
-                return Err(String::from("Invalid address"));
-            }
-            ClientStateGetResponse_Status::INVALID_ROOT => {
-                return Err(String::from("Invalid root"));
+        match response.status {
+            ClientStateGetResponse_Status::OK => Ok(Some(response.value)),
+            ClientStateGetResponse_Status::NO_RESOURCE => Ok(None),
+            status => {
+                error!("Failed to get state at {}: {:?}", address, status);
+                Err(Error::ValidatorError)
             }
-        };
-
-        match protobuf::parse_from_bytes(&state_data) {
-            Ok(e) => Ok(Some(e)),
-            Err(error) => Err(format!("Failed to deserialize EVM entry: {:?}", error)),
         }
     }
 
@@ -656,7 +1145,7 @@ impl<S: MessageSender> ValidatorClient<S> {
         &self,
         account_address: &str,
         block: BlockKey,
-    ) -> Result<Option<EvmStateAccount>, String> {
+    ) -> Result<Option<EvmStateAccount>, Error> {
         self.get_entry(account_address, block)
             .map(|option| option.map(|mut entry| entry.take_account()))
     }
@@ -665,7 +1154,7 @@ impl<S: MessageSender> ValidatorClient<S> {
         &self,
         account_address: &str,
         block: BlockKey,
-    ) -> Result<Option<Vec<EvmStorage>>, String> {
+    ) -> Result<Option<Vec<EvmStorage>>, Error> {
         self.get_entry(account_address, block)
             .map(|option| option.map(|mut entry| entry.take_storage().into_vec()))
     }
@@ -673,28 +1162,20 @@ impl<S: MessageSender> ValidatorClient<S> {
     pub fn get_storage_at(
         &self,
         account_address: &str,
-        storage_address: &str,
+        position: &U256,
         block: BlockKey,
-    ) -> Result<Option<Vec<u8>>, String> {
+    ) -> Result<Option<Vec<u8>>, Error> {
         let storage = self.get_storage(account_address, block)?;
 
-        match storage {
-            Some(storage) => {
-                let position = match transform::hex_str_to_bytes(&storage_address) {
-                    Some(p) => p,
-                    None => {
-                        return Err(String::from("Failed to decode position, invalid hex."));
-                    }
-                };
-                for entry in storage {
-                    if entry.key == position {
-                        return Ok(Some(entry.value));
-                    }
-                }
-                Ok(None)
-            }
-            None => Ok(None),
-        }
+        // Keys are compared as numbers, since they aren't always stored as full words
+        Ok(storage.and_then(|storage| {
+            storage
+                .into_iter()
+                .find(|entry| {
+                    entry.key.len() <= 32 && U256::from_big_endian(&entry.key) == *position
+                })
+                .map(|entry| entry.value)
+        }))
     }
 
     pub fn get_current_block(&self) -> Result<Block, Error> {
@@ -703,79 +1184,69 @@ impl<S: MessageSender> ValidatorClient<S> {
         let mut request = ClientBlockListRequest::new();
         request.set_paging(paging);
 
-        let response: ClientBlockListResponse =
+        let mut response: ClientBlockListResponse =
             self.send_request(Message_MessageType::CLIENT_BLOCK_LIST_REQUEST, &request)?;
 
-        let block = &response.blocks[0];
-        Ok(block.clone())
-    }
-
-    pub fn get_current_block_number(&self) -> Result<u64, Error> {
-        let block = self.get_current_block()?;
-        let block_header: BlockHeader =
-            protobuf::parse_from_bytes(&block.header).map_err(|error| {
-                Error::ParseError(format!("Error parsing block_header: {:?}", error))
-            })?;
-        Ok(block_header.block_num)
-    }
-
-    pub fn get_blocks_since(&self, since: u64) -> Result<Vec<(u64, Block)>, Error> {
-        let block = self.get_current_block()?;
-        let block_header: BlockHeader =
-            protobuf::parse_from_bytes(&block.header).map_err(|error| {
-                Error::ParseError(format!("Error parsing block_header: {:?}", error))
-            })?;
-        let block_num = block_header.block_num;
-        if block_num <= since {
-            return Ok(Vec::new());
+        match response.status {
+            ClientBlockListResponse_Status::OK => response.blocks.pop().ok_or(Error::NoResource),
+            ClientBlockListResponse_Status::NO_ROOT
+            | ClientBlockListResponse_Status::NO_RESOURCE => Err(Error::NoResource),
+            _ => Err(Error::ValidatorError),
         }
+    }
 
-        let mut blocks = Vec::with_capacity((block_num - (since + 1)) as usize);
-        for block_num in (since + 1)..block_num {
-            let block = self.get_block(BlockKey::Number(block_num))?;
-            let block_header: BlockHeader =
-                protobuf::parse_from_bytes(&block.header).map_err(|error| {
+    /// Gets the state root hash of the given block
+    pub fn get_state_root(&self, block: BlockKey) -> Result<String, Error> {
+        self.get_block(block).and_then(|block| {
+            protobuf::parse_from_bytes(&block.header)
+                .map_err(|error| {
                     Error::ParseError(format!("Error parsing block_header: {:?}", error))
-                })?;
-            let block_num = block_header.block_num;
-            blocks.push((block_num, block));
-        }
-        blocks.push((block_num, block));
-
-        Ok(blocks)
+                })
+                .map(|block_header: BlockHeader| block_header.state_root_hash)
+        })
     }
 
-    fn block_num_to_state_root(&self, block_num: u64) -> Result<String, Error> {
-        self.get_block(BlockKey::Number(block_num))
-            .and_then(|block| {
-                protobuf::parse_from_bytes(&block.header)
-                    .map_err(|error| {
-                        Error::ParseError(format!("Error parsing block_header: {:?}", error))
-                    })
-                    .map(|block_header: BlockHeader| block_header.state_root_hash)
+    /// Subscribes to the given validator events. The events are delivered to the receiver that
+    /// was created along with this client's sender. If `last_known_block_id` is given, the
+    /// validator first delivers the events of the blocks committed after it. Returns
+    /// `Error::NoResource` if the validator doesn't know that block.
+    pub fn subscribe_to_events(
+        &self,
+        event_types: &[&str],
+        last_known_block_id: Option<&str>,
+    ) -> Result<(), Error> {
+        let subscriptions = event_types
+            .iter()
+            .map(|event_type| {
+                let mut subscription = EventSubscription::new();
+                subscription.set_event_type(String::from(*event_type));
+                subscription
             })
-    }
+            .collect();
+        let mut request = ClientEventsSubscribeRequest::new();
+        request.set_subscriptions(protobuf::RepeatedField::from_vec(subscriptions));
+        if let Some(block_id) = last_known_block_id {
+            request.set_last_known_block_ids(protobuf::RepeatedField::from_vec(vec![
+                String::from(block_id),
+            ]));
+        }
 
-    fn block_id_to_state_root(&self, block_id: String) -> Result<String, Error> {
-        self.get_block(BlockKey::Signature(block_id))
-            .and_then(|block| {
-                protobuf::parse_from_bytes(&block.header)
-                    .map_err(|error| {
-                        Error::ParseError(format!("Error parsing block_header: {:?}", error))
-                    })
-                    .map(|block_header: BlockHeader| block_header.state_root_hash)
-            })
-    }
+        let response: ClientEventsSubscribeResponse = self.send_request(
+            Message_MessageType::CLIENT_EVENTS_SUBSCRIBE_REQUEST,
+            &request,
+        )?;
 
-    fn transaction_to_state_root(&self, transaction_id: String) -> Result<String, Error> {
-        self.get_block(BlockKey::Transaction(transaction_id))
-            .and_then(|block| {
-                protobuf::parse_from_bytes(&block.header)
-                    .map_err(|error| {
-                        Error::ParseError(format!("Error parsing block_header: {:?}", error))
-                    })
-                    .map(|block_header: BlockHeader| block_header.state_root_hash)
-            })
+        match response.status {
+            ClientEventsSubscribeResponse_Status::OK => Ok(()),
+            ClientEventsSubscribeResponse_Status::UNKNOWN_BLOCK => Err(Error::NoResource),
+            _ => {
+                error!(
+                    "Failed to subscribe to events: {:?} {}",
+                    response.status, response.response_message
+                );
+                Err(Error::ValidatorError)
+            }
+        }
     }
 
     pub fn get_peers(&self) -> Result<usize, Error> {
@@ -797,3 +1268,164 @@ impl<S: MessageSender> ValidatorClient<S> {
         Ok(n)
     }
 }
+
+/// Returns the reason the validator gave for the transaction in an invalid batch. When another
+/// transaction in the batch was the invalid one, this one wasn't applied either.
+pub fn invalid_transaction_message(status: &ClientBatchStatus, txn_id: &str) -> String {
+    status
+        .get_invalid_transactions()
+        .iter()
+        .find(|invalid| invalid.transaction_id == txn_id)
+        .map(|invalid| invalid.message.clone())
+        .unwrap_or_else(|| String::from("Another transaction in the batch was invalid"))
+}
+
+/// Returns the address of the state entry that holds the account with the given address
+fn account_state_address(address: &str) -> String {
+    String::from(SETH_NS) + address + "000000000000000000000000"
+}
+
+/// The state addresses a transaction is allowed to read and write. The validator's scheduler
+/// only runs transactions in parallel if they don't write what the other reads.
+struct TransactionAddresses {
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+}
+
+impl TransactionAddresses {
+    /// Lists the entries the transaction accesses: the sender, the account it creates or calls,
+    /// and the accounts in the access list, which contracts may call or create. The global
+    /// permissions are read to check the sender's permissions. A transaction that executes code
+    /// reads the block info config and the info of the latest block, and contracts can read the
+    /// info of the 256 blocks before it. Which block is the latest depends on the block the
+    /// transaction ends up in, so the info of the blocks up to 256 after `latest_block` is
+    /// declared too.
+    fn new(
+        sender: &Address,
+        txn: &SethTransaction,
+        access_list: &[Address],
+        latest_block: Option<u64>,
+    ) -> Self {
+        let mut accounts = vec![*sender];
+        match *txn {
+            SethTransaction::CreateExternalAccount(ref inner) => {
+                accounts.extend(evm::seth_address(&inner.to));
+            }
+            SethTransaction::CreateContractAccount(ref inner) => {
+                accounts.push(evm::derive_address(sender, inner.nonce));
+            }
+            SethTransaction::MessageCall(ref inner) => {
+                accounts.extend(evm::seth_address(&inner.to));
+            }
+            SethTransaction::SetPermissions(ref inner) => {
+                accounts.extend(evm::seth_address(&inner.to));
+            }
+        }
+        accounts.extend_from_slice(access_list);
+
+        let mut outputs: Vec<String> = accounts
+            .iter()
+            .map(|address| account_state_address(&transform::bytes_to_hex_str(address.as_bytes())))
+            .collect();
+        outputs.sort();
+        outputs.dedup();
+
+        let mut inputs = outputs.clone();
+        inputs.push(account_state_address(GLOBAL_PERMISSIONS_ADDRESS));
+        if let Some(latest_block) = latest_block {
+            inputs.push(String::from(BLOCK_INFO_CONFIG_ADDRESS));
+            inputs.extend(block_info_prefixes(
+                latest_block.saturating_sub(BLOCKHASH_DEPTH),
+                latest_block.saturating_add(BLOCK_INFO_LOOKAHEAD),
+            ));
+        }
+        inputs.sort();
+        inputs.dedup();
+
+        TransactionAddresses { inputs, outputs }
+    }
+}
+
+/// Returns the address prefixes that cover the block info of the blocks from `first` to `last`.
+/// Each prefix covers the info of 256 consecutive blocks.
+fn block_info_prefixes(first: u64, last: u64) -> Vec<String> {
+    (first >> 8..=last >> 8)
+        .map(|prefix| format!("{}00{:060x}", BLOCK_INFO_NS, prefix))
+        .collect()
+}
+
+/// Signs the transaction with the account, which also batches it
+fn sign_transaction(
+    account: &Account,
+    txn: &SethTransaction,
+    addresses: TransactionAddresses,
+    dependencies: Vec<String>,
+    nonce: String,
+) -> Result<TransactionPb, Error> {
+    let payload = protobuf::Message::write_to_bytes(&txn.to_pb())
+        .map_err(|error| Error::ParseError(format!("Error serializing payload: {:?}", error)))?;
+
+    let mut txn_header = TransactionHeader::new();
+    txn_header.set_batcher_public_key(String::from(account.public_key()));
+    txn_header.set_family_name(String::from(FAMILY_NAME));
+    txn_header.set_family_version(String::from(FAMILY_VERSION));
+    txn_header.set_inputs(protobuf::RepeatedField::from_vec(addresses.inputs));
+    txn_header.set_outputs(protobuf::RepeatedField::from_vec(addresses.outputs));
+    txn_header.set_dependencies(protobuf::RepeatedField::from_vec(dependencies));
+    txn_header.set_nonce(nonce);
+
+    let mut sha = Sha512::new();
+    sha.input(&payload);
+    let hash = sha.result_str();
+    txn_header.set_payload_sha512(hash);
+
+    txn_header.set_signer_public_key(String::from(account.public_key()));
+    let txn_header_bytes = protobuf::Message::write_to_bytes(&txn_header).map_err(|error| {
+        Error::ParseError(format!("Error serializing transaction header: {:?}", error))
+    })?;
+
+    let txn_signature = account.sign(&txn_header_bytes)?;
+
+    let mut txn = TransactionPb::new();
+    txn.set_header(txn_header_bytes);
+    txn.set_header_signature(txn_signature);
+    txn.set_payload(payload);
+    Ok(txn)
+}
+
+/// Returns the Ethereum transaction hash, signer and nonce from the header nonce of a relayed
+/// transaction
+fn parse_relayed_nonce(nonce: &str) -> Option<(String, String, u64)> {
+    let mut fields = nonce.strip_prefix(RELAYED_NONCE_PREFIX)?.split(':');
+    match (fields.next(), fields.next(), fields.next(), fields.next()) {
+        (Some(eth_txn_hash), Some(signer), Some(signer_nonce), None) => Some((
+            String::from(eth_txn_hash),
+            String::from(signer),
+            signer_nonce.parse().ok()?,
+        )),
+        _ => None,
+    }
+}
+
+/// Wraps the transactions, in order, in a batch signed by the account
+fn sign_batch(account: &Account, txns: Vec<TransactionPb>) -> Result<Batch, Error> {
+    let mut batch_header = BatchHeader::new();
+    batch_header.set_signer_public_key(String::from(account.public_key()));
+    batch_header.set_transaction_ids(protobuf::RepeatedField::from_vec(
+        txns.iter()
+            .map(|txn| txn.header_signature.clone())
+            .collect(),
+    ));
+    let batch_header_bytes = protobuf::Message::write_to_bytes(&batch_header).map_err(|error| {
+        Error::ParseError(format!("Error serializing batch header: {:?}", error))
+    })?;
+
+    let batch_signature = account.sign(&batch_header_bytes)?;
+
+    let mut batch = Batch::new();
+    batch.set_header(batch_header_bytes);
+    batch.set_header_signature(batch_signature);
+    batch.set_transactions(protobuf::RepeatedField::from_vec(txns));
+
+    Ok(batch)
+}
@@ -0,0 +1,350 @@
+/*
+ * Copyright 2019 Cargill Incorporated
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! The settings of seth-rpc, which are read from a TOML file and can be overridden by
+//! command-line flags. Every setting is optional in the file.
+
+use accounts::get_key_dir;
+use auth::AuthConfig;
+use rate_limit::RateLimitConfig;
+use std::error::Error as StdError;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::fs;
+use std::io::Error as IoError;
+use std::path::{Path, PathBuf};
+use toml;
+
+/// The environment variable that holds the path of the config file when `--config` isn't passed
+pub const CONFIG_ENV_VAR: &str = "SETH_RPC_CONFIG";
+
+/// The namespaces whose methods can be enabled, which are the prefixes of the method names
+pub const NAMESPACES: &[&str] = &["debug", "eth", "net", "personal", "seth"];
+
+/// The namespaces enabled when `namespaces` isn't set. Like geth, the `debug` methods, which can
+/// execute expensive traces, are only served if enabled explicitly.
+pub const DEFAULT_NAMESPACES: &[&str] = &["eth", "net", "personal", "seth"];
+
+#[derive(Debug)]
+pub enum Error {
+    IoError(IoError),
+    ParseError(String),
+}
+
+impl StdError for Error {
+    fn description(&self) -> &str {
+        match *self {
+            Error::IoError(ref ie) => ie.description(),
+            Error::ParseError(ref msg) => msg,
+        }
+    }
+
+    fn cause(&self) -> Option<&dyn StdError> {
+        None
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match *self {
+            Error::IoError(ref ie) => ie.fmt(f),
+            Error::ParseError(ref msg) => write!(f, "ParseError: {}", msg),
+        }
+    }
+}
+
+impl From<IoError> for Error {
+    fn from(e: IoError) -> Self {
+        Error::IoError(e)
+    }
+}
+
+/// Returns the error of a file that couldn't be read, naming the file
+pub fn read_error(path: &Path, error: IoError) -> Error {
+    Error::IoError(IoError::new(
+        error.kind(),
+        format!("Failed to read {}: {}", path.display(), error),
+    ))
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RpcConfig {
+    /// The host and port of the HTTP server
+    pub bind: String,
+    /// The host and port of the WebSocket server, which is disabled if not set
+    pub ws_bind: Option<String>,
+    /// The origins of the web pages allowed to call seth-rpc, or `*` for any. Browsers can't call
+    /// seth-rpc from other origins if this is empty.
+    pub cors_origins: Vec<String>,
+    /// The `Host` headers to accept requests with, which keeps DNS rebinding from exposing
+    /// seth-rpc to web pages. Any host is accepted if this is empty.
+    pub allowed_hosts: Vec<String>,
+    /// The PEM files of the certificate chain and private key to serve HTTPS and WSS with, instead
+    /// of HTTP and WS
+    pub tls_cert: Option<PathBuf>,
+    pub tls_key: Option<PathBuf>,
+    /// The PEM file of the CAs that must have signed the certificates of clients, if set
+    pub tls_client_ca: Option<PathBuf>,
+    /// How many TLS connections are served at once by each of the HTTPS and WSS servers
+    pub tls_max_connections: usize,
+    /// How many seconds a TLS connection is kept open while no data is sent either way. This must
+    /// be longer than the requests that wait for the validator take.
+    pub tls_idle_timeout: u64,
+    /// The credentials that requests are authenticated with, and what each one may call
+    pub auth: AuthConfig,
+    /// How fast each client may send requests, which is unlimited if not set
+    pub rate_limit: Option<RateLimitConfig>,
+    /// The component endpoint of the validator
+    pub connect: String,
+    /// How many milliseconds to wait for the validator to respond to a request
+    pub request_timeout: u64,
+    /// How many times to retry a request after the validator was unavailable. Only reads are
+    /// retried after they were sent; other requests only if they couldn't be sent.
+    pub request_retries: u32,
+    /// How many milliseconds to wait before retrying a request or reconnecting to the validator,
+    /// which doubles with each failure in a row
+    pub retry_interval: u64,
+    /// The most milliseconds to wait before retrying a request or reconnecting
+    pub max_retry_interval: u64,
+    /// The most seconds a transaction's `wait` may ask to wait for it to be committed
+    pub max_wait: u64,
+    /// The number of threads of the HTTP server
+    pub server_threads: usize,
+    /// The number of threads that run requests, which defaults to the number of CPUs
+    pub pool_size: Option<usize>,
+    /// The directory of the account keys, which defaults to `~/.sawtooth/keys`
+    pub key_dir: Option<PathBuf>,
+    /// The aliases of the accounts to unlock
+    pub unlock: Vec<String>,
+    /// The alias of the account that signs the Seth transactions for `eth_sendRawTransaction`
+    pub relayer: Option<String>,
+    /// How many milliseconds to collect batches for before submitting them together, if set
+    pub aggregate_window: Option<u64>,
+    /// The number of collected batches that are submitted without waiting for the window to end
+    pub aggregate_max_batches: usize,
+    /// Declare the whole Seth and block info namespaces as the inputs and outputs of every
+    /// transaction, instead of the entries it accesses
+    pub wide_inputs: bool,
+    /// The gas limit of transactions that don't set `gas`
+    pub default_gas: u64,
+    /// The gas price of transactions that don't set `gasPrice`
+    pub default_gas_price: u64,
+    /// The network id, which is also the chain id that signed transactions must be signed for
+    pub chain_id: u64,
+    /// Read the chain id from the `seth.chain_id` Sawtooth setting, falling back to `chain_id`
+    /// if it isn't set
+    pub chain_id_from_settings: bool,
+    /// How many seconds the head block may go without advancing before `/ready` reports that
+    /// seth-rpc isn't ready. The age of the head isn't checked if not set.
+    pub max_head_age: Option<u64>,
+    /// The namespaces whose methods are served
+    pub namespaces: Vec<String>,
+}
+
+impl Default for RpcConfig {
+    fn default() -> Self {
+        RpcConfig {
+            bind: String::from("127.0.0.1:3030"),
+            ws_bind: None,
+            cors_origins: Vec::new(),
+            allowed_hosts: Vec::new(),
+            tls_cert: None,
+            tls_key: None,
+            tls_client_ca: None,
+            tls_max_connections: 1024,
+            tls_idle_timeout: 600,
+            auth: AuthConfig::default(),
+            rate_limit: None,
+            connect: String::from("tcp://127.0.0.1:4004"),
+            request_timeout: 10_000,
+            request_retries: 2,
+            retry_interval: 500,
+            max_retry_interval: 30_000,
+            max_wait: 300,
+            server_threads: 3,
+            pool_size: None,
+            key_dir: None,
+            unlock: Vec::new(),
+            relayer: None,
+            aggregate_window: None,
+            aggregate_max_batches: 100,
+            wide_inputs: false,
+            default_gas: 90_000,
+            default_gas_price: 10_000_000_000_000,
+            chain_id: 19,
+            chain_id_from_settings: false,
+            max_head_age: None,
+            namespaces: DEFAULT_NAMESPACES
+                .iter()
+                .map(|ns| String::from(*ns))
+                .collect(),
+        }
+    }
+}
+
+impl RpcConfig {
+    pub fn from_file(path: &Path) -> Result<Self, Error> {
+        Self::parse(&fs::read_to_string(path).map_err(|error| read_error(path, error))?)
+    }
+
+    pub fn parse(toml: &str) -> Result<Self, Error> {
+        let config: RpcConfig =
+            toml::from_str(toml).map_err(|error| Error::ParseError(format!("{}", error)))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Checks the settings that can't be checked by their type alone
+    pub fn validate(&self) -> Result<(), Error> {
+        if let Some(namespace) = self
+            .namespaces
+            .iter()
+            .find(|namespace| !NAMESPACES.contains(&namespace.as_str()))
+        {
+            return Err(Error::ParseError(format!(
+                "Unknown namespace `{}`, expected one of {}",
+                namespace,
+                NAMESPACES.join(", ")
+            )));
+        }
+        if self.tls_cert.is_some() != self.tls_key.is_some() {
+            return Err(Error::ParseError(String::from(
+                "`tls_cert` and `tls_key` must be set together",
+            )));
+        }
+        if self.tls_client_ca.is_some() && self.tls_cert.is_none() {
+            return Err(Error::ParseError(String::from(
+                "`tls_client_ca` requires `tls_cert` and `tls_key`",
+            )));
+        }
+        self.auth.validate().map_err(Error::ParseError)?;
+        if let Some(ref rate_limit) = self.rate_limit {
+            rate_limit.validate().map_err(Error::ParseError)?;
+        }
+        if self.request_timeout == 0 || self.retry_interval == 0 {
+            return Err(Error::ParseError(String::from(
+                "`request_timeout` and `retry_interval` must be at least 1",
+            )));
+        }
+        if self.max_retry_interval < self.retry_interval {
+            return Err(Error::ParseError(String::from(
+                "`max_retry_interval` can't be less than `retry_interval`",
+            )));
+        }
+        if self.tls_cert.is_some() && self.tls_idle_timeout <= self.max_wait {
+            return Err(Error::ParseError(String::from(
+                "`tls_idle_timeout` must be longer than `max_wait`",
+            )));
+        }
+        if self.server_threads == 0 || self.pool_size == Some(0) {
+            return Err(Error::ParseError(String::from(
+                "`server_threads` and `pool_size` must be at least 1",
+            )));
+        }
+        Ok(())
+    }
+
+    /// Returns the directory of the account keys
+    pub fn key_dir(&self) -> Option<PathBuf> {
+        self.key_dir.clone().or_else(get_key_dir)
+    }
+
+    /// Returns true if the method is in an enabled namespace
+    pub fn method_enabled(&self, method: &str) -> bool {
+        let namespace = method.split('_').next().unwrap_or("");
+        self.namespaces.iter().any(|enabled| enabled == namespace)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RpcConfig;
+    use std::path::PathBuf;
+
+    #[test]
+    fn missing_settings_have_defaults() {
+        let config = RpcConfig::parse(
+            r#"
+            bind = "0.0.0.0:3030"
+            unlock = ["alice", "bob"]
+            key_dir = "/etc/seth/keys"
+            namespaces = ["eth", "net"]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!("0.0.0.0:3030", config.bind);
+        assert_eq!("tcp://127.0.0.1:4004", config.connect);
+        assert_eq!(vec!["alice", "bob"], config.unlock);
+        assert_eq!(Some(PathBuf::from("/etc/seth/keys")), config.key_dir());
+        assert_eq!(90_000, config.default_gas);
+        assert_eq!(None, config.max_head_age);
+        assert_eq!(10_000, config.request_timeout);
+        assert!(config.method_enabled("eth_call"));
+        assert!(!config.method_enabled("personal_newAccount"));
+
+        let config = RpcConfig::default();
+        assert!(config.method_enabled("personal_newAccount"));
+        assert!(!config.method_enabled("debug_traceTransaction"));
+    }
+
+    #[test]
+    fn parse_auth_settings() {
+        let config = RpcConfig::parse(
+            r#"
+            [auth]
+            anonymous = ["net"]
+
+            [[auth.api_keys]]
+            name = "dashboard"
+            key = "secret-key"
+            allow = ["eth", "personal_listAccounts"]
+
+            [[auth.jwt_keys]]
+            name = "wallet"
+            algorithm = "RS256"
+            key_file = "/etc/seth/jwt.pem"
+            allow = ["eth"]
+            "#,
+        )
+        .unwrap();
+
+        assert!(config.auth.enabled());
+        assert_eq!("dashboard", config.auth.api_keys[0].name);
+        assert_eq!(
+            PathBuf::from("/etc/seth/jwt.pem"),
+            config.auth.jwt_keys[0].key_file
+        );
+        assert!(!RpcConfig::default().auth.enabled());
+    }
+
+    #[test]
+    fn reject_invalid_settings() {
+        assert!(RpcConfig::parse("bnid = \"0.0.0.0:3030\"").is_err());
+        assert!(RpcConfig::parse("namespaces = [\"web3\"]").is_err());
+        assert!(RpcConfig::parse("server_threads = 0").is_err());
+        assert!(RpcConfig::parse("request_timeout = 0").is_err());
+        assert!(RpcConfig::parse("max_retry_interval = 100").is_err());
+        assert!(RpcConfig::parse("tls_cert = \"cert.pem\"").is_err());
+        assert!(RpcConfig::parse(
+            "tls_cert = \"cert.pem\"\ntls_key = \"key.pem\"\ntls_idle_timeout = 300"
+        )
+        .is_err());
+        assert!(RpcConfig::parse("[auth]\nanonymous = [\"web3\"]").is_err());
+        assert!(RpcConfig::parse("[rate_limit]\nburst = 0").is_err());
+    }
+}
@@ -0,0 +1,340 @@
+/*
+ * Copyright 2019 Cargill Incorporated
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! The connection to the validator, which gives every request a deadline and is replaced when it
+//! drops.
+//!
+//! Requests that fail because the validator is unavailable are retried if sending them twice is
+//! harmless: reads are retried after a timeout or a dropped connection, but anything else, such as
+//! a batch submission, is only retried if it couldn't be sent at all. The connection is replaced
+//! by the thread following the events of the validator, which notices first when it drops.
+
+use client::Error;
+use jsonrpc_core::{Error as RpcError, ErrorCode};
+use metrics;
+use sawtooth_sdk::messages::validator::{Message, Message_MessageType};
+use sawtooth_sdk::messaging::stream::{
+    MessageConnection, MessageReceiver, MessageSender, ReceiveError,
+};
+use std::sync::RwLock;
+use std::thread;
+use std::time::Duration;
+use uuid;
+
+/// The error code of requests that failed because the validator was unavailable
+pub const VALIDATOR_UNAVAILABLE: i64 = -32002;
+
+/// The requests that only read from the validator, which can be sent again safely
+const IDEMPOTENT_REQUESTS: &[Message_MessageType] = &[
+    Message_MessageType::CLIENT_BATCH_STATUS_REQUEST,
+    Message_MessageType::CLIENT_BLOCK_GET_BY_ID_REQUEST,
+    Message_MessageType::CLIENT_BLOCK_GET_BY_NUM_REQUEST,
+    Message_MessageType::CLIENT_BLOCK_GET_BY_TRANSACTION_ID_REQUEST,
+    Message_MessageType::CLIENT_BLOCK_LIST_REQUEST,
+    Message_MessageType::CLIENT_PEERS_GET_REQUEST,
+    Message_MessageType::CLIENT_RECEIPT_GET_REQUEST,
+    Message_MessageType::CLIENT_STATE_GET_REQUEST,
+    Message_MessageType::CLIENT_TRANSACTION_GET_REQUEST,
+];
+
+/// Returns the error of JSON-RPC requests that failed because the validator was unavailable. The
+/// handlers return it for `Error::ValidatorUnavailable`, through `Error::or_unavailable`.
+pub fn unavailable_error() -> RpcError {
+    RpcError {
+        code: ErrorCode::ServerError(VALIDATOR_UNAVAILABLE),
+        message: String::from("Validator unavailable"),
+        data: None,
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ConnectionConfig {
+    /// How long to wait for the response to a request
+    pub timeout: Duration,
+    /// How many times a request is sent again after the validator was unavailable
+    pub retries: u32,
+    /// How long to wait before the first retry or reconnection
+    pub retry_interval: Duration,
+    /// The longest wait between retries or reconnections
+    pub max_retry_interval: Duration,
+}
+
+/// Exponentially growing waits between attempts
+pub struct Backoff {
+    initial: Duration,
+    max: Duration,
+    next: Duration,
+}
+
+impl Backoff {
+    pub fn new(initial: Duration, max: Duration) -> Self {
+        Backoff {
+            initial,
+            max,
+            next: initial,
+        }
+    }
+
+    /// Returns how long to wait before the next attempt, and doubles the wait after it
+    pub fn next_wait(&mut self) -> Duration {
+        let wait = self.next;
+        self.next = (self.next * 2).min(self.max);
+        wait
+    }
+
+    /// Starts over from the initial wait, after an attempt succeeded
+    pub fn reset(&mut self) {
+        self.next = self.initial;
+    }
+}
+
+/// Why an attempt to send a request failed
+enum Failure {
+    /// The request never left seth-rpc
+    NotSent(String),
+    /// The request was sent, but the validator may or may not have handled it
+    NoResponse(String),
+}
+
+pub struct ValidatorConnection<S: MessageSender> {
+    connection: Box<dyn MessageConnection<S> + Send + Sync>,
+    sender: RwLock<S>,
+    config: ConnectionConfig,
+}
+
+impl<S: MessageSender> ValidatorConnection<S> {
+    /// Connects to the validator. Returns the connection, and the receiver of the messages that
+    /// the validator sends on its own, such as events.
+    pub fn new(
+        connection: Box<dyn MessageConnection<S> + Send + Sync>,
+        config: ConnectionConfig,
+    ) -> (Self, MessageReceiver) {
+        let (sender, receiver) = connection.create();
+        let connection = ValidatorConnection {
+            connection,
+            sender: RwLock::new(sender),
+            config,
+        };
+        (connection, receiver)
+    }
+
+    /// Replaces the connection to the validator, and returns the receiver of the new one
+    pub fn reconnect(&self) -> MessageReceiver {
+        let (sender, receiver) = self.connection.create();
+        *self.sender.write().unwrap() = sender;
+        receiver
+    }
+
+    /// Returns the waits between reconnections
+    pub fn backoff(&self) -> Backoff {
+        Backoff::new(self.config.retry_interval, self.config.max_retry_interval)
+    }
+
+    /// Sends the request and waits for the response, retrying it as long as that is safe. If
+    /// `wait` is set, the validator holds the request for up to that long before responding, so
+    /// the response is waited for that much longer, and the request isn't sent again once the
+    /// wait ran out.
+    pub fn request(
+        &self,
+        msg_type: Message_MessageType,
+        content: &[u8],
+        wait: Option<Duration>,
+    ) -> Result<Message, Error> {
+        let idempotent = wait.is_none() && IDEMPOTENT_REQUESTS.contains(&msg_type);
+        let timeout = self.config.timeout + wait.unwrap_or_default();
+        let mut backoff = self.backoff();
+        let mut retries = self.config.retries;
+        loop {
+            let (reason, retry) = match self.send(msg_type, content, timeout) {
+                Ok(response) => return Ok(response),
+                Err(Failure::NotSent(reason)) => (reason, true),
+                Err(Failure::NoResponse(reason)) => (reason, idempotent),
+            };
+            if !retry || retries == 0 {
+                return Err(Error::ValidatorUnavailable(reason));
+            }
+            retries -= 1;
+            let wait = backoff.next_wait();
+            warn!("Retrying {:?} in {:?}: {}", msg_type, wait, reason);
+            thread::sleep(wait);
+        }
+    }
+
+    fn send(
+        &self,
+        msg_type: Message_MessageType,
+        content: &[u8],
+        timeout: Duration,
+    ) -> Result<Message, Failure> {
+        let _timer = metrics::validator_timer(msg_type);
+        let correlation_id = uuid::Uuid::new_v4().to_string();
+        let mut future = self
+            .sender
+            .read()
+            .unwrap()
+            .send(msg_type, &correlation_id, content)
+            .map_err(|error| Failure::NotSent(format!("Failed to send message: {:?}", error)))?;
+        future.get_timeout(timeout).map_err(|error| match error {
+            ReceiveError::TimeoutError => {
+                Failure::NoResponse(format!("No response within {:?}", timeout))
+            }
+            error => Failure::NoResponse(format!("Failed to receive message: {:?}", error)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Backoff, ConnectionConfig, ValidatorConnection};
+    use client::Error;
+    use sawtooth_sdk::messages::validator::{Message, Message_MessageType};
+    use sawtooth_sdk::messaging::stream::{
+        MessageConnection, MessageFuture, MessageReceiver, MessageSender, SendError,
+    };
+    use std::collections::VecDeque;
+    use std::sync::mpsc::channel;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    /// What happens to a request sent with the mock sender
+    #[derive(Clone, Copy)]
+    enum Outcome {
+        Response,
+        NotSent,
+        NoResponse,
+    }
+
+    /// Gives the requests sent with it the next outcome, or a response once there are none left
+    #[derive(Clone, Default)]
+    struct MockSender {
+        outcomes: Arc<Mutex<VecDeque<Outcome>>>,
+        attempts: Arc<Mutex<u32>>,
+    }
+
+    impl MessageSender for MockSender {
+        fn send(
+            &self,
+            _: Message_MessageType,
+            correlation_id: &str,
+            _: &[u8],
+        ) -> Result<MessageFuture, SendError> {
+            *self.attempts.lock().unwrap() += 1;
+            let (sender, receiver) = channel();
+            match self.outcomes.lock().unwrap().pop_front() {
+                Some(Outcome::NotSent) => return Err(SendError::DisconnectedError),
+                // Dropping the sender makes the response time out right away
+                Some(Outcome::NoResponse) => {}
+                Some(Outcome::Response) | None => {
+                    let mut message = Message::new();
+                    message.set_correlation_id(String::from(correlation_id));
+                    sender.send(Ok(message)).unwrap();
+                }
+            }
+            Ok(MessageFuture::new(receiver))
+        }
+
+        fn reply(&self, _: Message_MessageType, _: &str, _: &[u8]) -> Result<(), SendError> {
+            Ok(())
+        }
+
+        fn close(&mut self) {}
+    }
+
+    struct MockConnection(MockSender);
+
+    impl MessageConnection<MockSender> for MockConnection {
+        fn create(&self) -> (MockSender, MessageReceiver) {
+            (self.0.clone(), channel().1)
+        }
+    }
+
+    /// Returns a connection whose requests have the given outcomes, and the sender counting the
+    /// attempts
+    fn connect(outcomes: &[Outcome]) -> (ValidatorConnection<MockSender>, MockSender) {
+        let sender = MockSender::default();
+        sender.outcomes.lock().unwrap().extend(outcomes);
+        let (connection, _) = ValidatorConnection::new(
+            Box::new(MockConnection(sender.clone())),
+            ConnectionConfig {
+                timeout: Duration::from_millis(100),
+                retries: 2,
+                retry_interval: Duration::from_millis(1),
+                max_retry_interval: Duration::from_millis(1),
+            },
+        );
+        (connection, sender)
+    }
+
+    fn unavailable<T>(result: Result<T, Error>) -> bool {
+        match result {
+            Err(Error::ValidatorUnavailable(_)) => true,
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn retry_reads_without_response() {
+        let (connection, sender) = connect(&[Outcome::NoResponse, Outcome::NotSent]);
+        let result = connection.request(Message_MessageType::CLIENT_STATE_GET_REQUEST, &[], None);
+        assert!(result.is_ok());
+        assert_eq!(3, *sender.attempts.lock().unwrap());
+
+        let (connection, sender) = connect(&[Outcome::NoResponse; 3]);
+        let result = connection.request(Message_MessageType::CLIENT_STATE_GET_REQUEST, &[], None);
+        assert!(unavailable(result));
+        assert_eq!(3, *sender.attempts.lock().unwrap());
+    }
+
+    #[test]
+    fn retry_submissions_only_if_not_sent() {
+        let (connection, sender) = connect(&[Outcome::NotSent, Outcome::Response]);
+        let result =
+            connection.request(Message_MessageType::CLIENT_BATCH_SUBMIT_REQUEST, &[], None);
+        assert!(result.is_ok());
+        assert_eq!(2, *sender.attempts.lock().unwrap());
+
+        let (connection, sender) = connect(&[Outcome::NotSent, Outcome::NoResponse]);
+        let result =
+            connection.request(Message_MessageType::CLIENT_BATCH_SUBMIT_REQUEST, &[], None);
+        assert!(unavailable(result));
+        assert_eq!(2, *sender.attempts.lock().unwrap());
+    }
+
+    #[test]
+    fn dont_retry_after_wait() {
+        let (connection, sender) = connect(&[Outcome::NoResponse]);
+        let result = connection.request(
+            Message_MessageType::CLIENT_BATCH_STATUS_REQUEST,
+            &[],
+            Some(Duration::from_millis(1)),
+        );
+        assert!(unavailable(result));
+        assert_eq!(1, *sender.attempts.lock().unwrap());
+    }
+
+    #[test]
+    fn double_waits_up_to_max() {
+        let mut backoff = Backoff::new(Duration::from_millis(100), Duration::from_millis(350));
+        assert_eq!(Duration::from_millis(100), backoff.next_wait());
+        assert_eq!(Duration::from_millis(200), backoff.next_wait());
+        assert_eq!(Duration::from_millis(350), backoff.next_wait());
+        assert_eq!(Duration::from_millis(350), backoff.next_wait());
+
+        backoff.reset();
+        assert_eq!(Duration::from_millis(100), backoff.next_wait());
+    }
+}
@@ -0,0 +1,291 @@
+/*
+ * Copyright 2019 Cargill Incorporated
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! Follows the blocks committed by the validator and feeds them to the installed filters and
+//! subscriptions.
+
+use client::{BlockKey, Error as ClientError, ValidatorClient};
+use filters::Filter;
+use jsonrpc_core::{Error, ErrorCode};
+use logs::{get_logs_from_receipts_and_filter, get_seth_transaction_ids};
+use protobuf;
+use pubsub;
+use sawtooth_sdk::messages::block::BlockHeader;
+use sawtooth_sdk::messages::events::{Event, EventList};
+use sawtooth_sdk::messages::validator::Message_MessageType;
+use sawtooth_sdk::messaging::stream::*;
+use std::collections::HashMap;
+use std::sync::mpsc::TryRecvError;
+use std::thread;
+use std::time::Duration;
+use subscriptions::Subscription;
+use transform;
+
+const BLOCK_COMMIT_EVENT: &str = "sawtooth/block-commit";
+const SETH_LOG_EVENT: &str = "seth_log_event";
+
+/// How long to wait before retrying a failed event subscription
+const SUBSCRIBE_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The most blocks that are backfilled after blocks were missed. If more were missed, the older
+/// ones are skipped and the filters report an error.
+const MAX_BACKFILL_BLOCKS: u64 = 100;
+
+/// The last block whose changes were pushed to the filters and subscriptions
+#[derive(Debug, Clone)]
+struct LastBlock {
+    num: u64,
+    id: String,
+}
+
+/// Subscribes to block commits and then processes the events delivered to `receiver`. When the
+/// connection to the validator is closed, it is replaced and the block commits are subscribed to
+/// again.
+pub fn follow_blocks<T>(client: ValidatorClient<T>, receiver: MessageReceiver)
+where
+    T: MessageSender,
+{
+    let mut backoff = client.reconnect_backoff();
+    let mut receiver = receiver;
+    let mut last_block = None;
+    loop {
+        if follow_connection(&client, &receiver, &mut last_block) {
+            backoff.reset();
+        }
+        let wait = backoff.next_wait();
+        error!("Connection to validator closed, reconnecting in {:?}", wait);
+        thread::sleep(wait);
+        receiver = client.reconnect();
+    }
+}
+
+/// Subscribes to block commits and processes the events delivered to `receiver` until the
+/// connection is closed. Returns whether the subscription succeeded.
+fn follow_connection<T>(
+    client: &ValidatorClient<T>,
+    receiver: &MessageReceiver,
+    last_block: &mut Option<LastBlock>,
+) -> bool
+where
+    T: MessageSender,
+{
+    // The validator catches up from the last block seen before the connection was closed
+    let mut last_known_block_id = last_block.as_ref().map(|last| last.id.clone());
+    loop {
+        let error = match client.subscribe_to_events(
+            &[BLOCK_COMMIT_EVENT, SETH_LOG_EVENT],
+            last_known_block_id.as_ref().map(String::as_str),
+        ) {
+            Ok(()) => break,
+            Err(ClientError::NoResource) if last_known_block_id.is_some() => {
+                // The block was abandoned for a fork, so the missed blocks are backfilled by
+                // number instead
+                warn!(
+                    "Validator doesn't know the last block seen {:?}, subscribing without it",
+                    last_known_block_id
+                );
+                last_known_block_id = None;
+                continue;
+            }
+            Err(error) => error,
+        };
+        error!("Failed to subscribe to block commits: {}", error);
+        thread::sleep(SUBSCRIBE_RETRY_INTERVAL);
+        // Stop retrying on a connection that was closed meanwhile
+        loop {
+            match receiver.try_recv() {
+                Ok(_) => (),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return false,
+            }
+        }
+    }
+
+    info!("Subscribed to block commits");
+
+    loop {
+        let message = match receiver.recv() {
+            Ok(Ok(message)) => message,
+            Ok(Err(error)) => {
+                error!("Failed to receive message from validator: {:?}", error);
+                continue;
+            }
+            Err(_) => return true,
+        };
+
+        if message.message_type != Message_MessageType::CLIENT_EVENTS {
+            debug!("Ignoring unexpected message: {:?}", message.message_type);
+            continue;
+        }
+
+        match protobuf::parse_from_bytes::<EventList>(&message.content) {
+            Ok(event_list) => handle_block_commit(client, event_list.get_events(), last_block),
+            Err(error) => error!("Failed to parse event list: {:?}", error),
+        }
+    }
+}
+
+/// Pushes the changes from a newly committed block to the filters and subscriptions, after the
+/// changes of the blocks since `last_block` that were missed, e.g. because they couldn't be read
+/// from the validator. A block that fails is tried again when the next block is committed.
+fn handle_block_commit<T>(
+    client: &ValidatorClient<T>,
+    events: &[Event],
+    last_block: &mut Option<LastBlock>,
+) where
+    T: MessageSender,
+{
+    let commit = events
+        .iter()
+        .find(|event| event.event_type == BLOCK_COMMIT_EVENT);
+    let block_id = commit.and_then(|event| get_attribute(event, "block_id"));
+    let block_num = commit
+        .and_then(|event| get_attribute(event, "block_num"))
+        .and_then(|block_num| block_num.parse::<u64>().ok());
+    let (block_id, block_num) = match (block_id, block_num) {
+        (Some(block_id), Some(block_num)) => (block_id, block_num),
+        _ => {
+            error!("Received events without a block commit");
+            return;
+        }
+    };
+    debug!("Block committed: {}", block_id);
+
+    if let Some(last) = last_block.clone() {
+        let mut first_missed = last.num + 1;
+        if block_num > first_missed + MAX_BACKFILL_BLOCKS {
+            let skipped = block_num - MAX_BACKFILL_BLOCKS - first_missed;
+            error!("Skipping the changes of {} missed blocks", skipped);
+            client.filters.push_changes(|_| {
+                Err(Error {
+                    code: ErrorCode::ServerError(-32000),
+                    message: format!("Missed the changes of {} blocks", skipped),
+                    data: None,
+                })
+            });
+            first_missed = block_num - MAX_BACKFILL_BLOCKS;
+        }
+        for num in first_missed..block_num {
+            debug!("Backfilling block {}", num);
+            match push_block(client, BlockKey::Number(num), None, true) {
+                Ok(id) => *last_block = Some(LastBlock { num, id }),
+                Err(error) => {
+                    error!("Failed to backfill block {}: {}", num, error);
+                    return;
+                }
+            }
+        }
+    }
+
+    let has_logs = events
+        .iter()
+        .any(|event| event.event_type == SETH_LOG_EVENT);
+    match push_block(
+        client,
+        BlockKey::Signature(block_id.clone()),
+        Some(&block_id),
+        has_logs,
+    ) {
+        Ok(id) => {
+            *last_block = Some(LastBlock { num: block_num, id });
+        }
+        Err(error) => error!("Failed to process committed block {}: {}", block_id, error),
+    }
+}
+
+/// Pushes the changes from a block to the filters and subscriptions, and returns its id. The
+/// block and its receipts are only requested from the validator if somebody needs them, or if
+/// the id of the block isn't known. Receipts are only requested if the block may have logs.
+fn push_block<T>(
+    client: &ValidatorClient<T>,
+    block_key: BlockKey,
+    block_id: Option<&str>,
+    has_logs: bool,
+) -> Result<String, ClientError>
+where
+    T: MessageSender,
+{
+    let filters = client.filters.get_filters();
+    let subscriptions = client.subscriptions.get_subscriptions();
+
+    let needs_block = !subscriptions.is_empty()
+        || filters.iter().any(|filter| match *filter {
+            Filter::Block => false,
+            _ => true,
+        });
+    if let (false, Some(block_id)) = (needs_block, block_id) {
+        let block_hash = transform::hex_prefix(block_id);
+        client
+            .filters
+            .push_changes(|_| Ok(vec![block_hash.clone()]));
+        return Ok(String::from(block_id));
+    }
+
+    let block = client.get_block(block_key)?;
+    let block_header: BlockHeader = protobuf::parse_from_bytes(&block.header).map_err(|error| {
+        ClientError::ParseError(format!("Error parsing block header: {:?}", error))
+    })?;
+    let txn_ids = get_seth_transaction_ids(&block);
+
+    // Receipts are needed for the logs, and for the gas used by the block
+    let watches_logs = filters.iter().any(|filter| match *filter {
+        Filter::Log(_) => true,
+        _ => false,
+    }) || subscriptions.iter().any(
+        |&(_, ref subscription)| match *subscription {
+            Subscription::Logs(_) => true,
+            _ => false,
+        },
+    );
+    let watches_heads = subscriptions
+        .iter()
+        .any(|&(_, ref subscription)| match *subscription {
+            Subscription::NewHeads => true,
+            _ => false,
+        });
+
+    let receipts = if (has_logs && watches_logs) || (watches_heads && !txn_ids.is_empty()) {
+        client.get_receipts_from_block(&block)?
+    } else {
+        HashMap::new()
+    };
+
+    client.filters.push_changes(|filter| match *filter {
+        Filter::Block => Ok(vec![transform::hex_prefix(&block.header_signature)]),
+        Filter::Transaction => Ok(txn_ids.clone()),
+        Filter::Log(ref log_filter) => {
+            get_logs_from_receipts_and_filter(&block, &receipts, log_filter).map_err(|error| {
+                error!(
+                    "Failed to get the logs of block {} for a filter: {:?}",
+                    block.header_signature, error
+                );
+                error
+            })
+        }
+    });
+
+    pubsub::notify_subscribers(client, &block, &block_header, &receipts);
+    Ok(block.header_signature.clone())
+}
+
+fn get_attribute(event: &Event, key: &str) -> Option<String> {
+    event
+        .get_attributes()
+        .iter()
+        .find(|attribute| attribute.key == key)
+        .map(|attribute| attribute.value.clone())
+}
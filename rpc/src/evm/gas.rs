@@ -0,0 +1,62 @@
+/*
+ * Copyright 2019 Cargill Incorporated
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! The gas schedule of the Burrow EVM used by the Seth transaction processor. Unlike Ethereum,
+//! Burrow only charges for a handful of operations and doesn't charge for memory at all.
+
+use super::opcodes;
+
+/// The gas limit the transaction processor passes to the EVM for every block
+pub const GAS_LIMIT: u64 = 1 << 31;
+
+pub const BASE_OP: u64 = 0;
+pub const SHA3: u64 = 1;
+pub const GET_ACCOUNT: u64 = 1;
+pub const STORAGE_UPDATE: u64 = 1;
+pub const CREATE_ACCOUNT: u64 = 1;
+
+pub const ECRECOVER: u64 = 1;
+pub const SHA256_WORD: u64 = 1;
+pub const SHA256_BASE: u64 = 1;
+pub const RIPEMD160_WORD: u64 = 1;
+pub const RIPEMD160_BASE: u64 = 1;
+pub const IDENTITY_WORD: u64 = 1;
+pub const IDENTITY_BASE: u64 = 1;
+
+/// Returns the gas charged up front for executing `op`
+pub fn op_cost(op: u8) -> u64 {
+    match op {
+        opcodes::SHA3 => SHA3,
+        opcodes::BALANCE
+        | opcodes::EXTCODESIZE
+        | opcodes::EXTCODECOPY
+        | opcodes::EXTCODEHASH
+        | opcodes::CALL
+        | opcodes::CALLCODE
+        | opcodes::DELEGATECALL
+        | opcodes::STATICCALL
+        | opcodes::SELFDESTRUCT => GET_ACCOUNT,
+        opcodes::SSTORE => STORAGE_UPDATE,
+        opcodes::CREATE => CREATE_ACCOUNT,
+        _ => BASE_OP,
+    }
+}
+
+/// Returns the gas charged by a precompiled contract with the given per word and base cost
+pub fn precompile_cost(input_len: usize, word: u64, base: u64) -> u64 {
+    ((input_len as u64 + 31) / 32) * word + base
+}
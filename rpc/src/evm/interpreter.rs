@@ -0,0 +1,1083 @@
+/*
+ * Copyright 2019 Cargill Incorporated
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! The EVM interpreter. Nested calls and contract creations are kept on an explicit stack of
+//! frames instead of the native stack, so that deep call chains can't overflow it.
+
+use ethereum_types::{U256, U512};
+use std::rc::Rc;
+use tiny_keccak;
+
+use super::gas;
+use super::opcodes::*;
+use super::precompiles::get_precompile;
+use super::state::{Backend, State};
+use super::tracer::{Call, CallKind, Step, Tracer};
+use super::{
+    derive_address, Address, BlockEnv, Error, ExecutionResult, Outcome, Transaction, VmError,
+};
+
+const MAX_STACK_SIZE: usize = 1024;
+const MAX_CALL_DEPTH: usize = 1024;
+/// Burrow doesn't charge for memory, so the memory of all of the frames of an execution together
+/// is bounded instead
+const MAX_MEMORY_SIZE: usize = 1 << 24;
+
+/// Why an instruction stopped the interpreter: either the executed code failed, or the state
+/// couldn't be read from the validator
+enum Halt {
+    Vm(VmError),
+    State(Error),
+}
+
+impl From<VmError> for Halt {
+    fn from(error: VmError) -> Self {
+        Halt::Vm(error)
+    }
+}
+
+impl From<Error> for Halt {
+    fn from(error: Error) -> Self {
+        Halt::State(error)
+    }
+}
+
+/// What the parent frame does with the result of a child frame
+enum Resume {
+    Transaction,
+    Call { out_offset: usize, out_size: usize },
+    Create(Address),
+}
+
+struct FrameResult {
+    outcome: Outcome,
+    output: Vec<u8>,
+    gas_left: u64,
+}
+
+/// What the interpreter does after executing a frame's instructions
+enum Action {
+    Call(Frame),
+    Return(FrameResult),
+}
+
+struct Frame {
+    caller: Address,
+    address: Address,
+    value: U256,
+    data: Vec<u8>,
+    code: Rc<Vec<u8>>,
+    jump_destinations: Vec<bool>,
+    is_static: bool,
+    depth: usize,
+    /// The gas the frame started with
+    gas_limit: u64,
+    gas: u64,
+    pc: usize,
+    stack: Vec<U256>,
+    memory: Vec<u8>,
+    /// How large the memory may grow, which is what the frames that called this one left over
+    memory_limit: usize,
+    return_data: Vec<u8>,
+    /// The state checkpoint to revert to if the frame fails
+    checkpoint: usize,
+    resume: Resume,
+}
+
+impl Frame {
+    fn pop(&mut self) -> Result<U256, VmError> {
+        self.stack.pop().ok_or(VmError::StackUnderflow)
+    }
+
+    fn push(&mut self, value: U256) -> Result<(), VmError> {
+        if self.stack.len() >= MAX_STACK_SIZE {
+            return Err(VmError::StackOverflow);
+        }
+        self.stack.push(value);
+        Ok(())
+    }
+
+    fn use_gas(&mut self, cost: u64) -> Result<(), VmError> {
+        if self.gas < cost {
+            return Err(VmError::OutOfGas);
+        }
+        self.gas -= cost;
+        Ok(())
+    }
+
+    /// Expands the memory to cover the given range and returns it as an offset and a size
+    fn memory_range(&mut self, offset: U256, size: U256) -> Result<(usize, usize), VmError> {
+        if size.is_zero() {
+            return Ok((0, 0));
+        }
+        let offset = to_memory_size(offset)?;
+        let size = to_memory_size(size)?;
+        let end = offset + size;
+        if end > self.memory_limit {
+            return Err(VmError::MemoryLimitExceeded);
+        }
+        if self.memory.len() < end {
+            self.memory.resize((end + 31) / 32 * 32, 0);
+        }
+        Ok((offset, size))
+    }
+
+    fn jump(&mut self, destination: U256) -> Result<(), VmError> {
+        if destination >= U256::from(self.code.len())
+            || !self.jump_destinations[destination.as_usize()]
+        {
+            return Err(VmError::InvalidJump);
+        }
+        self.pc = destination.as_usize();
+        Ok(())
+    }
+}
+
+fn to_memory_size(value: U256) -> Result<usize, VmError> {
+    if value > U256::from(MAX_MEMORY_SIZE) {
+        Err(VmError::MemoryLimitExceeded)
+    } else {
+        Ok(value.as_usize())
+    }
+}
+
+fn jump_destinations(code: &[u8]) -> Vec<bool> {
+    let mut destinations = vec![false; code.len()];
+    let mut pc = 0;
+    while pc < code.len() {
+        if code[pc] == JUMPDEST {
+            destinations[pc] = true;
+        }
+        pc += 1 + push_size(code[pc]);
+    }
+    destinations
+}
+
+/// Copies `src` starting at `offset` into `dest`, filling the rest with zeros
+fn copy_padded(dest: &mut [u8], src: &[u8], offset: U256) {
+    let start = if offset > U256::from(src.len()) {
+        src.len()
+    } else {
+        offset.as_usize()
+    };
+    let len = dest.len().min(src.len() - start);
+    dest[..len].copy_from_slice(&src[start..start + len]);
+    for byte in dest[len..].iter_mut() {
+        *byte = 0;
+    }
+}
+
+pub fn address_to_u256(address: &Address) -> U256 {
+    U256::from_big_endian(address.as_bytes())
+}
+
+pub fn u256_to_address(value: U256) -> Address {
+    let mut bytes = [0u8; 32];
+    value.to_big_endian(&mut bytes);
+    Address::from_slice(&bytes[12..])
+}
+
+fn u512_to_u256(value: U512) -> U256 {
+    let mut bytes = [0u8; 64];
+    value.to_big_endian(&mut bytes);
+    U256::from_big_endian(&bytes[32..])
+}
+
+fn bool_to_u256(value: bool) -> U256 {
+    if value {
+        U256::one()
+    } else {
+        U256::zero()
+    }
+}
+
+fn is_negative(value: U256) -> bool {
+    value.bit(255)
+}
+
+/// Two's complement negation
+fn negate(value: U256) -> U256 {
+    (!value).overflowing_add(U256::one()).0
+}
+
+fn abs(value: U256) -> U256 {
+    if is_negative(value) {
+        negate(value)
+    } else {
+        value
+    }
+}
+
+fn signed_less_than(a: U256, b: U256) -> bool {
+    match (is_negative(a), is_negative(b)) {
+        (true, false) => true,
+        (false, true) => false,
+        _ => a < b,
+    }
+}
+
+/// The gas passed to a called contract: what was requested, but never more than all but one
+/// 64th of the remaining gas
+fn call_gas(available: u64, requested: U256) -> u64 {
+    let max = available - available / 64;
+    if requested > U256::from(max) {
+        max
+    } else {
+        requested.as_u64()
+    }
+}
+
+pub struct Evm<'a, B: Backend + 'a> {
+    state: &'a mut State<B>,
+    env: &'a BlockEnv,
+    origin: Address,
+    gas_price: U256,
+    tracer: Option<&'a mut dyn Tracer>,
+}
+
+impl<'a, B: Backend + 'a> Evm<'a, B> {
+    pub fn new(state: &'a mut State<B>, env: &'a BlockEnv) -> Self {
+        Evm {
+            state,
+            env,
+            origin: Address::zero(),
+            gas_price: U256::zero(),
+            tracer: None,
+        }
+    }
+
+    /// Reports every instruction, call and contract creation to the tracer
+    pub fn with_tracer(mut self, tracer: &'a mut dyn Tracer) -> Self {
+        self.tracer = Some(tracer);
+        self
+    }
+
+    /// Executes a message call or contract creation the same way the transaction processor
+    /// does. Changes to the state are kept, even if the transaction failed.
+    pub fn transact(&mut self, txn: &Transaction) -> Result<ExecutionResult, Error> {
+        self.origin = txn.from;
+        self.gas_price = txn.gas_price;
+
+        let (frame, contract_address) = match txn.to {
+            Some(to) => {
+                let code = self.state.get_code(&to)?;
+                let frame = self.new_frame(
+                    txn.from,
+                    to,
+                    txn.value,
+                    txn.data.clone(),
+                    code,
+                    txn.gas,
+                    Resume::Transaction,
+                );
+                (frame, None)
+            }
+            None => {
+                let nonce = self.state.get_nonce(&txn.from)?;
+                let address = derive_address(&txn.from, nonce);
+                self.state.set_nonce(&txn.from, nonce + 1)?;
+                self.state.create_account(&address)?;
+                self.state.set_nonce(&address, 1)?;
+                // The processor runs the init code with the new contract as the caller
+                let frame = self.new_frame(
+                    address,
+                    address,
+                    txn.value,
+                    Vec::new(),
+                    Rc::new(txn.data.clone()),
+                    txn.gas,
+                    Resume::Create(address),
+                );
+                (frame, Some(address))
+            }
+        };
+
+        let kind = match txn.to {
+            Some(_) => CallKind::Call,
+            None => CallKind::Create,
+        };
+        self.trace_enter(kind, txn.from, frame.address, &txn.data, txn.gas, txn.value);
+        if !self.state.transfer(&txn.from, &frame.address, txn.value)? {
+            let result = FrameResult {
+                outcome: Outcome::Error(VmError::InsufficientBalance),
+                output: Vec::new(),
+                gas_left: txn.gas,
+            };
+            self.trace_exit(&result, txn.gas);
+            return Ok(ExecutionResult {
+                outcome: result.outcome,
+                output: result.output,
+                gas_used: 0,
+                contract_address: None,
+            });
+        }
+        let result = self.run(frame)?;
+
+        Ok(ExecutionResult {
+            contract_address: match result.outcome {
+                Outcome::Success => contract_address,
+                _ => None,
+            },
+            outcome: result.outcome,
+            output: result.output,
+            gas_used: txn.gas - result.gas_left,
+        })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn new_frame(
+        &self,
+        caller: Address,
+        address: Address,
+        value: U256,
+        data: Vec<u8>,
+        code: Rc<Vec<u8>>,
+        gas: u64,
+        resume: Resume,
+    ) -> Frame {
+        Frame {
+            caller,
+            address,
+            value,
+            data,
+            jump_destinations: jump_destinations(&code),
+            code,
+            is_static: false,
+            depth: 0,
+            gas_limit: gas,
+            gas,
+            pc: 0,
+            stack: Vec::new(),
+            memory: Vec::new(),
+            memory_limit: MAX_MEMORY_SIZE,
+            return_data: Vec::new(),
+            checkpoint: self.state.checkpoint(),
+            resume,
+        }
+    }
+
+    fn run(&mut self, frame: Frame) -> Result<FrameResult, Error> {
+        let mut frames = vec![frame];
+        loop {
+            let action = self.run_frame(frames.last_mut().unwrap())?;
+            let result = match action {
+                Action::Call(child) => {
+                    frames.push(child);
+                    continue;
+                }
+                Action::Return(result) => result,
+            };
+
+            let frame = frames.pop().unwrap();
+            let result = self.finish_frame(&frame, result)?;
+            self.trace_exit(&result, frame.gas_limit);
+            match frames.last_mut() {
+                Some(parent) => resume(parent, frame.resume, result),
+                None => return Ok(result),
+            }
+        }
+    }
+
+    /// Executes instructions until the frame returns or calls another contract
+    fn run_frame(&mut self, frame: &mut Frame) -> Result<Action, Error> {
+        loop {
+            match self.step(frame) {
+                Ok(None) => continue,
+                Ok(Some(action)) => return Ok(action),
+                Err(Halt::Vm(error)) => {
+                    return Ok(Action::Return(FrameResult {
+                        outcome: Outcome::Error(error),
+                        output: Vec::new(),
+                        gas_left: 0,
+                    }));
+                }
+                Err(Halt::State(error)) => return Err(error),
+            }
+        }
+    }
+
+    /// Stores the code of created contracts, and reverts the changes of failed frames
+    fn finish_frame(&mut self, frame: &Frame, result: FrameResult) -> Result<FrameResult, Error> {
+        match result.outcome {
+            Outcome::Success => {
+                if let Resume::Create(address) = frame.resume {
+                    self.state.set_code(&address, result.output.clone())?;
+                }
+            }
+            _ => self.state.revert_to(frame.checkpoint),
+        }
+        Ok(result)
+    }
+
+    /// Executes a single instruction
+    fn step(&mut self, frame: &mut Frame) -> Result<Option<Action>, Halt> {
+        let op = match frame.code.get(frame.pc) {
+            Some(op) => *op,
+            None => return Ok(Some(success(frame, Vec::new()))),
+        };
+        self.state.use_step()?;
+        if self.tracer.is_some() {
+            self.trace_step(frame, op)?;
+        }
+        frame.use_gas(gas::op_cost(op))?;
+        frame.pc += 1;
+
+        match op {
+            STOP => return Ok(Some(success(frame, Vec::new()))),
+
+            ADD => {
+                let (a, b) = (frame.pop()?, frame.pop()?);
+                frame.push(a.overflowing_add(b).0)?;
+            }
+            MUL => {
+                let (a, b) = (frame.pop()?, frame.pop()?);
+                frame.push(a.overflowing_mul(b).0)?;
+            }
+            SUB => {
+                let (a, b) = (frame.pop()?, frame.pop()?);
+                frame.push(a.overflowing_sub(b).0)?;
+            }
+            DIV => {
+                let (a, b) = (frame.pop()?, frame.pop()?);
+                frame.push(if b.is_zero() { b } else { a / b })?;
+            }
+            SDIV => {
+                let (a, b) = (frame.pop()?, frame.pop()?);
+                let result = if b.is_zero() {
+                    b
+                } else {
+                    let quotient = abs(a) / abs(b);
+                    if is_negative(a) != is_negative(b) {
+                        negate(quotient)
+                    } else {
+                        quotient
+                    }
+                };
+                frame.push(result)?;
+            }
+            MOD => {
+                let (a, b) = (frame.pop()?, frame.pop()?);
+                frame.push(if b.is_zero() { b } else { a % b })?;
+            }
+            SMOD => {
+                let (a, b) = (frame.pop()?, frame.pop()?);
+                let result = if b.is_zero() {
+                    b
+                } else {
+                    let remainder = abs(a) % abs(b);
+                    if is_negative(a) {
+                        negate(remainder)
+                    } else {
+                        remainder
+                    }
+                };
+                frame.push(result)?;
+            }
+            ADDMOD => {
+                let (a, b, n) = (frame.pop()?, frame.pop()?, frame.pop()?);
+                let result = if n.is_zero() {
+                    n
+                } else {
+                    u512_to_u256((U512::from(a) + U512::from(b)) % U512::from(n))
+                };
+                frame.push(result)?;
+            }
+            MULMOD => {
+                let (a, b, n) = (frame.pop()?, frame.pop()?, frame.pop()?);
+                let result = if n.is_zero() {
+                    n
+                } else {
+                    u512_to_u256((U512::from(a) * U512::from(b)) % U512::from(n))
+                };
+                frame.push(result)?;
+            }
+            EXP => {
+                let (base, exponent) = (frame.pop()?, frame.pop()?);
+                frame.push(base.overflowing_pow(exponent).0)?;
+            }
+            SIGNEXTEND => {
+                let (size, value) = (frame.pop()?, frame.pop()?);
+                let result = if size < U256::from(31) {
+                    let bit = size.as_usize() * 8 + 7;
+                    let mask = (U256::one() << bit) - U256::one();
+                    if value.bit(bit) {
+                        value | !mask
+                    } else {
+                        value & mask
+                    }
+                } else {
+                    value
+                };
+                frame.push(result)?;
+            }
+
+            LT => {
+                let (a, b) = (frame.pop()?, frame.pop()?);
+                frame.push(bool_to_u256(a < b))?;
+            }
+            GT => {
+                let (a, b) = (frame.pop()?, frame.pop()?);
+                frame.push(bool_to_u256(a > b))?;
+            }
+            SLT => {
+                let (a, b) = (frame.pop()?, frame.pop()?);
+                frame.push(bool_to_u256(signed_less_than(a, b)))?;
+            }
+            SGT => {
+                let (a, b) = (frame.pop()?, frame.pop()?);
+                frame.push(bool_to_u256(signed_less_than(b, a)))?;
+            }
+            EQ => {
+                let (a, b) = (frame.pop()?, frame.pop()?);
+                frame.push(bool_to_u256(a == b))?;
+            }
+            ISZERO => {
+                let a = frame.pop()?;
+                frame.push(bool_to_u256(a.is_zero()))?;
+            }
+            AND => {
+                let (a, b) = (frame.pop()?, frame.pop()?);
+                frame.push(a & b)?;
+            }
+            OR => {
+                let (a, b) = (frame.pop()?, frame.pop()?);
+                frame.push(a | b)?;
+            }
+            XOR => {
+                let (a, b) = (frame.pop()?, frame.pop()?);
+                frame.push(a ^ b)?;
+            }
+            NOT => {
+                let a = frame.pop()?;
+                frame.push(!a)?;
+            }
+            BYTE => {
+                let (index, value) = (frame.pop()?, frame.pop()?);
+                let result = if index < U256::from(32) {
+                    U256::from(value.byte(31 - index.as_usize()))
+                } else {
+                    U256::zero()
+                };
+                frame.push(result)?;
+            }
+            SHL => {
+                let (shift, value) = (frame.pop()?, frame.pop()?);
+                let result = if shift < U256::from(256) {
+                    value << shift.as_usize()
+                } else {
+                    U256::zero()
+                };
+                frame.push(result)?;
+            }
+            SHR => {
+                let (shift, value) = (frame.pop()?, frame.pop()?);
+                let result = if shift < U256::from(256) {
+                    value >> shift.as_usize()
+                } else {
+                    U256::zero()
+                };
+                frame.push(result)?;
+            }
+            SAR => {
+                let (shift, value) = (frame.pop()?, frame.pop()?);
+                let result = match (is_negative(value), shift < U256::from(256)) {
+                    (false, true) => value >> shift.as_usize(),
+                    (false, false) => U256::zero(),
+                    (true, true) => !(!value >> shift.as_usize()),
+                    (true, false) => U256::max_value(),
+                };
+                frame.push(result)?;
+            }
+
+            SHA3 => {
+                let (offset, size) = (frame.pop()?, frame.pop()?);
+                let (offset, size) = frame.memory_range(offset, size)?;
+                let hash = tiny_keccak::keccak256(&frame.memory[offset..offset + size]);
+                frame.push(U256::from_big_endian(&hash))?;
+            }
+
+            ADDRESS => {
+                let address = address_to_u256(&frame.address);
+                frame.push(address)?;
+            }
+            BALANCE => {
+                let address = u256_to_address(frame.pop()?);
+                let balance = self.state.get_balance(&address)?;
+                frame.push(balance)?;
+            }
+            ORIGIN => frame.push(address_to_u256(&self.origin))?,
+            CALLER => {
+                let caller = address_to_u256(&frame.caller);
+                frame.push(caller)?;
+            }
+            CALLVALUE => {
+                let value = frame.value;
+                frame.push(value)?;
+            }
+            CALLDATALOAD => {
+                let offset = frame.pop()?;
+                let mut word = [0u8; 32];
+                copy_padded(&mut word, &frame.data, offset);
+                frame.push(U256::from_big_endian(&word))?;
+            }
+            CALLDATASIZE => {
+                let size = U256::from(frame.data.len());
+                frame.push(size)?;
+            }
+            CALLDATACOPY => {
+                let (mem_offset, offset, size) = (frame.pop()?, frame.pop()?, frame.pop()?);
+                let (mem_offset, size) = frame.memory_range(mem_offset, size)?;
+                copy_padded(
+                    &mut frame.memory[mem_offset..mem_offset + size],
+                    &frame.data,
+                    offset,
+                );
+            }
+            CODESIZE => {
+                let size = U256::from(frame.code.len());
+                frame.push(size)?;
+            }
+            CODECOPY => {
+                let (mem_offset, offset, size) = (frame.pop()?, frame.pop()?, frame.pop()?);
+                let (mem_offset, size) = frame.memory_range(mem_offset, size)?;
+                copy_padded(
+                    &mut frame.memory[mem_offset..mem_offset + size],
+                    &frame.code,
+                    offset,
+                );
+            }
+            GASPRICE => frame.push(self.gas_price)?,
+            EXTCODESIZE => {
+                let address = u256_to_address(frame.pop()?);
+                let code = self.state.get_code(&address)?;
+                frame.push(U256::from(code.len()))?;
+            }
+            EXTCODECOPY => {
+                let address = u256_to_address(frame.pop()?);
+                let (mem_offset, offset, size) = (frame.pop()?, frame.pop()?, frame.pop()?);
+                let (mem_offset, size) = frame.memory_range(mem_offset, size)?;
+                let code = self.state.get_code(&address)?;
+                copy_padded(
+                    &mut frame.memory[mem_offset..mem_offset + size],
+                    &code,
+                    offset,
+                );
+            }
+            RETURNDATASIZE => {
+                let size = U256::from(frame.return_data.len());
+                frame.push(size)?;
+            }
+            RETURNDATACOPY => {
+                let (mem_offset, offset, size) = (frame.pop()?, frame.pop()?, frame.pop()?);
+                let end = offset.overflowing_add(size);
+                if end.1 || end.0 > U256::from(frame.return_data.len()) {
+                    return Err(VmError::ReturnDataOutOfBounds.into());
+                }
+                let (mem_offset, size) = frame.memory_range(mem_offset, size)?;
+                let offset = offset.as_usize();
+                frame.memory[mem_offset..mem_offset + size]
+                    .copy_from_slice(&frame.return_data[offset..offset + size]);
+            }
+            EXTCODEHASH => {
+                let address = u256_to_address(frame.pop()?);
+                let hash = if self.state.exists(&address)? {
+                    let code = self.state.get_code(&address)?;
+                    U256::from_big_endian(&tiny_keccak::keccak256(&code))
+                } else {
+                    U256::zero()
+                };
+                frame.push(hash)?;
+            }
+
+            BLOCKHASH => {
+                let block_num = frame.pop()?;
+                let hash = if block_num > U256::from(u64::max_value()) {
+                    U256::zero()
+                } else {
+                    let hash = self
+                        .state
+                        .get_block_hash(self.env.number, block_num.as_u64())?;
+                    U256::from_big_endian(hash.as_bytes())
+                };
+                frame.push(hash)?;
+            }
+            COINBASE | DIFFICULTY => frame.push(U256::zero())?,
+            TIMESTAMP => frame.push(U256::from(self.env.timestamp))?,
+            NUMBER => frame.push(U256::from(self.env.number))?,
+            GASLIMIT => frame.push(U256::from(self.env.gas_limit))?,
+
+            POP => {
+                frame.pop()?;
+            }
+            MLOAD => {
+                let offset = frame.pop()?;
+                let (offset, _) = frame.memory_range(offset, U256::from(32))?;
+                let value = U256::from_big_endian(&frame.memory[offset..offset + 32]);
+                frame.push(value)?;
+            }
+            MSTORE => {
+                let (offset, value) = (frame.pop()?, frame.pop()?);
+                let (offset, _) = frame.memory_range(offset, U256::from(32))?;
+                value.to_big_endian(&mut frame.memory[offset..offset + 32]);
+            }
+            MSTORE8 => {
+                let (offset, value) = (frame.pop()?, frame.pop()?);
+                let (offset, _) = frame.memory_range(offset, U256::one())?;
+                frame.memory[offset] = value.byte(0);
+            }
+            SLOAD => {
+                let key = frame.pop()?;
+                let value = self.state.get_storage(&frame.address, &key)?;
+                frame.push(value)?;
+            }
+            SSTORE => {
+                if frame.is_static {
+                    return Err(VmError::WriteProtection.into());
+                }
+                let (key, value) = (frame.pop()?, frame.pop()?);
+                self.state.set_storage(&frame.address, key, value)?;
+            }
+            JUMP => {
+                let destination = frame.pop()?;
+                frame.jump(destination)?;
+            }
+            JUMPI => {
+                let (destination, condition) = (frame.pop()?, frame.pop()?);
+                if !condition.is_zero() {
+                    frame.jump(destination)?;
+                }
+            }
+            PC => {
+                let pc = U256::from(frame.pc - 1);
+                frame.push(pc)?;
+            }
+            MSIZE => {
+                let size = U256::from(frame.memory.len());
+                frame.push(size)?;
+            }
+            GAS => {
+                let gas = U256::from(frame.gas);
+                frame.push(gas)?;
+            }
+            JUMPDEST => {}
+
+            PUSH1..=PUSH32 => {
+                let size = push_size(op);
+                let mut word = [0u8; 32];
+                let start = frame.pc.min(frame.code.len());
+                let end = (frame.pc + size).min(frame.code.len());
+                word[32 - size..32 - size + end - start].copy_from_slice(&frame.code[start..end]);
+                frame.pc += size;
+                frame.push(U256::from_big_endian(&word))?;
+            }
+            DUP1..=DUP16 => {
+                let n = (op - DUP1) as usize + 1;
+                if frame.stack.len() < n {
+                    return Err(VmError::StackUnderflow.into());
+                }
+                let value = frame.stack[frame.stack.len() - n];
+                frame.push(value)?;
+            }
+            SWAP1..=SWAP16 => {
+                let n = (op - SWAP1) as usize + 1;
+                let len = frame.stack.len();
+                if len < n + 1 {
+                    return Err(VmError::StackUnderflow.into());
+                }
+                frame.stack.swap(len - 1, len - 1 - n);
+            }
+            LOG0..=LOG4 => {
+                if frame.is_static {
+                    return Err(VmError::WriteProtection.into());
+                }
+                let (offset, size) = (frame.pop()?, frame.pop()?);
+                for _ in 0..(op - LOG0) {
+                    frame.pop()?;
+                }
+                frame.memory_range(offset, size)?;
+            }
+
+            CREATE => return self.create(frame),
+            CALL | CALLCODE | DELEGATECALL | STATICCALL => return self.call(frame, op),
+            RETURN | REVERT => {
+                let (offset, size) = (frame.pop()?, frame.pop()?);
+                let (offset, size) = frame.memory_range(offset, size)?;
+                let output = frame.memory[offset..offset + size].to_vec();
+                if op == RETURN {
+                    return Ok(Some(success(frame, output)));
+                }
+                return Ok(Some(Action::Return(FrameResult {
+                    outcome: Outcome::Revert,
+                    output,
+                    gas_left: frame.gas,
+                })));
+            }
+            SELFDESTRUCT => {
+                if frame.is_static {
+                    return Err(VmError::WriteProtection.into());
+                }
+                let beneficiary = u256_to_address(frame.pop()?);
+                let balance = self.state.get_balance(&frame.address)?;
+                self.state.transfer(&frame.address, &beneficiary, balance)?;
+                self.state.remove_account(&frame.address)?;
+                return Ok(Some(success(frame, Vec::new())));
+            }
+            _ => return Err(VmError::InvalidOpcode(op).into()),
+        }
+
+        Ok(None)
+    }
+
+    fn create(&mut self, frame: &mut Frame) -> Result<Option<Action>, Halt> {
+        if frame.is_static {
+            return Err(VmError::WriteProtection.into());
+        }
+        let (value, offset, size) = (frame.pop()?, frame.pop()?, frame.pop()?);
+        let (offset, size) = frame.memory_range(offset, size)?;
+        let init = frame.memory[offset..offset + size].to_vec();
+        frame.return_data.clear();
+
+        if frame.depth >= MAX_CALL_DEPTH || self.state.get_balance(&frame.address)? < value {
+            frame.push(U256::zero())?;
+            return Ok(None);
+        }
+
+        let nonce = self.state.get_nonce(&frame.address)?;
+        let address = derive_address(&frame.address, nonce);
+        self.state.set_nonce(&frame.address, nonce + 1)?;
+
+        let checkpoint = self.state.checkpoint();
+        self.state.create_account(&address)?;
+        self.state.set_nonce(&address, 1)?;
+        self.state.transfer(&frame.address, &address, value)?;
+
+        // Burrow gives the init code all of the remaining gas
+        let gas = frame.gas;
+        frame.gas = 0;
+
+        let mut child = self.new_frame(
+            frame.address,
+            address,
+            value,
+            Vec::new(),
+            Rc::new(init),
+            gas,
+            Resume::Create(address),
+        );
+        child.checkpoint = checkpoint;
+        child.depth = frame.depth + 1;
+        child.memory_limit = frame.memory_limit - frame.memory.len();
+        self.trace_enter(
+            CallKind::Create,
+            frame.address,
+            address,
+            &child.code,
+            gas,
+            value,
+        );
+        Ok(Some(Action::Call(child)))
+    }
+
+    fn call(&mut self, frame: &mut Frame, op: u8) -> Result<Option<Action>, Halt> {
+        let requested_gas = frame.pop()?;
+        let to = u256_to_address(frame.pop()?);
+        let value = match op {
+            CALL | CALLCODE => frame.pop()?,
+            _ => U256::zero(),
+        };
+        let (in_offset, in_size) = (frame.pop()?, frame.pop()?);
+        let (out_offset, out_size) = (frame.pop()?, frame.pop()?);
+        if frame.is_static && op == CALL && !value.is_zero() {
+            return Err(VmError::WriteProtection.into());
+        }
+
+        let (in_offset, in_size) = frame.memory_range(in_offset, in_size)?;
+        let (out_offset, out_size) = frame.memory_range(out_offset, out_size)?;
+        let input = frame.memory[in_offset..in_offset + in_size].to_vec();
+        frame.return_data.clear();
+
+        let gas = call_gas(frame.gas, requested_gas);
+        frame.gas -= gas;
+
+        let (caller, address, value, is_static) = match op {
+            CALL => (frame.address, to, value, frame.is_static),
+            CALLCODE => (frame.address, frame.address, value, frame.is_static),
+            DELEGATECALL => (frame.caller, frame.address, frame.value, frame.is_static),
+            _ => (frame.address, to, U256::zero(), true),
+        };
+        let transfers_value = op == CALL || op == CALLCODE;
+
+        if frame.depth >= MAX_CALL_DEPTH
+            || (transfers_value && self.state.get_balance(&frame.address)? < value)
+        {
+            frame.gas += gas;
+            frame.push(U256::zero())?;
+            return Ok(None);
+        }
+
+        let checkpoint = self.state.checkpoint();
+        if op == CALL {
+            self.state.transfer(&frame.address, &to, value)?;
+        }
+
+        let kind = match op {
+            CALL => CallKind::Call,
+            CALLCODE => CallKind::CallCode,
+            DELEGATECALL => CallKind::DelegateCall,
+            _ => CallKind::StaticCall,
+        };
+
+        if let Some(precompile) = get_precompile(&to) {
+            self.trace_enter(kind, frame.address, to, &input, gas, value);
+            let mut gas_left = gas;
+            let result = match precompile(&input, &mut gas_left) {
+                Some(output) => FrameResult {
+                    outcome: Outcome::Success,
+                    output,
+                    gas_left,
+                },
+                None => {
+                    self.state.revert_to(checkpoint);
+                    FrameResult {
+                        outcome: Outcome::Error(VmError::OutOfGas),
+                        output: Vec::new(),
+                        gas_left: 0,
+                    }
+                }
+            };
+            self.trace_exit(&result, gas);
+            resume(
+                frame,
+                Resume::Call {
+                    out_offset,
+                    out_size,
+                },
+                result,
+            );
+            return Ok(None);
+        }
+
+        let code = self.state.get_code(&to)?;
+        let mut child = self.new_frame(
+            caller,
+            address,
+            value,
+            input,
+            code,
+            gas,
+            Resume::Call {
+                out_offset,
+                out_size,
+            },
+        );
+        child.checkpoint = checkpoint;
+        child.is_static = is_static;
+        child.depth = frame.depth + 1;
+        child.memory_limit = frame.memory_limit - frame.memory.len();
+        self.trace_enter(kind, frame.address, to, &child.data, gas, value);
+        Ok(Some(Action::Call(child)))
+    }
+
+    fn trace_step(&mut self, frame: &Frame, op: u8) -> Result<(), Error> {
+        let len = frame.stack.len();
+        let storage = match op {
+            SLOAD if len >= 1 => {
+                let key = frame.stack[len - 1];
+                Some((key, self.state.get_storage(&frame.address, &key)?))
+            }
+            SSTORE if len >= 2 => Some((frame.stack[len - 1], frame.stack[len - 2])),
+            _ => None,
+        };
+        if let Some(ref mut tracer) = self.tracer {
+            tracer.step(&Step {
+                pc: frame.pc,
+                op,
+                gas: frame.gas,
+                gas_cost: gas::op_cost(op),
+                depth: frame.depth + 1,
+                address: &frame.address,
+                stack: &frame.stack,
+                memory: &frame.memory,
+                storage,
+            });
+        }
+        Ok(())
+    }
+
+    fn trace_enter(
+        &mut self,
+        kind: CallKind,
+        from: Address,
+        to: Address,
+        input: &[u8],
+        gas: u64,
+        value: U256,
+    ) {
+        if let Some(ref mut tracer) = self.tracer {
+            tracer.enter(&Call {
+                kind,
+                from,
+                to,
+                input,
+                gas,
+                value,
+            });
+        }
+    }
+
+    fn trace_exit(&mut self, result: &FrameResult, gas_limit: u64) {
+        if let Some(ref mut tracer) = self.tracer {
+            tracer.exit(&result.outcome, &result.output, gas_limit - result.gas_left);
+        }
+    }
+}
+
+fn success(frame: &Frame, output: Vec<u8>) -> Action {
+    Action::Return(FrameResult {
+        outcome: Outcome::Success,
+        output,
+        gas_left: frame.gas,
+    })
+}
+
+/// Hands the result of a child frame back to its parent
+fn resume(parent: &mut Frame, how: Resume, result: FrameResult) {
+    parent.gas += result.gas_left;
+    // The arguments of the call or create were popped, so there is room for the result
+    match how {
+        Resume::Call {
+            out_offset,
+            out_size,
+        } => {
+            let len = out_size.min(result.output.len());
+            parent.memory[out_offset..out_offset + len].copy_from_slice(&result.output[..len]);
+            parent
+                .stack
+                .push(bool_to_u256(result.outcome == Outcome::Success));
+            parent.return_data = result.output;
+        }
+        Resume::Create(address) => {
+            if result.outcome == Outcome::Success {
+                parent.stack.push(address_to_u256(&address));
+            } else {
+                parent.stack.push(U256::zero());
+                parent.return_data = result.output;
+            }
+        }
+        Resume::Transaction => {}
+    }
+}
@@ -0,0 +1,648 @@
+/*
+ * Copyright 2019 Cargill Incorporated
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! An EVM for simulating Seth transactions within the RPC. It follows the semantics of the
+//! Burrow EVM used by the Seth transaction processor, reads state lazily from the validator and
+//! never writes anything back.
+
+pub mod gas;
+mod interpreter;
+pub mod opcodes;
+mod precompiles;
+mod state;
+pub mod tracer;
+
+use client::{BlockKey, Error as ClientError, ValidatorClient};
+use ethereum_types::{H160, U256};
+use jsonrpc_core::{Error as RpcError, ErrorCode};
+use messages::block_info::{BlockInfo, BlockInfoConfig};
+use messages::seth::EvmEntry;
+use sawtooth_sdk::messaging::stream::MessageSender;
+use std::error::Error as StdError;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use tiny_keccak;
+use transactions::SethTransaction;
+use transform;
+
+pub use self::interpreter::Evm;
+pub use self::precompiles::recover_address;
+pub use self::state::{Backend, State, MAX_STEPS};
+
+pub type Address = H160;
+
+/// An error that prevented the EVM from executing a transaction
+#[derive(Debug)]
+pub enum Error {
+    StateError(String),
+    /// Reading state from the validator failed
+    ClientError(ClientError),
+    /// The executions of the request ran more instructions than the limit
+    StepLimitExceeded(u64),
+}
+
+impl Error {
+    /// Returns the error to fail a JSON-RPC request with because of this error: `error`, unless
+    /// the validator was unavailable or the code ran too long, which clients are told
+    pub fn or_unavailable(&self, error: RpcError) -> RpcError {
+        match *self {
+            Error::ClientError(ref client_error) => client_error.or_unavailable(error),
+            Error::StepLimitExceeded(_) => RpcError {
+                code: ErrorCode::ServerError(-32000),
+                message: format!("{}", self),
+                data: None,
+            },
+            Error::StateError(_) => error,
+        }
+    }
+}
+
+impl StdError for Error {
+    fn description(&self) -> &str {
+        match *self {
+            Error::StateError(ref msg) => msg,
+            Error::ClientError(ref error) => error.description(),
+            Error::StepLimitExceeded(_) => "Execution exceeded the instruction limit",
+        }
+    }
+
+    fn cause(&self) -> Option<&dyn StdError> {
+        None
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match *self {
+            Error::StateError(ref msg) => write!(f, "StateError: {}", msg),
+            Error::ClientError(ref error) => error.fmt(f),
+            Error::StepLimitExceeded(limit) => {
+                write!(f, "execution exceeded the limit of {} instructions", limit)
+            }
+        }
+    }
+}
+
+/// An error in the executed code, which consumes all of the gas of the frame it occurred in
+#[derive(Debug, Clone, PartialEq)]
+pub enum VmError {
+    OutOfGas,
+    StackUnderflow,
+    StackOverflow,
+    InvalidJump,
+    InvalidOpcode(u8),
+    WriteProtection,
+    ReturnDataOutOfBounds,
+    MemoryLimitExceeded,
+    InsufficientBalance,
+}
+
+impl Display for VmError {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match *self {
+            VmError::OutOfGas => write!(f, "out of gas"),
+            VmError::StackUnderflow => write!(f, "stack underflow"),
+            VmError::StackOverflow => write!(f, "stack limit reached"),
+            VmError::InvalidJump => write!(f, "invalid jump destination"),
+            VmError::InvalidOpcode(op) => write!(f, "invalid opcode {:#04x}", op),
+            VmError::WriteProtection => write!(f, "write protection"),
+            VmError::ReturnDataOutOfBounds => write!(f, "return data out of bounds"),
+            VmError::MemoryLimitExceeded => write!(f, "memory limit exceeded"),
+            VmError::InsufficientBalance => write!(f, "insufficient balance for transfer"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Success,
+    Revert,
+    Error(VmError),
+}
+
+#[derive(Debug, Clone)]
+pub struct ExecutionResult {
+    pub outcome: Outcome,
+    /// The return data, or the revert data if the transaction reverted
+    pub output: Vec<u8>,
+    pub gas_used: u64,
+    pub contract_address: Option<Address>,
+}
+
+/// A message call, or a contract creation if `to` isn't set
+#[derive(Debug, Clone)]
+pub struct Transaction {
+    pub from: Address,
+    pub to: Option<Address>,
+    pub data: Vec<u8>,
+    pub gas: u64,
+    pub gas_price: U256,
+    /// The value transferred to the called or created contract
+    pub value: U256,
+}
+
+impl Transaction {
+    /// Returns the message call or contract creation made by a Seth transaction, if it is a
+    /// well-formed one.
+    /// The processor never transfers the value of a Seth transaction, so the value is zero.
+    pub fn from_seth(from: Address, txn: &SethTransaction) -> Option<Self> {
+        match *txn {
+            SethTransaction::CreateContractAccount(ref txn) => Some(Transaction {
+                from,
+                to: None,
+                data: txn.init.clone(),
+                gas: txn.gas_limit,
+                gas_price: transform::u256_from_fields(&txn.gas_price_u256, txn.gas_price)?,
+                value: U256::zero(),
+            }),
+            SethTransaction::MessageCall(ref txn) => Some(Transaction {
+                from,
+                to: Some(seth_address(&txn.to)?),
+                data: txn.data.clone(),
+                gas: txn.gas_limit,
+                gas_price: transform::u256_from_fields(&txn.gas_price_u256, txn.gas_price)?,
+                value: U256::zero(),
+            }),
+            SethTransaction::CreateExternalAccount(_) | SethTransaction::SetPermissions(_) => None,
+        }
+    }
+}
+
+/// The block the EVM executes in
+#[derive(Debug, Clone, Default)]
+pub struct BlockEnv {
+    pub number: u64,
+    pub timestamp: u64,
+    pub gas_limit: u64,
+}
+
+impl BlockEnv {
+    /// Reads the latest block from the block info in state, the same way the transaction
+    /// processor does. If there is no block info, everything but the gas limit is zero.
+    pub fn load<B: Backend>(backend: &B) -> Result<Self, Error> {
+        let latest_block = match backend.get_block_info_config()? {
+            Some(config) => config.latest_block,
+            None => {
+                return Ok(BlockEnv {
+                    gas_limit: gas::GAS_LIMIT,
+                    ..BlockEnv::default()
+                });
+            }
+        };
+        let info = backend.get_block_info(latest_block)?.ok_or_else(|| {
+            Error::StateError(format!("Block info for block {} is missing", latest_block))
+        })?;
+
+        Ok(BlockEnv {
+            number: info.block_num,
+            timestamp: info.timestamp,
+            gas_limit: gas::GAS_LIMIT,
+        })
+    }
+}
+
+/// Parses an address in a Seth transaction, which is either an address or a state address
+pub fn seth_address(bytes: &[u8]) -> Option<Address> {
+    match bytes.len() {
+        20 => Some(Address::from_slice(bytes)),
+        35 => Some(Address::from_slice(&bytes[3..23])),
+        _ => None,
+    }
+}
+
+/// Computes the address of the contract created by `address` with the given nonce
+pub fn derive_address(address: &Address, nonce: u64) -> Address {
+    if nonce == 0 {
+        return *address;
+    }
+    let mut buf = address.as_bytes().to_vec();
+    buf.extend_from_slice(&[
+        (nonce >> 56) as u8,
+        (nonce >> 48) as u8,
+        (nonce >> 40) as u8,
+        (nonce >> 32) as u8,
+        (nonce >> 24) as u8,
+        (nonce >> 16) as u8,
+        (nonce >> 8) as u8,
+        nonce as u8,
+    ]);
+    Address::from_slice(&tiny_keccak::keccak256(&buf)[..20])
+}
+
+/// Reads state from the validator at a fixed state root
+pub struct ValidatorBackend<'a, S: MessageSender + 'a> {
+    client: &'a ValidatorClient<S>,
+    state_root: String,
+}
+
+impl<'a, S: MessageSender + 'a> ValidatorBackend<'a, S> {
+    pub fn new(client: &'a ValidatorClient<S>, block: BlockKey) -> Result<Self, Error> {
+        let state_root = client.get_state_root(block).map_err(Error::ClientError)?;
+        Ok(ValidatorBackend { client, state_root })
+    }
+}
+
+impl<'a, S: MessageSender + 'a> Backend for ValidatorBackend<'a, S> {
+    fn get_entry(&self, address: &Address) -> Result<Option<EvmEntry>, Error> {
+        self.client
+            .get_entry_at_root(
+                &transform::bytes_to_hex_str(address.as_bytes()),
+                Some(self.state_root.clone()),
+            )
+            .map_err(Error::ClientError)
+    }
+
+    fn get_block_info_config(&self) -> Result<Option<BlockInfoConfig>, Error> {
+        self.client
+            .get_block_info_config(Some(self.state_root.clone()))
+            .map_err(Error::ClientError)
+    }
+
+    fn get_block_info(&self, block_num: u64) -> Result<Option<BlockInfo>, Error> {
+        self.client
+            .get_block_info(block_num, Some(self.state_root.clone()))
+            .map_err(Error::ClientError)
+    }
+}
+
+/// Simulates the transaction against the state of the given block
+pub fn simulate<S: MessageSender>(
+    client: &ValidatorClient<S>,
+    block: BlockKey,
+    txn: &Transaction,
+) -> Result<ExecutionResult, Error> {
+    let backend = ValidatorBackend::new(client, block)?;
+    let env = BlockEnv::load(&backend)?;
+    let mut state = State::new(backend);
+    Evm::new(&mut state, &env).transact(txn)
+}
+
+/// Applies a committed Seth transaction to the state, the same way the transaction processor
+/// did. Permissions aren't tracked, because the transaction is known to have been valid.
+pub fn apply<B: Backend>(
+    state: &mut State<B>,
+    env: &BlockEnv,
+    from: Address,
+    txn: &SethTransaction,
+) -> Result<(), Error> {
+    if let Some(txn) = Transaction::from_seth(from, txn) {
+        Evm::new(state, env).transact(&txn)?;
+        // The processor increments the sender's nonce after a message call
+        if txn.to.is_some() {
+            let nonce = state.get_nonce(&from)?;
+            state.set_nonce(&from, nonce + 1)?;
+        }
+        return Ok(());
+    }
+
+    match *txn {
+        SethTransaction::CreateExternalAccount(ref txn) if !txn.to.is_empty() => {
+            let nonce = state.get_nonce(&from)?;
+            state.set_nonce(&from, nonce + 1)?;
+            let address = seth_address(&txn.to)
+                .ok_or_else(|| Error::StateError(String::from("Malformed account address")))?;
+            state.create_account(&address)?;
+            state.set_nonce(&address, 1)
+        }
+        SethTransaction::CreateExternalAccount(_) => {
+            state.create_account(&from)?;
+            state.set_nonce(&from, 1)
+        }
+        _ => {
+            let nonce = state.get_nonce(&from)?;
+            state.set_nonce(&from, nonce + 1)
+        }
+    }
+}
+
+/// Estimates the gas the transaction needs against the state of the given block
+pub fn simulate_estimate_gas<S: MessageSender>(
+    client: &ValidatorClient<S>,
+    block: BlockKey,
+    txn: &Transaction,
+) -> Result<(u64, ExecutionResult), Error> {
+    let backend = ValidatorBackend::new(client, block)?;
+    let env = BlockEnv::load(&backend)?;
+    let mut state = State::new(backend);
+    estimate_gas(&mut state, &env, txn)
+}
+
+/// Finds the lowest gas limit, up to `txn.gas`, that the transaction succeeds with. Returns that
+/// limit and the result of executing the transaction with it, or `txn.gas` and the failed
+/// result if the transaction doesn't succeed with any limit.
+pub fn estimate_gas<B: Backend>(
+    state: &mut State<B>,
+    env: &BlockEnv,
+    txn: &Transaction,
+) -> Result<(u64, ExecutionResult), Error> {
+    let mut txn = txn.clone();
+    let max_gas = txn.gas;
+    let mut best = execute_from_start(state, env, &txn)?;
+    if best.outcome != Outcome::Success {
+        return Ok((max_gas, best));
+    }
+
+    // The transaction can't succeed with less gas than it used, and always succeeds with the
+    // maximum. Anything in between depends on how much gas is passed on to other contracts.
+    let mut low = best.gas_used.saturating_sub(1);
+    let mut high = max_gas;
+    while low + 1 < high {
+        txn.gas = low + (high - low) / 2;
+        let result = execute_from_start(state, env, &txn)?;
+        if result.outcome == Outcome::Success {
+            high = txn.gas;
+            best = result;
+        } else {
+            low = txn.gas;
+        }
+    }
+
+    Ok((high, best))
+}
+
+/// Executes the transaction after reverting the changes of previous executions. The accounts
+/// read by previous executions stay cached.
+fn execute_from_start<B: Backend>(
+    state: &mut State<B>,
+    env: &BlockEnv,
+    txn: &Transaction,
+) -> Result<ExecutionResult, Error> {
+    state.revert_to(0);
+    Evm::new(state, env).transact(txn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::tracer::{CallTracer, StructLogger, StructLoggerConfig, MAX_STRUCT_LOGS};
+    use super::{
+        derive_address, estimate_gas, Address, Backend, BlockEnv, Error, Evm, Outcome, State,
+        Transaction,
+    };
+    use ethereum_types::U256;
+    use messages::block_info::{BlockInfo, BlockInfoConfig};
+    use messages::seth::{EvmEntry, EvmStateAccount};
+    use std::collections::HashMap;
+    use transform;
+
+    struct MemoryBackend(HashMap<Address, EvmEntry>);
+
+    impl Backend for MemoryBackend {
+        fn get_entry(&self, address: &Address) -> Result<Option<EvmEntry>, Error> {
+            Ok(self.0.get(address).cloned())
+        }
+
+        fn get_block_info_config(&self) -> Result<Option<BlockInfoConfig>, Error> {
+            Ok(None)
+        }
+
+        fn get_block_info(&self, _block_num: u64) -> Result<Option<BlockInfo>, Error> {
+            Ok(None)
+        }
+    }
+
+    fn sender() -> Address {
+        Address::from_low_u64_be(0x5e7)
+    }
+
+    fn new_state() -> State<MemoryBackend> {
+        let mut account = EvmStateAccount::new();
+        account.set_nonce(1);
+        let mut entry = EvmEntry::new();
+        entry.set_account(account);
+
+        let mut entries = HashMap::new();
+        entries.insert(sender(), entry);
+        State::new(MemoryBackend(entries))
+    }
+
+    fn make_txn(to: Option<Address>, code: &str) -> Transaction {
+        Transaction {
+            from: sender(),
+            to,
+            data: transform::hex_str_to_bytes(code).unwrap(),
+            gas: 1000,
+            gas_price: U256::zero(),
+            value: U256::zero(),
+        }
+    }
+
+    fn word(value: u8) -> Vec<u8> {
+        let mut word = vec![0; 32];
+        word[31] = value;
+        word
+    }
+
+    #[test]
+    fn create_and_call_contract() {
+        let mut state = new_state();
+        let env = BlockEnv::default();
+
+        // Stores 42 and deploys code that returns the stored value
+        let init = "602a600055600b6011600039600b6000f360005460005260206000f3";
+        let result = Evm::new(&mut state, &env)
+            .transact(&make_txn(None, init))
+            .unwrap();
+        let contract = derive_address(&sender(), 1);
+        assert_eq!(Outcome::Success, result.outcome);
+        assert_eq!(Some(contract), result.contract_address);
+        // Only the SSTORE is charged for by Burrow
+        assert_eq!(1, result.gas_used);
+
+        let result = Evm::new(&mut state, &env)
+            .transact(&make_txn(Some(contract), ""))
+            .unwrap();
+        assert_eq!(Outcome::Success, result.outcome);
+        assert_eq!(word(42), result.output);
+    }
+
+    #[test]
+    fn revert_and_fail() {
+        let mut state = new_state();
+        let env = BlockEnv::default();
+
+        // 2 + 3 is returned by the init code, reverted, or followed by an invalid jump
+        let code = "6002600301600052";
+        let result = Evm::new(&mut state, &env)
+            .transact(&make_txn(None, &format!("{}60206000f3", code)))
+            .unwrap();
+        assert_eq!(Outcome::Success, result.outcome);
+        assert_eq!(word(5), result.output);
+
+        let result = Evm::new(&mut state, &env)
+            .transact(&make_txn(None, &format!("{}60206000fd", code)))
+            .unwrap();
+        assert_eq!(Outcome::Revert, result.outcome);
+        assert_eq!(word(5), result.output);
+        assert_eq!(None, result.contract_address);
+
+        let result = Evm::new(&mut state, &env)
+            .transact(&make_txn(None, &format!("{}600056", code)))
+            .unwrap();
+        assert_eq!(Outcome::Error(super::VmError::InvalidJump), result.outcome);
+        assert_eq!(1000, result.gas_used);
+    }
+
+    #[test]
+    fn estimate_gas_for_call() {
+        let mut state = new_state();
+        let env = BlockEnv::default();
+
+        // Calls the identity precompile with 2048 bytes of input, which costs 65 gas, and fails
+        // if the call failed. Only 63/64 of the remaining gas is passed on, so 67 gas are needed
+        // even though only 66 are used.
+        let init = "6000600061080060006000600460fff1601457fe5b";
+        let (gas, result) = estimate_gas(&mut state, &env, &make_txn(None, init)).unwrap();
+        assert_eq!(Outcome::Success, result.outcome);
+        assert_eq!(66, result.gas_used);
+        assert_eq!(67, gas);
+
+        // Always reverts
+        let (gas, result) = estimate_gas(&mut state, &env, &make_txn(None, "60006000fd")).unwrap();
+        assert_eq!(Outcome::Revert, result.outcome);
+        assert_eq!(1000, gas);
+    }
+
+    #[test]
+    fn transfer_value() {
+        let mut state = new_state();
+        let env = BlockEnv::default();
+        state.set_balance(&sender(), U256::from(10)).unwrap();
+
+        // Returns the call value
+        let mut txn = make_txn(None, "3460005260206000f3");
+        txn.value = U256::from(7);
+        let result = Evm::new(&mut state, &env).transact(&txn).unwrap();
+        assert_eq!(Outcome::Success, result.outcome);
+        assert_eq!(word(7), result.output);
+        assert_eq!(U256::from(3), state.get_balance(&sender()).unwrap());
+
+        txn.value = U256::from(4);
+        let result = Evm::new(&mut state, &env).transact(&txn).unwrap();
+        assert_eq!(
+            Outcome::Error(super::VmError::InsufficientBalance),
+            result.outcome
+        );
+    }
+
+    #[test]
+    fn reject_malformed_balance() {
+        let mut account = EvmStateAccount::new();
+        account.set_balance(1);
+        account.set_balance_u256(vec![1; 33]);
+        let mut entry = EvmEntry::new();
+        entry.set_account(account);
+        let mut entries = HashMap::new();
+        entries.insert(sender(), entry);
+        let mut state = State::new(MemoryBackend(entries));
+
+        match state.get_balance(&sender()) {
+            Err(Error::StateError(_)) => (),
+            other => panic!("Expected a state error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn stop_code_that_loops() {
+        let mut state = new_state().with_step_limit(1000);
+        let env = BlockEnv::default();
+
+        // Jumps back to its start forever without using any gas
+        match Evm::new(&mut state, &env).transact(&make_txn(None, "5b600056")) {
+            Err(Error::StepLimitExceeded(1000)) => {}
+            result => panic!("Unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn limit_memory_of_all_frames() {
+        let mut state = new_state();
+        let env = BlockEnv::default();
+
+        // Stores init code that uses memory, fills the rest of the memory, and returns the
+        // address of the contract the init code creates, which is zero since no memory is left
+        let init = "646001600052600052600162ffffe0526005601b6000f060005260206000f3";
+        let result = Evm::new(&mut state, &env)
+            .transact(&make_txn(None, init))
+            .unwrap();
+        assert_eq!(Outcome::Success, result.outcome);
+        assert_eq!(word(0), result.output);
+    }
+
+    #[test]
+    fn trace_instructions_and_calls() {
+        let mut state = new_state();
+        let env = BlockEnv::default();
+
+        // Stores 42, then calls the identity precompile without any input
+        let init = "602a6000556000600060006000600460fffa";
+        let mut logger = StructLogger::new(StructLoggerConfig::default());
+        let result = Evm::new(&mut state, &env)
+            .with_tracer(&mut logger)
+            .transact(&make_txn(None, init))
+            .unwrap();
+        let trace = logger.into_trace(&result);
+        let logs = trace["structLogs"].as_array().unwrap();
+        assert_eq!(10, logs.len());
+        assert_eq!("SSTORE", logs[2]["op"]);
+        assert_eq!(1, logs[2]["gasCost"]);
+        assert_eq!(
+            format!("{:064x}", 42),
+            logs[2]["storage"][format!("{:064x}", 0)]
+        );
+        assert_eq!("STATICCALL", logs[9]["op"]);
+
+        let mut tracer = CallTracer::new();
+        Evm::new(&mut state, &env)
+            .with_tracer(&mut tracer)
+            .transact(&make_txn(None, init))
+            .unwrap();
+        let trace = tracer.into_trace();
+        assert_eq!("CREATE", trace["type"]);
+        assert_eq!("STATICCALL", trace["calls"][0]["type"]);
+        assert_eq!("0x1", trace["calls"][0]["gasUsed"]);
+    }
+
+    #[test]
+    fn truncate_long_traces() {
+        let mut state = new_state();
+        let env = BlockEnv::default();
+
+        // Counts down from 20000, executing 7 instructions each time
+        let code = "614e205b600190038060035700";
+        let mut logger = StructLogger::new(StructLoggerConfig {
+            disable_stack: true,
+            disable_memory: true,
+            disable_storage: true,
+        });
+        let result = Evm::new(&mut state, &env)
+            .with_tracer(&mut logger)
+            .transact(&make_txn(None, code))
+            .unwrap();
+        assert_eq!(Outcome::Success, result.outcome);
+        assert!(logger.truncated());
+        let trace = logger.into_trace(&result);
+        assert_eq!(
+            MAX_STRUCT_LOGS,
+            trace["structLogs"].as_array().unwrap().len()
+        );
+    }
+
+    #[test]
+    fn derive_contract_address() {
+        assert_eq!(sender(), derive_address(&sender(), 0));
+        assert_ne!(derive_address(&sender(), 1), derive_address(&sender(), 2));
+    }
+}
@@ -0,0 +1,314 @@
+/*
+ * Copyright 2019 Cargill Incorporated
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! The instructions supported by the EVM. This is the Constantinople instruction set without
+//! `CREATE2`, which the Burrow EVM used by the transaction processor doesn't support.
+
+pub const STOP: u8 = 0x00;
+pub const ADD: u8 = 0x01;
+pub const MUL: u8 = 0x02;
+pub const SUB: u8 = 0x03;
+pub const DIV: u8 = 0x04;
+pub const SDIV: u8 = 0x05;
+pub const MOD: u8 = 0x06;
+pub const SMOD: u8 = 0x07;
+pub const ADDMOD: u8 = 0x08;
+pub const MULMOD: u8 = 0x09;
+pub const EXP: u8 = 0x0a;
+pub const SIGNEXTEND: u8 = 0x0b;
+pub const LT: u8 = 0x10;
+pub const GT: u8 = 0x11;
+pub const SLT: u8 = 0x12;
+pub const SGT: u8 = 0x13;
+pub const EQ: u8 = 0x14;
+pub const ISZERO: u8 = 0x15;
+pub const AND: u8 = 0x16;
+pub const OR: u8 = 0x17;
+pub const XOR: u8 = 0x18;
+pub const NOT: u8 = 0x19;
+pub const BYTE: u8 = 0x1a;
+pub const SHL: u8 = 0x1b;
+pub const SHR: u8 = 0x1c;
+pub const SAR: u8 = 0x1d;
+pub const SHA3: u8 = 0x20;
+pub const ADDRESS: u8 = 0x30;
+pub const BALANCE: u8 = 0x31;
+pub const ORIGIN: u8 = 0x32;
+pub const CALLER: u8 = 0x33;
+pub const CALLVALUE: u8 = 0x34;
+pub const CALLDATALOAD: u8 = 0x35;
+pub const CALLDATASIZE: u8 = 0x36;
+pub const CALLDATACOPY: u8 = 0x37;
+pub const CODESIZE: u8 = 0x38;
+pub const CODECOPY: u8 = 0x39;
+pub const GASPRICE: u8 = 0x3a;
+pub const EXTCODESIZE: u8 = 0x3b;
+pub const EXTCODECOPY: u8 = 0x3c;
+pub const RETURNDATASIZE: u8 = 0x3d;
+pub const RETURNDATACOPY: u8 = 0x3e;
+pub const EXTCODEHASH: u8 = 0x3f;
+pub const BLOCKHASH: u8 = 0x40;
+pub const COINBASE: u8 = 0x41;
+pub const TIMESTAMP: u8 = 0x42;
+pub const NUMBER: u8 = 0x43;
+pub const DIFFICULTY: u8 = 0x44;
+pub const GASLIMIT: u8 = 0x45;
+pub const POP: u8 = 0x50;
+pub const MLOAD: u8 = 0x51;
+pub const MSTORE: u8 = 0x52;
+pub const MSTORE8: u8 = 0x53;
+pub const SLOAD: u8 = 0x54;
+pub const SSTORE: u8 = 0x55;
+pub const JUMP: u8 = 0x56;
+pub const JUMPI: u8 = 0x57;
+pub const PC: u8 = 0x58;
+pub const MSIZE: u8 = 0x59;
+pub const GAS: u8 = 0x5a;
+pub const JUMPDEST: u8 = 0x5b;
+pub const PUSH1: u8 = 0x60;
+pub const PUSH2: u8 = 0x61;
+pub const PUSH3: u8 = 0x62;
+pub const PUSH4: u8 = 0x63;
+pub const PUSH5: u8 = 0x64;
+pub const PUSH6: u8 = 0x65;
+pub const PUSH7: u8 = 0x66;
+pub const PUSH8: u8 = 0x67;
+pub const PUSH9: u8 = 0x68;
+pub const PUSH10: u8 = 0x69;
+pub const PUSH11: u8 = 0x6a;
+pub const PUSH12: u8 = 0x6b;
+pub const PUSH13: u8 = 0x6c;
+pub const PUSH14: u8 = 0x6d;
+pub const PUSH15: u8 = 0x6e;
+pub const PUSH16: u8 = 0x6f;
+pub const PUSH17: u8 = 0x70;
+pub const PUSH18: u8 = 0x71;
+pub const PUSH19: u8 = 0x72;
+pub const PUSH20: u8 = 0x73;
+pub const PUSH21: u8 = 0x74;
+pub const PUSH22: u8 = 0x75;
+pub const PUSH23: u8 = 0x76;
+pub const PUSH24: u8 = 0x77;
+pub const PUSH25: u8 = 0x78;
+pub const PUSH26: u8 = 0x79;
+pub const PUSH27: u8 = 0x7a;
+pub const PUSH28: u8 = 0x7b;
+pub const PUSH29: u8 = 0x7c;
+pub const PUSH30: u8 = 0x7d;
+pub const PUSH31: u8 = 0x7e;
+pub const PUSH32: u8 = 0x7f;
+pub const DUP1: u8 = 0x80;
+pub const DUP2: u8 = 0x81;
+pub const DUP3: u8 = 0x82;
+pub const DUP4: u8 = 0x83;
+pub const DUP5: u8 = 0x84;
+pub const DUP6: u8 = 0x85;
+pub const DUP7: u8 = 0x86;
+pub const DUP8: u8 = 0x87;
+pub const DUP9: u8 = 0x88;
+pub const DUP10: u8 = 0x89;
+pub const DUP11: u8 = 0x8a;
+pub const DUP12: u8 = 0x8b;
+pub const DUP13: u8 = 0x8c;
+pub const DUP14: u8 = 0x8d;
+pub const DUP15: u8 = 0x8e;
+pub const DUP16: u8 = 0x8f;
+pub const SWAP1: u8 = 0x90;
+pub const SWAP2: u8 = 0x91;
+pub const SWAP3: u8 = 0x92;
+pub const SWAP4: u8 = 0x93;
+pub const SWAP5: u8 = 0x94;
+pub const SWAP6: u8 = 0x95;
+pub const SWAP7: u8 = 0x96;
+pub const SWAP8: u8 = 0x97;
+pub const SWAP9: u8 = 0x98;
+pub const SWAP10: u8 = 0x99;
+pub const SWAP11: u8 = 0x9a;
+pub const SWAP12: u8 = 0x9b;
+pub const SWAP13: u8 = 0x9c;
+pub const SWAP14: u8 = 0x9d;
+pub const SWAP15: u8 = 0x9e;
+pub const SWAP16: u8 = 0x9f;
+pub const LOG0: u8 = 0xa0;
+pub const LOG1: u8 = 0xa1;
+pub const LOG2: u8 = 0xa2;
+pub const LOG3: u8 = 0xa3;
+pub const LOG4: u8 = 0xa4;
+pub const CREATE: u8 = 0xf0;
+pub const CALL: u8 = 0xf1;
+pub const CALLCODE: u8 = 0xf2;
+pub const RETURN: u8 = 0xf3;
+pub const DELEGATECALL: u8 = 0xf4;
+pub const STATICCALL: u8 = 0xfa;
+pub const REVERT: u8 = 0xfd;
+pub const INVALID: u8 = 0xfe;
+pub const SELFDESTRUCT: u8 = 0xff;
+
+/// Returns the number of bytes of immediate data following the instruction
+pub fn push_size(op: u8) -> usize {
+    if (PUSH1..=PUSH32).contains(&op) {
+        (op - PUSH1) as usize + 1
+    } else {
+        0
+    }
+}
+
+/// Returns the mnemonic of the instruction, if it is supported
+pub fn name(op: u8) -> Option<&'static str> {
+    match op {
+        STOP => Some("STOP"),
+        ADD => Some("ADD"),
+        MUL => Some("MUL"),
+        SUB => Some("SUB"),
+        DIV => Some("DIV"),
+        SDIV => Some("SDIV"),
+        MOD => Some("MOD"),
+        SMOD => Some("SMOD"),
+        ADDMOD => Some("ADDMOD"),
+        MULMOD => Some("MULMOD"),
+        EXP => Some("EXP"),
+        SIGNEXTEND => Some("SIGNEXTEND"),
+        LT => Some("LT"),
+        GT => Some("GT"),
+        SLT => Some("SLT"),
+        SGT => Some("SGT"),
+        EQ => Some("EQ"),
+        ISZERO => Some("ISZERO"),
+        AND => Some("AND"),
+        OR => Some("OR"),
+        XOR => Some("XOR"),
+        NOT => Some("NOT"),
+        BYTE => Some("BYTE"),
+        SHL => Some("SHL"),
+        SHR => Some("SHR"),
+        SAR => Some("SAR"),
+        SHA3 => Some("SHA3"),
+        ADDRESS => Some("ADDRESS"),
+        BALANCE => Some("BALANCE"),
+        ORIGIN => Some("ORIGIN"),
+        CALLER => Some("CALLER"),
+        CALLVALUE => Some("CALLVALUE"),
+        CALLDATALOAD => Some("CALLDATALOAD"),
+        CALLDATASIZE => Some("CALLDATASIZE"),
+        CALLDATACOPY => Some("CALLDATACOPY"),
+        CODESIZE => Some("CODESIZE"),
+        CODECOPY => Some("CODECOPY"),
+        GASPRICE => Some("GASPRICE"),
+        EXTCODESIZE => Some("EXTCODESIZE"),
+        EXTCODECOPY => Some("EXTCODECOPY"),
+        RETURNDATASIZE => Some("RETURNDATASIZE"),
+        RETURNDATACOPY => Some("RETURNDATACOPY"),
+        EXTCODEHASH => Some("EXTCODEHASH"),
+        BLOCKHASH => Some("BLOCKHASH"),
+        COINBASE => Some("COINBASE"),
+        TIMESTAMP => Some("TIMESTAMP"),
+        NUMBER => Some("NUMBER"),
+        DIFFICULTY => Some("DIFFICULTY"),
+        GASLIMIT => Some("GASLIMIT"),
+        POP => Some("POP"),
+        MLOAD => Some("MLOAD"),
+        MSTORE => Some("MSTORE"),
+        MSTORE8 => Some("MSTORE8"),
+        SLOAD => Some("SLOAD"),
+        SSTORE => Some("SSTORE"),
+        JUMP => Some("JUMP"),
+        JUMPI => Some("JUMPI"),
+        PC => Some("PC"),
+        MSIZE => Some("MSIZE"),
+        GAS => Some("GAS"),
+        JUMPDEST => Some("JUMPDEST"),
+        PUSH1 => Some("PUSH1"),
+        PUSH2 => Some("PUSH2"),
+        PUSH3 => Some("PUSH3"),
+        PUSH4 => Some("PUSH4"),
+        PUSH5 => Some("PUSH5"),
+        PUSH6 => Some("PUSH6"),
+        PUSH7 => Some("PUSH7"),
+        PUSH8 => Some("PUSH8"),
+        PUSH9 => Some("PUSH9"),
+        PUSH10 => Some("PUSH10"),
+        PUSH11 => Some("PUSH11"),
+        PUSH12 => Some("PUSH12"),
+        PUSH13 => Some("PUSH13"),
+        PUSH14 => Some("PUSH14"),
+        PUSH15 => Some("PUSH15"),
+        PUSH16 => Some("PUSH16"),
+        PUSH17 => Some("PUSH17"),
+        PUSH18 => Some("PUSH18"),
+        PUSH19 => Some("PUSH19"),
+        PUSH20 => Some("PUSH20"),
+        PUSH21 => Some("PUSH21"),
+        PUSH22 => Some("PUSH22"),
+        PUSH23 => Some("PUSH23"),
+        PUSH24 => Some("PUSH24"),
+        PUSH25 => Some("PUSH25"),
+        PUSH26 => Some("PUSH26"),
+        PUSH27 => Some("PUSH27"),
+        PUSH28 => Some("PUSH28"),
+        PUSH29 => Some("PUSH29"),
+        PUSH30 => Some("PUSH30"),
+        PUSH31 => Some("PUSH31"),
+        PUSH32 => Some("PUSH32"),
+        DUP1 => Some("DUP1"),
+        DUP2 => Some("DUP2"),
+        DUP3 => Some("DUP3"),
+        DUP4 => Some("DUP4"),
+        DUP5 => Some("DUP5"),
+        DUP6 => Some("DUP6"),
+        DUP7 => Some("DUP7"),
+        DUP8 => Some("DUP8"),
+        DUP9 => Some("DUP9"),
+        DUP10 => Some("DUP10"),
+        DUP11 => Some("DUP11"),
+        DUP12 => Some("DUP12"),
+        DUP13 => Some("DUP13"),
+        DUP14 => Some("DUP14"),
+        DUP15 => Some("DUP15"),
+        DUP16 => Some("DUP16"),
+        SWAP1 => Some("SWAP1"),
+        SWAP2 => Some("SWAP2"),
+        SWAP3 => Some("SWAP3"),
+        SWAP4 => Some("SWAP4"),
+        SWAP5 => Some("SWAP5"),
+        SWAP6 => Some("SWAP6"),
+        SWAP7 => Some("SWAP7"),
+        SWAP8 => Some("SWAP8"),
+        SWAP9 => Some("SWAP9"),
+        SWAP10 => Some("SWAP10"),
+        SWAP11 => Some("SWAP11"),
+        SWAP12 => Some("SWAP12"),
+        SWAP13 => Some("SWAP13"),
+        SWAP14 => Some("SWAP14"),
+        SWAP15 => Some("SWAP15"),
+        SWAP16 => Some("SWAP16"),
+        LOG0 => Some("LOG0"),
+        LOG1 => Some("LOG1"),
+        LOG2 => Some("LOG2"),
+        LOG3 => Some("LOG3"),
+        LOG4 => Some("LOG4"),
+        CREATE => Some("CREATE"),
+        CALL => Some("CALL"),
+        CALLCODE => Some("CALLCODE"),
+        RETURN => Some("RETURN"),
+        DELEGATECALL => Some("DELEGATECALL"),
+        STATICCALL => Some("STATICCALL"),
+        REVERT => Some("REVERT"),
+        INVALID => Some("INVALID"),
+        SELFDESTRUCT => Some("SELFDESTRUCT"),
+        _ => None,
+    }
+}
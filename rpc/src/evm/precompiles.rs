@@ -0,0 +1,128 @@
+/*
+ * Copyright 2019 Cargill Incorporated
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! The precompiled contracts at addresses 1 to 4
+
+use crypto::digest::Digest;
+use crypto::ripemd160::Ripemd160;
+use crypto::sha2::Sha256;
+use secp256k1::recovery::{RecoverableSignature, RecoveryId};
+use secp256k1::{Message, Secp256k1};
+use tiny_keccak;
+
+use super::gas;
+use super::Address;
+
+/// A precompiled contract, which returns its output or `None` if it ran out of gas
+pub type Precompile = fn(&[u8], &mut u64) -> Option<Vec<u8>>;
+
+/// Returns the precompiled contract at `address`, if there is one
+pub fn get_precompile(address: &Address) -> Option<Precompile> {
+    let bytes = address.as_bytes();
+    if bytes[..19].iter().any(|byte| *byte != 0) {
+        return None;
+    }
+    match bytes[19] {
+        1 => Some(ecrecover),
+        2 => Some(sha256),
+        3 => Some(ripemd160),
+        4 => Some(identity),
+        _ => None,
+    }
+}
+
+fn use_gas(gas: &mut u64, cost: u64) -> bool {
+    if *gas < cost {
+        false
+    } else {
+        *gas -= cost;
+        true
+    }
+}
+
+fn ecrecover(input: &[u8], gas: &mut u64) -> Option<Vec<u8>> {
+    if !use_gas(gas, gas::ECRECOVER) {
+        return None;
+    }
+
+    let mut data = [0u8; 128];
+    let len = input.len().min(128);
+    data[..len].copy_from_slice(&input[..len]);
+
+    // Invalid signatures aren't an error, they just don't produce any output
+    Some(
+        recover_address(&data[..32], &data[32..64], &data[64..128])
+            .map(|address| {
+                let mut output = vec![0u8; 12];
+                output.extend_from_slice(address.as_bytes());
+                output
+            })
+            .unwrap_or_else(Vec::new),
+    )
+}
+
+/// Recovers the address that signed the hash, given `v` as a 32 byte word and `r` and `s`
+pub fn recover_address(hash: &[u8], v: &[u8], rs: &[u8]) -> Option<Address> {
+    if v[..31].iter().any(|byte| *byte != 0) || (v[31] != 27 && v[31] != 28) {
+        return None;
+    }
+    let recovery_id = RecoveryId::from_i32(i32::from(v[31] - 27)).ok()?;
+    let signature = RecoverableSignature::from_compact(rs, recovery_id).ok()?;
+    let message = Message::from_slice(hash).ok()?;
+    let public_key = Secp256k1::verification_only()
+        .recover(&message, &signature)
+        .ok()?;
+
+    let public_key = public_key.serialize_uncompressed();
+    let hash = tiny_keccak::keccak256(&public_key[1..]);
+    Some(Address::from_slice(&hash[12..]))
+}
+
+fn sha256(input: &[u8], gas: &mut u64) -> Option<Vec<u8>> {
+    let cost = gas::precompile_cost(input.len(), gas::SHA256_WORD, gas::SHA256_BASE);
+    if !use_gas(gas, cost) {
+        return None;
+    }
+
+    let mut hasher = Sha256::new();
+    hasher.input(input);
+    let mut output = vec![0u8; 32];
+    hasher.result(&mut output);
+    Some(output)
+}
+
+fn ripemd160(input: &[u8], gas: &mut u64) -> Option<Vec<u8>> {
+    let cost = gas::precompile_cost(input.len(), gas::RIPEMD160_WORD, gas::RIPEMD160_BASE);
+    if !use_gas(gas, cost) {
+        return None;
+    }
+
+    let mut hasher = Ripemd160::new();
+    hasher.input(input);
+    let mut output = vec![0u8; 32];
+    hasher.result(&mut output[12..]);
+    Some(output)
+}
+
+fn identity(input: &[u8], gas: &mut u64) -> Option<Vec<u8>> {
+    let cost = gas::precompile_cost(input.len(), gas::IDENTITY_WORD, gas::IDENTITY_BASE);
+    if !use_gas(gas, cost) {
+        return None;
+    }
+
+    Some(input.to_vec())
+}
@@ -0,0 +1,290 @@
+/*
+ * Copyright 2019 Cargill Incorporated
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! The state the EVM executes against. Accounts are read lazily from the backend and every
+//! change is kept in memory, so that nothing is ever written back to the validator.
+
+use ethereum_types::{H256, U256};
+use messages::block_info::{BlockInfo, BlockInfoConfig};
+use messages::seth::EvmEntry;
+use std::collections::HashMap;
+use std::rc::Rc;
+use transform;
+
+use super::{Address, Error};
+
+/// Where the EVM reads Seth state from
+pub trait Backend {
+    /// Returns the Seth entry stored at the account address
+    fn get_entry(&self, address: &Address) -> Result<Option<EvmEntry>, Error>;
+
+    /// Returns the block info config, which points to the latest block in state
+    fn get_block_info_config(&self) -> Result<Option<BlockInfoConfig>, Error>;
+
+    /// Returns the block info of the given block, if it is still stored in state
+    fn get_block_info(&self, block_num: u64) -> Result<Option<BlockInfo>, Error>;
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Account {
+    pub nonce: u64,
+    pub balance: U256,
+    pub code: Rc<Vec<u8>>,
+    pub storage: HashMap<U256, U256>,
+}
+
+impl Account {
+    fn from_entry(mut entry: EvmEntry) -> Result<Self, Error> {
+        let mut account = entry.take_account();
+        let storage = entry
+            .take_storage()
+            .into_iter()
+            .filter(|storage| storage.key.len() <= 32 && storage.value.len() <= 32)
+            .map(|storage| {
+                (
+                    U256::from_big_endian(&storage.key),
+                    U256::from_big_endian(&storage.value),
+                )
+            })
+            .collect();
+
+        let balance =
+            transform::u256_from_fields(&account.balance_u256, account.balance.max(0) as u64)
+                .ok_or_else(|| Error::StateError(String::from("Malformed account balance")))?;
+        Ok(Account {
+            nonce: account.nonce,
+            balance,
+            code: Rc::new(account.take_code()),
+            storage,
+        })
+    }
+}
+
+/// The previous value of something that was changed, so that the change can be reverted
+enum Change {
+    Account(Address, Option<Account>),
+    Nonce(Address, u64),
+    Balance(Address, U256),
+    Code(Address, Rc<Vec<u8>>),
+    Storage(Address, U256, Option<U256>),
+}
+
+/// How many instructions may be executed against a state in total, by every execution of a
+/// request. Burrow charges no gas for most instructions, so gas alone doesn't stop code that
+/// loops.
+pub const MAX_STEPS: u64 = 10_000_000;
+
+pub struct State<B: Backend> {
+    backend: B,
+    accounts: HashMap<Address, Option<Account>>,
+    journal: Vec<Change>,
+    step_limit: u64,
+    steps_left: u64,
+}
+
+impl<B: Backend> State<B> {
+    pub fn new(backend: B) -> Self {
+        State {
+            backend,
+            accounts: HashMap::new(),
+            journal: Vec::new(),
+            step_limit: MAX_STEPS,
+            steps_left: MAX_STEPS,
+        }
+    }
+
+    /// Allows a different number of instructions than `MAX_STEPS`
+    pub fn with_step_limit(mut self, limit: u64) -> Self {
+        self.step_limit = limit;
+        self.steps_left = limit;
+        self
+    }
+
+    /// Counts an executed instruction, failing once the limit is used up. The count isn't reset
+    /// by `revert_to`, so that repeated executions share the limit.
+    pub fn use_step(&mut self) -> Result<(), Error> {
+        if self.steps_left == 0 {
+            return Err(Error::StepLimitExceeded(self.step_limit));
+        }
+        self.steps_left -= 1;
+        Ok(())
+    }
+
+    fn load(&mut self, address: &Address) -> Result<&mut Option<Account>, Error> {
+        if !self.accounts.contains_key(address) {
+            let account = match self.backend.get_entry(address)? {
+                Some(entry) => Some(Account::from_entry(entry)?),
+                None => None,
+            };
+            self.accounts.insert(*address, account);
+        }
+        Ok(self.accounts.get_mut(address).unwrap())
+    }
+
+    /// Returns the account, creating an empty one if it doesn't exist yet
+    fn load_or_create(&mut self, address: &Address) -> Result<&mut Account, Error> {
+        if self.load(address)?.is_none() {
+            self.journal.push(Change::Account(*address, None));
+        }
+        Ok(self.load(address)?.get_or_insert_with(Account::default))
+    }
+
+    pub fn exists(&mut self, address: &Address) -> Result<bool, Error> {
+        self.load(address).map(|account| account.is_some())
+    }
+
+    pub fn get_nonce(&mut self, address: &Address) -> Result<u64, Error> {
+        self.load(address)
+            .map(|account| account.as_ref().map(|a| a.nonce).unwrap_or(0))
+    }
+
+    pub fn get_balance(&mut self, address: &Address) -> Result<U256, Error> {
+        self.load(address)
+            .map(|account| account.as_ref().map(|a| a.balance).unwrap_or_default())
+    }
+
+    pub fn get_code(&mut self, address: &Address) -> Result<Rc<Vec<u8>>, Error> {
+        self.load(address)
+            .map(|account| account.as_ref().map(|a| a.code.clone()).unwrap_or_default())
+    }
+
+    pub fn get_storage(&mut self, address: &Address, key: &U256) -> Result<U256, Error> {
+        self.load(address).map(|account| {
+            account
+                .as_ref()
+                .and_then(|a| a.storage.get(key).cloned())
+                .unwrap_or_default()
+        })
+    }
+
+    /// Creates a new, empty account, replacing the account at the address if there is one
+    pub fn create_account(&mut self, address: &Address) -> Result<(), Error> {
+        let previous = self.load(address)?.replace(Account::default());
+        self.journal.push(Change::Account(*address, previous));
+        Ok(())
+    }
+
+    pub fn remove_account(&mut self, address: &Address) -> Result<(), Error> {
+        let previous = self.load(address)?.take();
+        self.journal.push(Change::Account(*address, previous));
+        Ok(())
+    }
+
+    pub fn set_nonce(&mut self, address: &Address, nonce: u64) -> Result<(), Error> {
+        let account = self.load_or_create(address)?;
+        let previous = account.nonce;
+        account.nonce = nonce;
+        self.journal.push(Change::Nonce(*address, previous));
+        Ok(())
+    }
+
+    pub fn set_balance(&mut self, address: &Address, balance: U256) -> Result<(), Error> {
+        let account = self.load_or_create(address)?;
+        let previous = account.balance;
+        account.balance = balance;
+        self.journal.push(Change::Balance(*address, previous));
+        Ok(())
+    }
+
+    pub fn set_code(&mut self, address: &Address, code: Vec<u8>) -> Result<(), Error> {
+        let account = self.load_or_create(address)?;
+        let previous = ::std::mem::replace(&mut account.code, Rc::new(code));
+        self.journal.push(Change::Code(*address, previous));
+        Ok(())
+    }
+
+    pub fn set_storage(&mut self, address: &Address, key: U256, value: U256) -> Result<(), Error> {
+        let account = self.load_or_create(address)?;
+        let previous = account.storage.insert(key, value);
+        self.journal.push(Change::Storage(*address, key, previous));
+        Ok(())
+    }
+
+    /// Moves `value` from one account to another. Returns false if the sender can't afford it.
+    pub fn transfer(&mut self, from: &Address, to: &Address, value: U256) -> Result<bool, Error> {
+        if value.is_zero() {
+            return Ok(true);
+        }
+        let from_balance = self.get_balance(from)?;
+        if from_balance < value {
+            return Ok(false);
+        }
+        self.set_balance(from, from_balance - value)?;
+        let to_balance = self.get_balance(to)?;
+        self.set_balance(to, to_balance.overflowing_add(value).0)?;
+        Ok(true)
+    }
+
+    /// Returns a checkpoint that the state can be reverted to
+    pub fn checkpoint(&self) -> usize {
+        self.journal.len()
+    }
+
+    /// Reverts every change made since the checkpoint was taken
+    pub fn revert_to(&mut self, checkpoint: usize) {
+        while self.journal.len() > checkpoint {
+            match self.journal.pop().unwrap() {
+                Change::Account(address, previous) => {
+                    self.accounts.insert(address, previous);
+                }
+                Change::Nonce(address, nonce) => {
+                    if let Some(Some(account)) = self.accounts.get_mut(&address) {
+                        account.nonce = nonce;
+                    }
+                }
+                Change::Balance(address, balance) => {
+                    if let Some(Some(account)) = self.accounts.get_mut(&address) {
+                        account.balance = balance;
+                    }
+                }
+                Change::Code(address, code) => {
+                    if let Some(Some(account)) = self.accounts.get_mut(&address) {
+                        account.code = code;
+                    }
+                }
+                Change::Storage(address, key, value) => {
+                    if let Some(Some(account)) = self.accounts.get_mut(&address) {
+                        match value {
+                            Some(value) => account.storage.insert(key, value),
+                            None => account.storage.remove(&key),
+                        };
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns the hash of one of the 256 blocks before `current`, or zero for other blocks
+    pub fn get_block_hash(&self, current: u64, block_num: u64) -> Result<H256, Error> {
+        if block_num >= current || current - block_num > 256 {
+            return Ok(H256::zero());
+        }
+        self.backend
+            .get_block_info(block_num)
+            .map(|info| info.map(|info| block_hash(&info)).unwrap_or_default())
+    }
+}
+
+/// Returns the hash the processor gives the EVM for a block. Sawtooth block ids are 64 bytes,
+/// so only the first 32 are used.
+pub fn block_hash(info: &BlockInfo) -> H256 {
+    let mut hash = H256::zero();
+    let id = transform::hex_str_to_bytes(&info.header_signature).unwrap_or_default();
+    let len = id.len().min(32);
+    hash.as_bytes_mut()[..len].copy_from_slice(&id[..len]);
+    hash
+}
@@ -0,0 +1,316 @@
+/*
+ * Copyright 2019 Cargill Incorporated
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! Tracers that observe the EVM as it executes. Their output follows the formats of geth's
+//! struct logger and call tracer, so that existing tooling can read it.
+
+use ethereum_types::U256;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use transform;
+
+use super::opcodes;
+use super::{Address, ExecutionResult, Outcome};
+
+/// The state of the interpreter before an instruction is executed
+pub struct Step<'a> {
+    pub pc: usize,
+    pub op: u8,
+    /// The gas left before the instruction is charged for
+    pub gas: u64,
+    pub gas_cost: u64,
+    /// The call depth, which is 1 for the code called by the transaction
+    pub depth: usize,
+    pub address: &'a Address,
+    pub stack: &'a [U256],
+    pub memory: &'a [u8],
+    /// The key and value of the storage slot read by `SLOAD` or written by `SSTORE`
+    pub storage: Option<(U256, U256)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CallKind {
+    Call,
+    CallCode,
+    DelegateCall,
+    StaticCall,
+    Create,
+}
+
+impl CallKind {
+    fn name(self) -> &'static str {
+        match self {
+            CallKind::Call => "CALL",
+            CallKind::CallCode => "CALLCODE",
+            CallKind::DelegateCall => "DELEGATECALL",
+            CallKind::StaticCall => "STATICCALL",
+            CallKind::Create => "CREATE",
+        }
+    }
+}
+
+/// A call or contract creation, as seen by the calling contract
+pub struct Call<'a> {
+    pub kind: CallKind,
+    pub from: Address,
+    /// The called contract, or the created one
+    pub to: Address,
+    pub input: &'a [u8],
+    pub gas: u64,
+    pub value: U256,
+}
+
+pub trait Tracer {
+    /// Called before every instruction
+    fn step(&mut self, _step: &Step) {}
+
+    /// Called when a call or contract creation starts, including the transaction itself
+    fn enter(&mut self, _call: &Call) {}
+
+    /// Called when the most recently entered call or contract creation finishes
+    fn exit(&mut self, _outcome: &Outcome, _output: &[u8], _gas_used: u64) {}
+}
+
+/// Formats a word the way geth's struct logger does, without a prefix
+fn word_to_hex(value: &U256) -> String {
+    let mut bytes = [0u8; 32];
+    value.to_big_endian(&mut bytes);
+    transform::bytes_to_hex_str(&bytes)
+}
+
+fn bytes_to_hex(bytes: &[u8]) -> Value {
+    transform::hex_prefix(&transform::bytes_to_hex_str(bytes))
+}
+
+/// The most instructions the struct logger records
+pub const MAX_STRUCT_LOGS: usize = 100_000;
+
+/// The most bytes of stack, memory and storage the struct logger captures, counted as hex digits
+pub const MAX_CAPTURED_BYTES: usize = 64 * 1024 * 1024;
+
+#[derive(Debug, Clone, Default)]
+pub struct StructLoggerConfig {
+    pub disable_stack: bool,
+    pub disable_memory: bool,
+    pub disable_storage: bool,
+}
+
+/// Records every executed instruction, until `MAX_STRUCT_LOGS` instructions or
+/// `MAX_CAPTURED_BYTES` bytes have been recorded
+pub struct StructLogger {
+    config: StructLoggerConfig,
+    logs: Vec<Map<String, Value>>,
+    /// The storage slots each contract has accessed so far
+    storage: HashMap<Address, Map<String, Value>>,
+    depth: usize,
+    captured: usize,
+    truncated: bool,
+}
+
+impl StructLogger {
+    pub fn new(config: StructLoggerConfig) -> Self {
+        StructLogger {
+            config,
+            logs: Vec::new(),
+            storage: HashMap::new(),
+            depth: 0,
+            captured: 0,
+            truncated: false,
+        }
+    }
+
+    /// Returns true if instructions went unrecorded because the trace reached its limits
+    pub fn truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Returns the number of bytes the instruction would add to the trace
+    fn size_of(&self, step: &Step) -> usize {
+        let mut size = 0;
+        if !self.config.disable_stack {
+            size += step.stack.len() * 64;
+        }
+        if !self.config.disable_memory {
+            size += step.memory.len() * 2;
+        }
+        if !self.config.disable_storage && step.storage.is_some() {
+            let slots = self.storage.get(step.address).map_or(0, Map::len);
+            size += (slots + 1) * 128;
+        }
+        size
+    }
+
+    /// Returns the trace of the transaction, given the result of executing it
+    pub fn into_trace(self, result: &ExecutionResult) -> Value {
+        let mut trace = Map::new();
+        trace.insert(String::from("gas"), Value::from(result.gas_used));
+        trace.insert(
+            String::from("failed"),
+            Value::Bool(result.outcome != Outcome::Success),
+        );
+        trace.insert(
+            String::from("returnValue"),
+            Value::String(transform::bytes_to_hex_str(&result.output)),
+        );
+        trace.insert(
+            String::from("structLogs"),
+            Value::Array(self.logs.into_iter().map(Value::Object).collect()),
+        );
+        Value::Object(trace)
+    }
+}
+
+impl Tracer for StructLogger {
+    fn step(&mut self, step: &Step) {
+        if self.truncated {
+            return;
+        }
+        let size = self.size_of(step);
+        if self.logs.len() >= MAX_STRUCT_LOGS || self.captured + size > MAX_CAPTURED_BYTES {
+            self.truncated = true;
+            return;
+        }
+        self.captured += size;
+
+        let mut log = Map::new();
+        log.insert(String::from("pc"), Value::from(step.pc));
+        log.insert(
+            String::from("op"),
+            match opcodes::name(step.op) {
+                Some(name) => Value::String(String::from(name)),
+                None => Value::String(format!("opcode {:#04x} not defined", step.op)),
+            },
+        );
+        log.insert(String::from("gas"), Value::from(step.gas));
+        log.insert(String::from("gasCost"), Value::from(step.gas_cost));
+        log.insert(String::from("depth"), Value::from(step.depth));
+
+        if !self.config.disable_stack {
+            let stack: Vec<Value> = step
+                .stack
+                .iter()
+                .map(|value| Value::String(word_to_hex(value)))
+                .collect();
+            log.insert(String::from("stack"), Value::Array(stack));
+        }
+        if !self.config.disable_memory {
+            let memory: Vec<Value> = step
+                .memory
+                .chunks(32)
+                .map(|chunk| Value::String(transform::bytes_to_hex_str(chunk)))
+                .collect();
+            log.insert(String::from("memory"), Value::Array(memory));
+        }
+        if let (false, Some((key, value))) = (self.config.disable_storage, step.storage) {
+            let storage = self.storage.entry(*step.address).or_insert_with(Map::new);
+            storage.insert(word_to_hex(&key), Value::String(word_to_hex(&value)));
+            log.insert(String::from("storage"), Value::Object(storage.clone()));
+        }
+
+        self.logs.push(log);
+    }
+
+    fn enter(&mut self, _call: &Call) {
+        self.depth += 1;
+    }
+
+    fn exit(&mut self, outcome: &Outcome, _output: &[u8], _gas_used: u64) {
+        // The instruction that failed is the last one executed at this depth, unless the trace
+        // was truncated before it
+        if let (&Outcome::Error(ref error), Some(log)) = (outcome, self.logs.last_mut()) {
+            if !self.truncated && log.get("depth") == Some(&Value::from(self.depth)) {
+                log.insert(String::from("error"), Value::String(format!("{}", error)));
+            }
+        }
+        self.depth -= 1;
+    }
+}
+
+/// Records the tree of calls and contract creations made by the transaction
+#[derive(Default)]
+pub struct CallTracer {
+    calls: Vec<Map<String, Value>>,
+    trace: Value,
+}
+
+impl CallTracer {
+    pub fn new() -> Self {
+        CallTracer::default()
+    }
+
+    /// Returns the call made by the transaction, with the calls it made nested under `calls`
+    pub fn into_trace(self) -> Value {
+        self.trace
+    }
+}
+
+impl Tracer for CallTracer {
+    fn enter(&mut self, call: &Call) {
+        let mut frame = Map::new();
+        frame.insert(
+            String::from("type"),
+            Value::String(String::from(call.kind.name())),
+        );
+        frame.insert(String::from("from"), bytes_to_hex(call.from.as_bytes()));
+        frame.insert(String::from("to"), bytes_to_hex(call.to.as_bytes()));
+        match call.kind {
+            CallKind::DelegateCall | CallKind::StaticCall => {}
+            _ => {
+                frame.insert(String::from("value"), transform::num_to_hex(&call.value));
+            }
+        }
+        frame.insert(String::from("gas"), transform::num_to_hex(&call.gas));
+        frame.insert(String::from("input"), bytes_to_hex(call.input));
+        self.calls.push(frame);
+    }
+
+    fn exit(&mut self, outcome: &Outcome, output: &[u8], gas_used: u64) {
+        let mut frame = match self.calls.pop() {
+            Some(frame) => frame,
+            None => return,
+        };
+        frame.insert(String::from("gasUsed"), transform::num_to_hex(&gas_used));
+        // Like geth, keep the output of reverted calls, which holds the revert reason
+        match *outcome {
+            Outcome::Success => {
+                frame.insert(String::from("output"), bytes_to_hex(output));
+            }
+            Outcome::Revert => {
+                frame.insert(String::from("output"), bytes_to_hex(output));
+                frame.insert(
+                    String::from("error"),
+                    Value::String(String::from("execution reverted")),
+                );
+            }
+            Outcome::Error(ref error) => {
+                frame.insert(String::from("error"), Value::String(format!("{}", error)));
+            }
+        }
+
+        match self.calls.last_mut() {
+            Some(parent) => {
+                if let Value::Array(ref mut calls) = *parent
+                    .entry(String::from("calls"))
+                    .or_insert_with(|| Value::Array(Vec::new()))
+                {
+                    calls.push(Value::Object(frame));
+                }
+            }
+            None => self.trace = Value::Object(frame),
+        }
+    }
+}
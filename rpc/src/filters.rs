@@ -22,6 +22,7 @@ use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use transactions::SethLog;
 use transform;
@@ -182,10 +183,18 @@ pub fn filter_id_to_hex(f: FilterId) -> String {
     format!("{:x}", f)
 }
 
+/// Filters that haven't been polled for this long are uninstalled
+const FILTER_TIMEOUT: Duration = Duration::from_secs(300);
+
 #[derive(Debug, Clone)]
 pub struct FilterEntry {
     pub filter: Filter,
-    pub last_block_sent: u64,
+    /// Changes that haven't been returned by `eth_getFilterChanges` yet
+    pub changes: Vec<Value>,
+    /// The first error from getting the changes of a block since the filter was last polled,
+    /// which means that some changes are missing
+    pub error: Option<RpcError>,
+    last_polled: Instant,
 }
 
 #[derive(Debug, Clone)]
@@ -202,9 +211,9 @@ impl FilterManager {
         }
     }
 
-    pub fn new_filter(&self, filter: Filter, block_num: u64) -> FilterId {
+    pub fn new_filter(&self, filter: Filter) -> FilterId {
         let filter_id = self.id_ctr.fetch_add(1, Ordering::SeqCst);
-        self.set_filter(filter_id, filter, block_num);
+        self.set_filter(filter_id, filter);
         filter_id
     }
 
@@ -216,24 +225,61 @@ impl FilterManager {
         self.filters.lock().unwrap().get(&filter_id).cloned()
     }
 
-    pub fn update_latest_block(&self, filter_id: FilterId, block_num: u64) -> bool {
+    /// Returns the number of installed filters
+    pub fn count(&self) -> usize {
+        self.filters.lock().unwrap().len()
+    }
+
+    /// Returns the installed filters
+    pub fn get_filters(&self) -> Vec<Filter> {
+        self.filters
+            .lock()
+            .unwrap()
+            .values()
+            .map(|entry| entry.filter.clone())
+            .collect()
+    }
+
+    /// Removes and returns the changes buffered for the filter since it was last polled. If some
+    /// changes were missed, the error is returned instead, and the changes on the next poll.
+    pub fn take_changes(&self, filter_id: FilterId) -> Option<Result<Vec<Value>, RpcError>> {
         if let Entry::Occupied(mut entry) = self.filters.lock().unwrap().entry(filter_id) {
-            (*entry.get_mut()).last_block_sent = block_num;
-            true
+            let entry = entry.get_mut();
+            entry.last_polled = Instant::now();
+            Some(match entry.error.take() {
+                Some(error) => Err(error),
+                None => Ok(entry.changes.drain(..).collect()),
+            })
         } else {
-            false
+            None
         }
     }
 
-    pub fn set_filter(
-        &self,
-        filter_id: FilterId,
-        filter: Filter,
-        block_num: u64,
-    ) -> Option<FilterEntry> {
+    /// Appends the changes returned by `get_changes` to the buffer of each filter, or records the
+    /// error it returned. Filters that haven't been polled recently are uninstalled instead, so
+    /// that their buffers don't grow forever.
+    pub fn push_changes<F>(&self, get_changes: F)
+    where
+        F: Fn(&Filter) -> Result<Vec<Value>, RpcError>,
+    {
+        let mut filters = self.filters.lock().unwrap();
+        filters.retain(|_, entry| entry.last_polled.elapsed() < FILTER_TIMEOUT);
+        for entry in filters.values_mut() {
+            match get_changes(&entry.filter) {
+                Ok(changes) => entry.changes.extend(changes.into_iter()),
+                Err(error) => {
+                    entry.error.get_or_insert(error);
+                }
+            }
+        }
+    }
+
+    pub fn set_filter(&self, filter_id: FilterId, filter: Filter) -> Option<FilterEntry> {
         let filter_entry = FilterEntry {
             filter,
-            last_block_sent: block_num,
+            changes: Vec::new(),
+            error: None,
+            last_polled: Instant::now(),
         };
         self.filters.lock().unwrap().insert(filter_id, filter_entry)
     }
@@ -248,8 +294,8 @@ pub enum Filter {
 
 #[cfg(test)]
 mod tests {
-    use super::TopicFilter;
-    use jsonrpc_core::Value;
+    use super::{Filter, FilterManager, TopicFilter};
+    use jsonrpc_core::{Error as RpcError, Value};
 
     #[test]
     fn parse_topics() {
@@ -272,4 +318,54 @@ mod tests {
             .unwrap()
         );
     }
+
+    #[test]
+    fn buffer_filter_changes() {
+        let manager = FilterManager::new();
+        let block_filter = manager.new_filter(Filter::Block);
+        let txn_filter = manager.new_filter(Filter::Transaction);
+
+        manager.push_changes(|filter| match *filter {
+            Filter::Block => Ok(vec![Value::String(String::from("0x1"))]),
+            _ => Ok(vec![]),
+        });
+        manager.push_changes(|filter| match *filter {
+            Filter::Block => Ok(vec![Value::String(String::from("0x2"))]),
+            _ => Ok(vec![Value::String(String::from("0x3"))]),
+        });
+
+        assert_eq!(
+            Some(Ok(vec![
+                Value::String(String::from("0x1")),
+                Value::String(String::from("0x2")),
+            ])),
+            manager.take_changes(block_filter)
+        );
+        assert_eq!(Some(Ok(vec![])), manager.take_changes(block_filter));
+        assert_eq!(
+            Some(Ok(vec![Value::String(String::from("0x3"))])),
+            manager.take_changes(txn_filter)
+        );
+
+        manager.remove_filter(txn_filter);
+        assert_eq!(None, manager.take_changes(txn_filter));
+    }
+
+    #[test]
+    fn report_missed_changes() {
+        let manager = FilterManager::new();
+        let block_filter = manager.new_filter(Filter::Block);
+
+        manager.push_changes(|_| Err(RpcError::internal_error()));
+        manager.push_changes(|_| Ok(vec![Value::String(String::from("0x1"))]));
+
+        assert_eq!(
+            Some(Err(RpcError::internal_error())),
+            manager.take_changes(block_filter)
+        );
+        assert_eq!(
+            Some(Ok(vec![Value::String(String::from("0x1"))])),
+            manager.take_changes(block_filter)
+        );
+    }
 }
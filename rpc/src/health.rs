@@ -0,0 +1,206 @@
+/*
+ * Copyright 2019 Cargill Incorporated
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! Checks whether seth-rpc can serve requests, for the `/ready` endpoint of the HTTP server.
+//!
+//! seth-rpc is ready when the validator answers and has a head block. Sawtooth blocks have no
+//! timestamp, so the age of the head is the time since seth-rpc first saw it.
+//!
+//! The endpoint is served by the threads of the HTTP server, which mustn't wait for the validator,
+//! so it reports the result of the last check and starts the next one in the background.
+
+use client::{Error, ValidatorClient};
+use protobuf;
+use sawtooth_sdk::messages::block::BlockHeader;
+use sawtooth_sdk::messaging::stream::MessageSender;
+use serde_json::{Map, Value};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The head block, and when it was first seen
+struct Head {
+    number: u64,
+    since: Instant,
+}
+
+struct State {
+    /// The head block, as of the last successful check
+    head: Option<Head>,
+    /// Why the last check failed
+    error: Option<String>,
+    /// Whether a check is running
+    checking: bool,
+}
+
+#[derive(Clone)]
+pub struct ReadinessCheck {
+    /// How long the head may go without advancing before seth-rpc isn't ready, if set
+    max_head_age: Option<Duration>,
+    state: Arc<Mutex<State>>,
+}
+
+impl ReadinessCheck {
+    pub fn new(max_head_age: Option<Duration>) -> Self {
+        ReadinessCheck {
+            max_head_age,
+            state: Arc::new(Mutex::new(State {
+                head: None,
+                error: Some(String::from("Not checked yet")),
+                checking: false,
+            })),
+        }
+    }
+
+    /// Returns whether seth-rpc was ready at the last check, and a report of the head block
+    /// number and age, or of why it isn't ready. Starts another check unless one is running.
+    pub fn report<S>(&self, client: &ValidatorClient<S>) -> (bool, Value)
+    where
+        S: MessageSender + Clone + Send + Sync + 'static,
+    {
+        self.start_check(client);
+        self.report_at(Instant::now())
+    }
+
+    /// Asks the validator for the head block in the background
+    fn start_check<S>(&self, client: &ValidatorClient<S>)
+    where
+        S: MessageSender + Clone + Send + Sync + 'static,
+    {
+        {
+            let mut state = self.state.lock().unwrap();
+            if state.checking {
+                return;
+            }
+            state.checking = true;
+        }
+        let check = self.clone();
+        let client = client.clone();
+        thread::spawn(move || check.record(get_head_number(&client), Instant::now()));
+    }
+
+    /// Records the result of a check, and when the head block changed
+    fn record(&self, result: Result<u64, String>, now: Instant) {
+        let mut state = self.state.lock().unwrap();
+        state.checking = false;
+        match result {
+            Ok(number) => {
+                state.error = None;
+                match state.head {
+                    Some(ref head) if head.number == number => (),
+                    _ => state.head = Some(Head { number, since: now }),
+                }
+            }
+            Err(reason) => {
+                warn!("Not ready: {}", reason);
+                state.error = Some(reason);
+            }
+        }
+    }
+
+    fn report_at(&self, now: Instant) -> (bool, Value) {
+        let state = self.state.lock().unwrap();
+        let mut report = Map::new();
+        let head = match (&state.error, &state.head) {
+            (&None, &Some(ref head)) => head,
+            (error, _) => {
+                let reason = error.clone().unwrap_or_default();
+                report.insert(String::from("ready"), Value::Bool(false));
+                report.insert(String::from("reason"), Value::String(reason));
+                return (false, Value::Object(report));
+            }
+        };
+
+        let age = now.duration_since(head.since);
+        let ready = self.max_head_age.map_or(true, |max_age| age <= max_age);
+        report.insert(String::from("ready"), Value::Bool(ready));
+        report.insert(String::from("head_block_number"), Value::from(head.number));
+        report.insert(String::from("head_age_seconds"), Value::from(age.as_secs()));
+        if !ready {
+            warn!("Not ready: head block {} is {:?} old", head.number, age);
+            report.insert(
+                String::from("reason"),
+                Value::from("The head block hasn't advanced"),
+            );
+        }
+        (ready, Value::Object(report))
+    }
+}
+
+/// Gets the number of the head block
+fn get_head_number<S: MessageSender>(client: &ValidatorClient<S>) -> Result<u64, String> {
+    let block = client.get_current_block().map_err(|error| match error {
+        Error::NoResource => String::from("The validator has no blocks"),
+        error => format!("Failed to get the head block: {}", error),
+    })?;
+    let header: BlockHeader = protobuf::parse_from_bytes(&block.header)
+        .map_err(|error| format!("Invalid block header: {:?}", error))?;
+    Ok(header.block_num)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ReadinessCheck;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn report_head_age() {
+        let check = ReadinessCheck::new(Some(Duration::from_secs(60)));
+        let start = Instant::now();
+        assert!(!check.report_at(start).0);
+
+        check.record(Ok(5), start);
+        let (ready, report) = check.report_at(start);
+        assert!(ready);
+        assert_eq!(5, report["head_block_number"]);
+        assert_eq!(0, report["head_age_seconds"]);
+
+        check.record(Ok(5), start + Duration::from_secs(30));
+        let (ready, report) = check.report_at(start + Duration::from_secs(30));
+        assert!(ready);
+        assert_eq!(30, report["head_age_seconds"]);
+
+        // The head hasn't advanced for too long
+        assert!(!check.report_at(start + Duration::from_secs(61)).0);
+
+        // A new head is ready again
+        check.record(Ok(6), start + Duration::from_secs(62));
+        let (ready, report) = check.report_at(start + Duration::from_secs(62));
+        assert!(ready);
+        assert_eq!(0, report["head_age_seconds"]);
+    }
+
+    #[test]
+    fn report_failed_check() {
+        let check = ReadinessCheck::new(None);
+        let start = Instant::now();
+        check.record(Ok(5), start);
+        check.record(Err(String::from("Validator unavailable")), start);
+
+        let (ready, report) = check.report_at(start);
+        assert!(!ready);
+        assert_eq!("Validator unavailable", report["reason"]);
+    }
+
+    #[test]
+    fn ignore_head_age_without_limit() {
+        let check = ReadinessCheck::new(None);
+        let start = Instant::now();
+        check.record(Ok(5), start);
+        assert!(check.report_at(start + Duration::from_secs(3600)).0);
+    }
+}
@@ -19,17 +19,34 @@
 extern crate clap;
 extern crate crypto;
 extern crate dirs;
+extern crate ethereum_types;
 extern crate futures_cpupool;
 extern crate jsonrpc_core;
 extern crate jsonrpc_http_server;
+extern crate jsonrpc_pubsub;
+extern crate jsonrpc_ws_server;
+extern crate jsonwebtoken;
+#[macro_use]
+extern crate lazy_static;
 #[macro_use]
 extern crate log;
+#[macro_use]
+extern crate prometheus;
 extern crate protobuf;
+extern crate rlp;
+extern crate rustls;
 extern crate sawtooth_sdk;
+extern crate secp256k1;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
 extern crate serde_json;
 extern crate simple_logging;
 extern crate tiny_keccak;
+extern crate toml;
 extern crate uuid;
+#[cfg(test)]
+extern crate webpki;
 
 /// Creates a custom `jsonrpc_core::Error` object, and logs the message
 macro_rules! fail {
@@ -50,55 +67,154 @@ macro_rules! fail {
 }
 
 mod accounts;
+mod aggregator;
+mod auth;
 mod calls;
 mod client;
+mod config;
+mod connection;
+mod events;
+mod evm;
 mod filters;
+mod health;
 mod messages;
+mod metrics;
+mod nonces;
+mod rate_limit;
+mod raw_transaction;
+mod recent;
 mod requests;
+mod subscriptions;
+mod tls;
 mod transactions;
 mod transform;
 
 use accounts::Account;
+use aggregator::{AggregatorConfig, BatchAggregator};
+use auth::{AccessControl, Credentials};
 use calls::*;
+use clap::ArgMatches;
 use client::ValidatorClient;
-use jsonrpc_core::{IoHandler, Params};
-use jsonrpc_http_server::ServerBuilder;
+use config::{RpcConfig, CONFIG_ENV_VAR};
+use connection::{ConnectionConfig, ValidatorConnection};
+use health::ReadinessCheck;
+use jsonrpc_core::{MetaIoHandler, Params};
+use jsonrpc_http_server::hyper::header::{HeaderValue, CONTENT_TYPE};
+use jsonrpc_http_server::hyper::{Body, Method, Request, Response, StatusCode};
+use jsonrpc_http_server::{
+    AccessControlAllowOrigin, DomainsValidation, Host, RequestMiddlewareAction, ServerBuilder,
+};
+use jsonrpc_pubsub::{PubSubHandler, Session, Subscriber};
+use jsonrpc_ws_server::{ws, Origin, RequestContext};
+use rate_limit::RateLimiter;
 use requests::{RequestExecutor, RequestHandler};
 use sawtooth_sdk::messaging::stream::*;
 use sawtooth_sdk::messaging::zmq_stream::*;
+use std::env;
+use std::net::{SocketAddr, TcpListener};
+use std::path::{Path, PathBuf};
 use std::process;
-
-const SERVER_THREADS: usize = 3;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use tls::ProxyConfig;
 
 fn main() {
     let arg_matches = clap_app!(("seth-rpc") =>
         (version: crate_version!())
         (about: "Seth RPC Server")
+        (@arg config: --config +takes_value
+         "The TOML file to read settings from. Defaults to the file named by the \
+          SETH_RPC_CONFIG environment variable, if it is set. Flags override the file.")
         (@arg connect: --connect +takes_value
          "Component endpoint of the validator to communicate with.")
         (@arg bind: --bind +takes_value
          "The host and port the RPC server should bind to.")
+        (@arg ws_bind: --("ws-bind") +takes_value
+         "The host and port the WebSocket RPC server should bind to. Disabled if not set.")
+        (@arg cors_origins: --("cors-origins")... +takes_value
+         "The origins of the web pages allowed to call the RPC server, or * for any.")
+        (@arg allowed_hosts: --("allowed-hosts")... +takes_value
+         "The Host headers to accept requests with. Any host is accepted if not set.")
+        (@arg tls_cert: --("tls-cert") +takes_value
+         "The PEM certificate chain to serve HTTPS and WSS with. Requires --tls-key.")
+        (@arg tls_key: --("tls-key") +takes_value
+         "The PEM private key of the TLS certificate.")
+        (@arg tls_client_ca: --("tls-client-ca") +takes_value
+         "Only accept clients with a certificate signed by a CA in this PEM file.")
+        (@arg key_dir: --("key-dir") +takes_value
+         "The directory of the account keys. Defaults to ~/.sawtooth/keys.")
         (@arg unlock: --unlock... +takes_value
          "The aliases of the accounts to unlock.")
+        (@arg relayer: --relayer +takes_value
+         "The alias of the account that signs the Seth transactions for eth_sendRawTransaction.")
+        (@arg aggregate_window: --("aggregate-window") +takes_value
+         "Submit the batches received within this many milliseconds in one request to the \
+          validator. Disabled if not set.")
+        (@arg aggregate_max_batches: --("aggregate-max-batches") +takes_value
+         "Submit the aggregated batches as soon as there are this many. Defaults to 100.")
+        (@arg request_timeout: --("request-timeout") +takes_value
+         "How many milliseconds to wait for the validator to respond to a request. Defaults to \
+          10000.")
+        (@arg request_retries: --("request-retries") +takes_value
+         "How many times to retry a request after the validator was unavailable. Defaults to 2.")
+        (@arg max_wait: --("max-wait") +takes_value
+         "The most seconds a transaction's wait field may ask to wait for it to be committed. \
+          Defaults to 300.")
+        (@arg chain_id: --("chain-id") +takes_value
+         "The chain id, which is also the network id. Defaults to 19.")
+        (@arg max_head_age: --("max-head-age") +takes_value
+         "Report that seth-rpc isn't ready at /ready if the head block hasn't advanced for this \
+          many seconds. Not checked if not set.")
+        (@arg namespaces: --namespaces... +takes_value
+         "The namespaces whose methods are served. Defaults to all of them except debug.")
+        (@arg wide_inputs: --("wide-inputs")
+         "Declare the whole Seth and block info namespaces as the inputs and outputs of every \
+          transaction, which keeps the validator from running them in parallel.")
         (@arg verbose: -v... "Increase the logging level.")
     )
     .get_matches();
 
-    let bind = arg_matches.value_of("bind").unwrap_or("127.0.0.1:3030");
-    let connect = arg_matches
-        .value_of("connect")
-        .unwrap_or("tcp://127.0.0.1:4004");
-    let accounts: Vec<Account> = arg_matches
-        .values_of_lossy("unlock")
-        .unwrap_or_else(Vec::new)
+    let config_file = arg_matches
+        .value_of("config")
+        .map(String::from)
+        .or_else(|| env::var(CONFIG_ENV_VAR).ok());
+    let mut config = match config_file {
+        Some(path) => abort_if_err(RpcConfig::from_file(Path::new(&path))),
+        None => RpcConfig::default(),
+    };
+    apply_flags(&mut config, &arg_matches);
+    abort_if_err(config.validate());
+
+    let accounts: Vec<Account> = config
+        .unlock
         .iter()
-        .map(|alias| abort_if_err(Account::load_from_file(alias, &None)))
+        .map(|alias| abort_if_err(Account::load_from_file(config.key_dir(), alias, &None)))
         .collect();
 
     for account in &accounts {
         println!("{} unlocked: {}", account.alias(), account.address());
     }
 
+    let relayer = config
+        .relayer
+        .as_ref()
+        .map(|alias| abort_if_err(Account::load_from_file(config.key_dir(), alias, &None)));
+    if let Some(ref relayer) = relayer {
+        println!(
+            "{} relays raw transactions: {}",
+            relayer.alias(),
+            relayer.address()
+        );
+    }
+
+    let aggregator = config.aggregate_window.map(|window| {
+        BatchAggregator::new(AggregatorConfig {
+            window: Duration::from_millis(window),
+            max_batches: config.aggregate_max_batches,
+        })
+    });
+
     let vs = arg_matches.occurrences_of("verbose");
     let log_level = match vs {
         0 => log::LevelFilter::Warn,
@@ -108,32 +224,373 @@ fn main() {
     };
     simple_logging::log_to_stderr(log_level);
 
-    info!("Trying to connect to validator at {}", connect);
+    let tls = config.tls_cert.as_ref().map(|cert| {
+        abort_if_err(tls::load_server_config(
+            cert,
+            config.tls_key.as_ref().unwrap(),
+            config.tls_client_ca.as_ref().map(PathBuf::as_path),
+        ))
+    });
+    let trust_forwarded_for = config
+        .rate_limit
+        .as_ref()
+        .map_or(false, |rate_limit| rate_limit.trust_forwarded_for);
+    let proxy = ProxyConfig::new(
+        config.tls_max_connections,
+        Duration::from_secs(config.tls_idle_timeout),
+        trust_forwarded_for,
+    );
+    let proxy_token = tls.as_ref().map(|_| proxy.token.clone());
+    if config.rate_limit.is_some() && tls.is_none() && !trust_forwarded_for {
+        warn!(
+            "The address of HTTP clients isn't known without --tls-cert or trust_forwarded_for, \
+             so every client without credentials shares the same rate limit"
+        );
+    }
+
+    let access = abort_if_err(AccessControl::from_config(&config.auth));
+
+    info!("Trying to connect to validator at {}", config.connect);
+
+    let mut io = MetaIoHandler::default();
+    let (connection, receiver) = ValidatorConnection::new(
+        Box::new(ZmqMessageConnection::new(&config.connect)),
+        ConnectionConfig {
+            timeout: Duration::from_millis(config.request_timeout),
+            retries: config.request_retries,
+            retry_interval: Duration::from_millis(config.retry_interval),
+            max_retry_interval: Duration::from_millis(config.max_retry_interval),
+        },
+    );
+    let client = ValidatorClient::new(connection, accounts, relayer, aggregator, config.clone());
+    let executor = RequestExecutor::new(
+        client.clone(),
+        config.pool_size,
+        access.clone(),
+        config.rate_limit.clone().map(RateLimiter::new),
+    );
 
-    let mut io = IoHandler::new();
-    let connection = ZmqMessageConnection::new(connect);
-    let (sender, _) = connection.create();
-    let client = ValidatorClient::new(sender, accounts);
-    let executor = RequestExecutor::new(client);
+    // Filters and subscriptions are fed by the blocks the validator commits
+    let follower_client = client.clone();
+    thread::spawn(move || events::follow_blocks(follower_client, receiver));
 
-    let methods = get_method_list();
+    // Raw transactions aren't relayed until the ones relayed before seth-rpc started are known,
+    // so that they can't be replayed
+    if config.relayer.is_some() {
+        let restore_client = client.clone();
+        thread::spawn(move || restore_relayed_transactions(&restore_client));
+    }
+
+    let methods = get_method_list(&config);
     for (name, method) in methods {
         let clone = executor.clone();
-        io.add_method(&name, move |params: Params| clone.run(params, method));
+        let method_name = name.clone();
+        io.add_method_with_meta(&name, move |params: Params, credentials: Credentials| {
+            clone.run(&method_name, params, &credentials, method)
+        });
     }
 
-    let endpoint: std::net::SocketAddr = bind.parse().unwrap();
-    let server = ServerBuilder::new(io)
-        .threads(SERVER_THREADS)
-        .start_http(&endpoint)
-        .unwrap();
+    // The WebSocket server offers every method of the HTTP server, plus subscriptions. It doesn't
+    // see the headers of the handshake, so its clients only get the anonymous policy.
+    let _ws_server =
+        config.ws_bind.as_ref().map(|ws_bind| {
+            if config.auth.enabled() {
+                warn!("WebSocket clients can only call the methods allowed without credentials");
+            }
+            if config.rate_limit.is_some() {
+                warn!("WebSocket clients all share the same rate limit");
+            }
+            let mut ws_io = PubSubHandler::new(MetaIoHandler::default());
+            for (name, method) in get_method_list(&config) {
+                let clone = executor.clone();
+                let method_name = name.clone();
+                ws_io.add_method(&name, move |params: Params| {
+                    clone.run(&method_name, params, &Credentials::anonymous(), method)
+                });
+            }
+
+            if config.method_enabled("eth_subscribe")
+                && access
+                    .check("eth_subscribe", &Credentials::anonymous())
+                    .is_ok()
+            {
+                let subscribe_client = client.clone();
+                let unsubscribe_client = client.clone();
+                let subscribe_executor = executor.clone();
+                ws_io.add_subscription(
+                    "eth_subscription",
+                    (
+                        "eth_subscribe",
+                        move |params: Params, _, subscriber: Subscriber| match subscribe_executor
+                            .admit("eth_subscribe", &params, &Credentials::anonymous())
+                        {
+                            Ok(()) => pubsub::subscribe(params, &subscribe_client, subscriber),
+                            Err(error) => {
+                                let _ = subscriber.reject(error);
+                            }
+                        },
+                    ),
+                    ("eth_unsubscribe", move |id, _| {
+                        pubsub::unsubscribe(id, &unsubscribe_client)
+                    }),
+                );
+            }
+
+            let ws_token = proxy_token.clone();
+            let ws_server = jsonrpc_ws_server::ServerBuilder::with_meta_extractor(
+                ws_io,
+                |context: &RequestContext| Arc::new(Session::new(context.sender())),
+            )
+            .request_middleware(move |request: &ws::Request| {
+                let token = request.header(tls::TOKEN_HEADER).map(Vec::as_slice);
+                if tls::is_forwarded(ws_token.as_ref().map(String::as_str), token) {
+                    None
+                } else {
+                    Some(ws::Response::new(403, "Forbidden", Vec::new()))
+                }
+            })
+            .allowed_origins(domains::<Origin>(&config.cors_origins))
+            .allowed_hosts(domains::<Host>(&config.allowed_hosts))
+            .start(&local_endpoint(ws_bind, tls.is_some()))
+            .unwrap();
 
-    info!("Starting seth-rpc on http://{}", bind);
+            match tls {
+                Some(ref tls) => {
+                    tls::serve(
+                        bind_tls(ws_bind),
+                        *ws_server.addr(),
+                        tls.clone(),
+                        proxy.clone(),
+                    );
+                    info!("Starting seth-rpc on wss://{}", ws_bind);
+                }
+                None => info!("Starting seth-rpc on ws://{}", ws_bind),
+            }
+
+            ws_server
+        });
+
+    // The TLS proxy sets the header to the address of the client
+    let read_forwarded_for = trust_forwarded_for || tls.is_some();
+    // Every entry but the ones added by the proxies in front of seth-rpc can be forged by the
+    // client, so the address is read from the right. Behind both a trusted proxy and the TLS
+    // proxy, the rightmost entry is the address of the trusted proxy, and the client is the one
+    // before it.
+    let proxy_hops = if trust_forwarded_for && tls.is_some() {
+        1
+    } else {
+        0
+    };
+    let endpoints_client = client.clone();
+    let readiness = ReadinessCheck::new(config.max_head_age.map(Duration::from_secs));
+    let server = ServerBuilder::with_meta_extractor(io, move |request: &Request<Body>| {
+        let header = |name: &str| {
+            request
+                .headers()
+                .get(name)
+                .and_then(|header| header.to_str().ok())
+        };
+        let forwarded_for: Vec<&str> = request
+            .headers()
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|header| header.to_str().ok())
+            .collect();
+        let address = forwarded_for
+            .join(",")
+            .rsplit(',')
+            .nth(proxy_hops)
+            .map(str::trim)
+            .filter(|address| read_forwarded_for && !address.is_empty())
+            .map(String::from);
+        Credentials::from_authorization(header("authorization")).with_address(address)
+    })
+    .request_middleware(move |request: Request<Body>| {
+        let token = request
+            .headers()
+            .get(tls::TOKEN_HEADER)
+            .map(HeaderValue::as_bytes);
+        if !tls::is_forwarded(proxy_token.as_ref().map(String::as_str), token) {
+            return Response::builder()
+                .status(StatusCode::FORBIDDEN)
+                .body(Body::empty())
+                .unwrap()
+                .into();
+        }
+        serve_endpoints(request, &endpoints_client, &readiness)
+    })
+    .threads(config.server_threads)
+    .cors(domains::<AccessControlAllowOrigin>(&config.cors_origins))
+    .allowed_hosts(domains::<Host>(&config.allowed_hosts))
+    .start_http(&local_endpoint(&config.bind, tls.is_some()))
+    .unwrap();
+
+    match tls {
+        Some(tls) => {
+            tls::serve(bind_tls(&config.bind), *server.address(), tls, proxy);
+            info!("Starting seth-rpc on https://{}", config.bind);
+        }
+        None => info!("Starting seth-rpc on http://{}", config.bind),
+    }
 
     server.wait();
 }
 
-fn get_method_list<T>() -> Vec<(String, RequestHandler<T>)>
+/// Overrides the settings of the config file with the flags that were passed
+fn apply_flags(config: &mut RpcConfig, matches: &ArgMatches) {
+    if let Some(connect) = matches.value_of("connect") {
+        config.connect = String::from(connect);
+    }
+    if let Some(bind) = matches.value_of("bind") {
+        config.bind = String::from(bind);
+    }
+    if let Some(ws_bind) = matches.value_of("ws_bind") {
+        config.ws_bind = Some(String::from(ws_bind));
+    }
+    if let Some(cors_origins) = matches.values_of_lossy("cors_origins") {
+        config.cors_origins = cors_origins;
+    }
+    if let Some(allowed_hosts) = matches.values_of_lossy("allowed_hosts") {
+        config.allowed_hosts = allowed_hosts;
+    }
+    if let Some(tls_cert) = matches.value_of("tls_cert") {
+        config.tls_cert = Some(PathBuf::from(tls_cert));
+    }
+    if let Some(tls_key) = matches.value_of("tls_key") {
+        config.tls_key = Some(PathBuf::from(tls_key));
+    }
+    if let Some(tls_client_ca) = matches.value_of("tls_client_ca") {
+        config.tls_client_ca = Some(PathBuf::from(tls_client_ca));
+    }
+    if let Some(key_dir) = matches.value_of("key_dir") {
+        config.key_dir = Some(PathBuf::from(key_dir));
+    }
+    if let Some(unlock) = matches.values_of_lossy("unlock") {
+        config.unlock = unlock;
+    }
+    if let Some(relayer) = matches.value_of("relayer") {
+        config.relayer = Some(String::from(relayer));
+    }
+    if let Some(window) = matches.value_of("aggregate_window") {
+        config.aggregate_window = Some(abort_if_err(window.parse::<u64>()));
+    }
+    if let Some(max_batches) = matches.value_of("aggregate_max_batches") {
+        config.aggregate_max_batches = abort_if_err(max_batches.parse::<usize>());
+    }
+    if let Some(timeout) = matches.value_of("request_timeout") {
+        config.request_timeout = abort_if_err(timeout.parse::<u64>());
+    }
+    if let Some(retries) = matches.value_of("request_retries") {
+        config.request_retries = abort_if_err(retries.parse::<u32>());
+    }
+    if let Some(max_wait) = matches.value_of("max_wait") {
+        config.max_wait = abort_if_err(max_wait.parse::<u64>());
+    }
+    if let Some(chain_id) = matches.value_of("chain_id") {
+        config.chain_id = abort_if_err(chain_id.parse::<u64>());
+    }
+    if let Some(max_head_age) = matches.value_of("max_head_age") {
+        config.max_head_age = Some(abort_if_err(max_head_age.parse::<u64>()));
+    }
+    if let Some(namespaces) = matches.values_of_lossy("namespaces") {
+        config.namespaces = namespaces;
+    }
+    if matches.is_present("wide_inputs") {
+        config.wide_inputs = true;
+    }
+}
+
+/// Reads the transactions relayed before seth-rpc started from the chain, retrying until the
+/// validator answers
+fn restore_relayed_transactions<S: MessageSender>(client: &ValidatorClient<S>) {
+    let mut backoff = client.reconnect_backoff();
+    while let Err(error) = client.restore_relayed_transactions() {
+        let wait = backoff.next_wait();
+        error!(
+            "Failed to read the relayed transactions, retrying in {:?}: {}",
+            wait, error
+        );
+        thread::sleep(wait);
+    }
+    info!("Read the transactions relayed before seth-rpc started");
+}
+
+/// Serves the HTTP endpoints that aren't JSON-RPC methods, and lets every other request through
+fn serve_endpoints<S>(
+    request: Request<Body>,
+    client: &ValidatorClient<S>,
+    readiness: &ReadinessCheck,
+) -> RequestMiddlewareAction
+where
+    S: MessageSender + Clone + Send + Sync + 'static,
+{
+    if request.method() != Method::GET {
+        return request.into();
+    }
+    let (status, content_type, body) = match request.uri().path() {
+        "/metrics" => (
+            StatusCode::OK,
+            metrics::content_type(),
+            metrics::render(client),
+        ),
+        // The process is up
+        "/health" => (
+            StatusCode::OK,
+            json_content_type(),
+            b"{\"healthy\":true}".to_vec(),
+        ),
+        "/ready" => {
+            let (ready, report) = readiness.report(client);
+            let status = if ready {
+                StatusCode::OK
+            } else {
+                StatusCode::SERVICE_UNAVAILABLE
+            };
+            (status, json_content_type(), report.to_string().into_bytes())
+        }
+        _ => return request.into(),
+    };
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, content_type.as_str())
+        .body(Body::from(body))
+        .unwrap()
+        .into()
+}
+
+fn json_content_type() -> String {
+    String::from("application/json")
+}
+
+/// Returns the domains that requests are validated against, or disables the validation if there
+/// are none
+fn domains<T>(values: &[String]) -> DomainsValidation<T>
+where
+    T: for<'a> From<&'a str>,
+{
+    if values.is_empty() {
+        DomainsValidation::Disabled
+    } else {
+        DomainsValidation::AllowOnly(values.iter().map(|value| T::from(value.as_str())).collect())
+    }
+}
+
+/// Returns the address a JSON-RPC server should listen on. With TLS, the server only listens
+/// locally, and the TLS connections on `bind` are forwarded to it.
+fn local_endpoint(bind: &str, tls: bool) -> SocketAddr {
+    if tls {
+        "127.0.0.1:0".parse().unwrap()
+    } else {
+        abort_if_err(bind.parse())
+    }
+}
+
+fn bind_tls(bind: &str) -> TcpListener {
+    abort_if_err(TcpListener::bind(bind))
+}
+
+/// Returns the methods of the enabled namespaces
+fn get_method_list<T>(config: &RpcConfig) -> Vec<(String, RequestHandler<T>)>
 where
     T: MessageSender,
 {
@@ -141,6 +598,7 @@ where
 
     methods.extend(account::get_method_list().into_iter());
     methods.extend(block::get_method_list().into_iter());
+    methods.extend(debug::get_method_list().into_iter());
     methods.extend(logs::get_method_list().into_iter());
     methods.extend(network::get_method_list().into_iter());
     methods.extend(transaction::get_method_list().into_iter());
@@ -148,6 +606,9 @@ where
     methods.extend(seth::get_method_list().into_iter());
 
     methods
+        .into_iter()
+        .filter(|&(ref name, _)| config.method_enabled(name))
+        .collect()
 }
 
 fn abort_if_err<T, E: std::error::Error>(r: Result<T, E>) -> T {
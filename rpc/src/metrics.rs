@@ -0,0 +1,150 @@
+/*
+ * Copyright 2019 Cargill Incorporated
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! The Prometheus metrics of seth-rpc, which the HTTP server serves at `/metrics`.
+
+use client::{Error as ClientError, ValidatorClient};
+use prometheus::{
+    self, Encoder, HistogramTimer, HistogramVec, IntCounterVec, IntGauge, TextEncoder,
+};
+use sawtooth_sdk::messages::client_batch_submit::ClientBatchSubmitResponse_Status;
+use sawtooth_sdk::messages::validator::Message_MessageType;
+use sawtooth_sdk::messaging::stream::MessageSender;
+
+/// The result of a request that was rejected before its method ran
+pub const REJECTED: &str = "rejected";
+
+lazy_static! {
+    static ref REQUESTS: IntCounterVec = register_int_counter_vec!(
+        "seth_rpc_requests_total",
+        "JSON-RPC requests by method and result",
+        &["method", "result"]
+    )
+    .unwrap();
+    static ref REQUEST_DURATION: HistogramVec = register_histogram_vec!(
+        "seth_rpc_request_duration_seconds",
+        "Time taken to run the JSON-RPC requests of each method",
+        &["method"]
+    )
+    .unwrap();
+    static ref VALIDATOR_REQUEST_DURATION: HistogramVec = register_histogram_vec!(
+        "seth_rpc_validator_request_duration_seconds",
+        "Round-trip time of each attempt to send a request to the validator, by message type",
+        &["message_type"]
+    )
+    .unwrap();
+    static ref CLIENT_ERRORS: IntCounterVec = register_int_counter_vec!(
+        "seth_rpc_client_errors_total",
+        "Requests to the validator that failed, by error",
+        &["error"]
+    )
+    .unwrap();
+    static ref BATCH_SUBMISSIONS: IntCounterVec = register_int_counter_vec!(
+        "seth_rpc_batch_submissions_total",
+        "Batch submissions by the status the validator responded with",
+        &["status"]
+    )
+    .unwrap();
+    static ref ACTIVE_FILTERS: IntGauge =
+        register_int_gauge!("seth_rpc_active_filters", "Filters currently installed").unwrap();
+    static ref UNLOCKED_ACCOUNTS: IntGauge = register_int_gauge!(
+        "seth_rpc_unlocked_accounts",
+        "Accounts seth-rpc can sign with"
+    )
+    .unwrap();
+}
+
+/// Starts timing a JSON-RPC request, which is recorded when the timer is dropped
+pub fn request_timer(method: &str) -> HistogramTimer {
+    REQUEST_DURATION.with_label_values(&[method]).start_timer()
+}
+
+/// Counts a JSON-RPC request by its result: `ok`, `error`, or `rejected`
+pub fn request_done(method: &str, result: &str) {
+    REQUESTS.with_label_values(&[method, result]).inc();
+}
+
+/// Starts timing an attempt to send a request to the validator, which is recorded when the timer
+/// is dropped
+pub fn validator_timer(message_type: Message_MessageType) -> HistogramTimer {
+    VALIDATOR_REQUEST_DURATION
+        .with_label_values(&[&format!("{:?}", message_type)])
+        .start_timer()
+}
+
+/// Counts a request to the validator that failed, after any retries
+pub fn client_error(error: &ClientError) {
+    CLIENT_ERRORS.with_label_values(&[error.kind()]).inc();
+}
+
+pub fn batch_submitted(status: ClientBatchSubmitResponse_Status) {
+    BATCH_SUBMISSIONS
+        .with_label_values(&[&format!("{:?}", status)])
+        .inc();
+}
+
+/// Returns the content type of the rendered metrics
+pub fn content_type() -> String {
+    String::from(TextEncoder::new().format_type())
+}
+
+/// Renders every metric in the Prometheus text format
+pub fn render<S: MessageSender>(client: &ValidatorClient<S>) -> Vec<u8> {
+    ACTIVE_FILTERS.set(client.filters.count() as i64);
+    UNLOCKED_ACCOUNTS.set(client.loaded_accounts().read().unwrap().len() as i64);
+
+    let mut buffer = Vec::new();
+    if let Err(error) = TextEncoder::new().encode(&prometheus::gather(), &mut buffer) {
+        error!("Failed to render metrics: {}", error);
+    }
+    buffer
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{batch_submitted, client_error, request_done, request_timer, REQUESTS};
+    use client::Error as ClientError;
+    use prometheus::{self, Encoder, TextEncoder};
+    use sawtooth_sdk::messages::client_batch_submit::ClientBatchSubmitResponse_Status;
+
+    #[test]
+    fn render_recorded_metrics() {
+        let before = REQUESTS.with_label_values(&["eth_blockNumber", "ok"]).get();
+        {
+            let _timer = request_timer("eth_blockNumber");
+            request_done("eth_blockNumber", "ok");
+        }
+        client_error(&ClientError::ValidatorUnavailable(String::from("timeout")));
+        batch_submitted(ClientBatchSubmitResponse_Status::QUEUE_FULL);
+
+        assert_eq!(
+            before + 1,
+            REQUESTS.with_label_values(&["eth_blockNumber", "ok"]).get()
+        );
+
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&prometheus::gather(), &mut buffer)
+            .unwrap();
+        let text = String::from_utf8(buffer).unwrap();
+        assert!(
+            text.contains("seth_rpc_request_duration_seconds_count{method=\"eth_blockNumber\"}")
+        );
+        assert!(text.contains("seth_rpc_client_errors_total{error=\"ValidatorUnavailable\"}"));
+        assert!(text.contains("seth_rpc_batch_submissions_total{status=\"QUEUE_FULL\"}"));
+    }
+}
@@ -0,0 +1,309 @@
+/*
+ * Copyright 2019 Cargill Incorporated
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! Tracks the nonces of transactions that were submitted through seth-rpc but aren't committed
+//! yet, so that several transactions can be sent from the same account before a block commits.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+/// The nonces in flight for an account, with the transaction that was submitted with each one.
+/// Nonces that are reserved but not submitted yet have no transaction.
+type InFlight = BTreeMap<u64, Option<Submitted>>;
+
+#[derive(Debug, Clone, PartialEq)]
+struct Submitted {
+    transaction_id: String,
+    batch_id: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct NonceManager {
+    accounts: Arc<Mutex<HashMap<String, InFlight>>>,
+}
+
+impl NonceManager {
+    pub fn new() -> Self {
+        NonceManager::default()
+    }
+
+    /// Forgets the nonces below the account's nonce in state, since their transactions were
+    /// committed. Returns the lowest nonce still in flight and its batch, if it was submitted.
+    pub fn resync(&self, address: &str, state_nonce: u64) -> Option<(u64, Option<String>)> {
+        let mut accounts = self.accounts.lock().unwrap();
+        let in_flight = accounts.get_mut(address)?;
+        *in_flight = in_flight.split_off(&state_nonce);
+        let first = in_flight.iter().next().map(|(nonce, submitted)| {
+            let batch_id = submitted
+                .as_ref()
+                .map(|submitted| submitted.batch_id.clone());
+            (*nonce, batch_id)
+        });
+        if first.is_none() {
+            accounts.remove(address);
+        }
+        first
+    }
+
+    /// Returns the nonce the next transaction from the account should have
+    pub fn next(&self, address: &str, state_nonce: u64) -> u64 {
+        let accounts = self.accounts.lock().unwrap();
+        accounts
+            .get(address)
+            .and_then(|in_flight| in_flight.keys().next_back())
+            .map(|nonce| (nonce + 1).max(state_nonce))
+            .unwrap_or(state_nonce)
+    }
+
+    /// Reserves `count` consecutive nonces for transactions from the account and returns the
+    /// first one
+    pub fn reserve(&self, address: &str, state_nonce: u64, count: u64) -> u64 {
+        let mut accounts = self.accounts.lock().unwrap();
+        let in_flight = accounts
+            .entry(String::from(address))
+            .or_insert_with(BTreeMap::new);
+        let first = in_flight
+            .keys()
+            .next_back()
+            .map(|nonce| (nonce + 1).max(state_nonce))
+            .unwrap_or(state_nonce);
+        for nonce in first..first + count {
+            in_flight.insert(nonce, None);
+        }
+        first
+    }
+
+    /// Records a transaction from the account that was submitted, and the batch it was in
+    pub fn submitted(&self, address: &str, nonce: u64, transaction_id: &str, batch_id: &str) {
+        self.accounts
+            .lock()
+            .unwrap()
+            .entry(String::from(address))
+            .or_insert_with(BTreeMap::new)
+            .insert(
+                nonce,
+                Some(Submitted {
+                    transaction_id: String::from(transaction_id),
+                    batch_id: String::from(batch_id),
+                }),
+            );
+    }
+
+    /// Returns the id of the transaction from the account with the given nonce, if it was
+    /// submitted and isn't known to be committed yet
+    pub fn transaction_id(&self, address: &str, nonce: u64) -> Option<String> {
+        self.accounts
+            .lock()
+            .unwrap()
+            .get(address)
+            .and_then(|in_flight| in_flight.get(&nonce))
+            .and_then(|submitted| submitted.as_ref())
+            .map(|submitted| submitted.transaction_id.clone())
+    }
+
+    /// Releases reserved nonces whose transactions couldn't be submitted
+    pub fn release(&self, address: &str, first: u64, count: u64) {
+        let mut accounts = self.accounts.lock().unwrap();
+        if let Some(in_flight) = accounts.get_mut(address) {
+            for nonce in first..first + count {
+                in_flight.remove(&nonce);
+            }
+        }
+    }
+
+    /// Releases the nonce and every nonce after it. Once a transaction is invalid or lost, the
+    /// transactions after it can't be committed either, since their nonces are too high.
+    pub fn release_from(&self, address: &str, nonce: u64) {
+        let mut accounts = self.accounts.lock().unwrap();
+        if let Some(in_flight) = accounts.get_mut(address) {
+            in_flight.retain(|in_flight_nonce, _| *in_flight_nonce < nonce);
+        }
+    }
+}
+
+/// The next nonce of each Ethereum account whose raw transactions are relayed. Seth only knows
+/// the relayer, so these nonces are kept by seth-rpc alone, which keeps a signed transaction from
+/// being relayed more than once. They are restored from the relayed transactions on chain when
+/// seth-rpc starts. Only the accounts that sent a transaction most recently are remembered, and
+/// a forgotten account may start from any nonce again.
+#[derive(Debug, Clone)]
+pub struct SignerNonces {
+    signers: Arc<Mutex<Signers>>,
+    capacity: usize,
+}
+
+#[derive(Debug, Default)]
+struct Signers {
+    /// The next nonce of each account, and when the account last sent a transaction
+    next: HashMap<String, (u64, u64)>,
+    /// The accounts by when they last sent a transaction, least recent first
+    by_use: BTreeMap<u64, String>,
+    uses: u64,
+}
+
+impl Signers {
+    fn set(&mut self, signer: &str, nonce: u64, capacity: usize) {
+        self.uses += 1;
+        if let Some((_, used)) = self.next.insert(String::from(signer), (nonce, self.uses)) {
+            self.by_use.remove(&used);
+        }
+        self.by_use.insert(self.uses, String::from(signer));
+
+        while self.next.len() > capacity {
+            let oldest = match self.by_use.keys().next() {
+                Some(&oldest) => oldest,
+                None => break,
+            };
+            if let Some(signer) = self.by_use.remove(&oldest) {
+                self.next.remove(&signer);
+            }
+        }
+    }
+}
+
+impl SignerNonces {
+    /// Remembers the nonces of up to `capacity` accounts
+    pub fn new(capacity: usize) -> Self {
+        SignerNonces {
+            signers: Arc::new(Mutex::new(Signers::default())),
+            capacity,
+        }
+    }
+
+    /// Claims the nonce of a transaction signed by the account, which must be the account's next
+    /// nonce if it is known. Returns the expected nonce otherwise.
+    pub fn claim(&self, signer: &str, nonce: u64) -> Result<(), u64> {
+        let mut signers = self.signers.lock().unwrap();
+        match signers.next.get(signer) {
+            Some(&(expected, _)) if expected != nonce => Err(expected),
+            _ => {
+                signers.set(signer, nonce + 1, self.capacity);
+                Ok(())
+            }
+        }
+    }
+
+    /// Gives a claimed nonce back after its transaction couldn't be relayed
+    pub fn release(&self, signer: &str, nonce: u64) {
+        let mut signers = self.signers.lock().unwrap();
+        if signers.next.get(signer).map(|&(next, _)| next) == Some(nonce + 1) {
+            signers.set(signer, nonce, self.capacity);
+        }
+    }
+
+    /// Records a transaction of the account that was relayed before seth-rpc started
+    pub fn restore(&self, signer: &str, nonce: u64) {
+        let mut signers = self.signers.lock().unwrap();
+        let next = signers
+            .next
+            .get(signer)
+            .map_or(nonce + 1, |&(next, _)| next.max(nonce + 1));
+        signers.set(signer, next, self.capacity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{NonceManager, SignerNonces};
+
+    #[test]
+    fn reserve_consecutive_nonces() {
+        let nonces = NonceManager::new();
+        assert_eq!(3, nonces.reserve("a", 3, 1));
+        assert_eq!(4, nonces.reserve("a", 3, 2));
+        assert_eq!(6, nonces.next("a", 3));
+        assert_eq!(0, nonces.reserve("b", 0, 1));
+
+        // A failed submission gives its nonce back
+        nonces.release("a", 4, 2);
+        assert_eq!(4, nonces.next("a", 3));
+    }
+
+    #[test]
+    fn resync_from_state() {
+        let nonces = NonceManager::new();
+        nonces.reserve("a", 3, 1);
+        nonces.submitted("a", 3, "txn3", "batch3");
+        nonces.reserve("a", 3, 1);
+        nonces.submitted("a", 4, "txn4", "batch4");
+
+        // The first transaction was committed
+        assert_eq!(
+            Some((4, Some(String::from("batch4")))),
+            nonces.resync("a", 4)
+        );
+        assert_eq!(5, nonces.next("a", 4));
+
+        // Another client moved the account's nonce past everything in flight
+        assert_eq!(None, nonces.resync("a", 9));
+        assert_eq!(9, nonces.next("a", 9));
+    }
+
+    #[test]
+    fn find_submitted_transactions() {
+        let nonces = NonceManager::new();
+        nonces.reserve("a", 0, 2);
+        nonces.submitted("a", 0, "txn0", "batch0");
+        assert_eq!(Some(String::from("txn0")), nonces.transaction_id("a", 0));
+
+        // Reserved, but not submitted yet
+        assert_eq!(None, nonces.transaction_id("a", 1));
+
+        // Committed
+        nonces.resync("a", 1);
+        assert_eq!(None, nonces.transaction_id("a", 0));
+    }
+
+    #[test]
+    fn claim_signer_nonces_in_order() {
+        let nonces = SignerNonces::new(10);
+        // Any nonce is accepted from an unknown account
+        assert_eq!(Ok(()), nonces.claim("a", 5));
+        assert_eq!(Ok(()), nonces.claim("a", 6));
+
+        // A replayed or skipped nonce is rejected
+        assert_eq!(Err(7), nonces.claim("a", 6));
+        assert_eq!(Err(7), nonces.claim("a", 9));
+
+        nonces.release("a", 6);
+        assert_eq!(Ok(()), nonces.claim("a", 6));
+    }
+
+    #[test]
+    fn forget_least_recent_signers() {
+        let nonces = SignerNonces::new(2);
+        nonces.restore("a", 3);
+        nonces.restore("a", 1);
+        assert_eq!(Err(4), nonces.claim("a", 1));
+
+        assert_eq!(Ok(()), nonces.claim("b", 0));
+        assert_eq!(Ok(()), nonces.claim("a", 4));
+        assert_eq!(Ok(()), nonces.claim("c", 0));
+
+        // b sent a transaction least recently
+        assert_eq!(Err(5), nonces.claim("a", 0));
+        assert_eq!(Ok(()), nonces.claim("b", 7));
+    }
+
+    #[test]
+    fn release_after_invalid_transaction() {
+        let nonces = NonceManager::new();
+        nonces.reserve("a", 0, 3);
+        nonces.release_from("a", 1);
+        assert_eq!(1, nonces.next("a", 0));
+    }
+}
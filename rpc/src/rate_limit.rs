@@ -0,0 +1,268 @@
+/*
+ * Copyright 2019 Cargill Incorporated
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! Limits how fast each client can send requests, so that one client can't keep the request
+//! threads and the validator busy.
+//!
+//! Every client has a bucket of tokens that refills at a steady rate, up to a burst size. Each
+//! request takes as many tokens as its method costs, and is rejected if there aren't enough.
+
+use auth::Credentials;
+use jsonrpc_core::{Error, ErrorCode, Params, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// The error code of requests rejected because their client sent too many
+pub const THROTTLED: i64 = -32005;
+
+/// The cost of the methods that are more expensive than others, unless it is configured
+const DEFAULT_COSTS: &[(&str, u32)] = &[
+    ("eth_getLogs", 10),
+    ("eth_getFilterLogs", 10),
+    ("eth_getFilterChanges", 5),
+];
+
+/// The number of buckets above which full buckets are forgotten, since a full bucket is the same
+/// as no bucket
+const MAX_BUCKETS: usize = 10_000;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    /// The tokens added to the bucket of each client per second
+    pub rate: f64,
+    /// The most tokens a bucket can hold, which is the largest burst of requests allowed
+    pub burst: u32,
+    /// The cost of each method that doesn't cost 1 token, on top of the defaults
+    pub costs: HashMap<String, u32>,
+    /// The cost of getting a block with its full transactions
+    pub full_block_cost: u32,
+    /// Identify anonymous clients by the address a trusted proxy in front of seth-rpc appended
+    /// to the `X-Forwarded-For` header, which clients must not be able to reach seth-rpc
+    /// without. Behind the TLS proxy of seth-rpc, clients are identified by their address
+    /// either way.
+    pub trust_forwarded_for: bool,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        RateLimitConfig {
+            rate: 20.0,
+            burst: 100,
+            costs: HashMap::new(),
+            full_block_cost: 5,
+            trust_forwarded_for: false,
+        }
+    }
+}
+
+impl RateLimitConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.rate.is_finite() || self.rate <= 0.0 || self.burst == 0 {
+            return Err(String::from(
+                "The `rate` and `burst` of rate limits must be positive",
+            ));
+        }
+        if let Some((method, cost)) = self.costs.iter().find(|&(_, cost)| *cost > self.burst) {
+            return Err(format!(
+                "The cost of {} is {}, but `burst` only allows {}",
+                method, cost, self.burst
+            ));
+        }
+        if self.full_block_cost > self.burst {
+            return Err(String::from("`full_block_cost` can't be more than `burst`"));
+        }
+        Ok(())
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+#[derive(Clone)]
+pub struct RateLimiter {
+    config: Arc<RateLimitConfig>,
+    buckets: Arc<Mutex<HashMap<String, Bucket>>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        RateLimiter {
+            config: Arc::new(config),
+            buckets: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Returns the number of tokens a call of the method with these params takes
+    pub fn cost(&self, method: &str, params: &Params) -> u32 {
+        if let Some(cost) = self.config.costs.get(method) {
+            return *cost;
+        }
+        let full_block = match *params {
+            Params::Array(ref params) => {
+                (method == "eth_getBlockByNumber" || method == "eth_getBlockByHash")
+                    && params.get(1) == Some(&Value::Bool(true))
+            }
+            _ => false,
+        };
+        if full_block {
+            return self.config.full_block_cost;
+        }
+        DEFAULT_COSTS
+            .iter()
+            .find(|&&(expensive, _)| expensive == method)
+            .map_or(1, |&(_, cost)| cost.min(self.config.burst))
+    }
+
+    /// Returns the key of the bucket of a client: the name of its credential if it has one, its
+    /// address if it is known, or the bucket shared by every other client. The HTTP and WebSocket
+    /// servers don't tell seth-rpc the address a connection comes from, so the address is only
+    /// known behind the TLS proxy of seth-rpc, or another proxy if `trust_forwarded_for` is set.
+    /// Otherwise, and for every WebSocket client, the limit is shared.
+    pub fn client_key(&self, name: Option<&str>, credentials: &Credentials) -> String {
+        match (name, credentials.address()) {
+            (Some(name), _) => format!("credential:{}", name),
+            (None, Some(address)) => format!("address:{}", address),
+            _ => String::from("anonymous"),
+        }
+    }
+
+    /// Takes `cost` tokens from the bucket of the client, and returns an error if there aren't
+    /// enough
+    pub fn acquire(&self, client: &str, cost: u32) -> Result<(), Error> {
+        self.acquire_at(client, cost, Instant::now())
+    }
+
+    fn acquire_at(&self, client: &str, cost: u32, now: Instant) -> Result<(), Error> {
+        let burst = f64::from(self.config.burst);
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() > MAX_BUCKETS {
+            let rate = self.config.rate;
+            buckets.retain(|_, bucket| refill(bucket, rate, burst, now) < burst);
+        }
+
+        let bucket = buckets.entry(String::from(client)).or_insert(Bucket {
+            tokens: burst,
+            updated: now,
+        });
+        let tokens = refill(bucket, self.config.rate, burst, now);
+        let cost = f64::from(cost);
+        if tokens < cost {
+            warn!("Throttled {}", client);
+            return Err(Error {
+                code: ErrorCode::ServerError(THROTTLED),
+                message: String::from("Too many requests"),
+                data: None,
+            });
+        }
+        bucket.tokens = tokens - cost;
+        Ok(())
+    }
+}
+
+/// Adds the tokens earned since the bucket was last updated, and returns how many it holds
+fn refill(bucket: &mut Bucket, rate: f64, burst: f64, now: Instant) -> f64 {
+    let elapsed = now.duration_since(bucket.updated);
+    let earned = (elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) * 1e-9) * rate;
+    bucket.tokens = (bucket.tokens + earned).min(burst);
+    bucket.updated = now;
+    bucket.tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RateLimitConfig, RateLimiter, THROTTLED};
+    use auth::Credentials;
+    use jsonrpc_core::{ErrorCode, Params, Value};
+    use std::time::{Duration, Instant};
+
+    fn limiter(rate: f64, burst: u32) -> RateLimiter {
+        RateLimiter::new(RateLimitConfig {
+            rate,
+            burst,
+            ..RateLimitConfig::default()
+        })
+    }
+
+    #[test]
+    fn refill_buckets_over_time() {
+        let limiter = limiter(2.0, 3);
+        let start = Instant::now();
+        for _ in 0..3 {
+            assert!(limiter.acquire_at("a", 1, start).is_ok());
+        }
+        assert_eq!(
+            ErrorCode::ServerError(THROTTLED),
+            limiter.acquire_at("a", 1, start).unwrap_err().code
+        );
+
+        // Other clients have their own bucket
+        assert!(limiter.acquire_at("b", 3, start).is_ok());
+
+        // Two tokens per second
+        let later = start + Duration::from_millis(500);
+        assert!(limiter.acquire_at("a", 1, later).is_ok());
+        assert!(limiter.acquire_at("a", 1, later).is_err());
+
+        // Buckets don't hold more than the burst
+        let much_later = start + Duration::from_secs(60);
+        assert!(limiter.acquire_at("a", 3, much_later).is_ok());
+        assert!(limiter.acquire_at("a", 1, much_later).is_err());
+    }
+
+    #[test]
+    fn expensive_methods_cost_more() {
+        let mut config = RateLimitConfig::default();
+        config.costs.insert(String::from("eth_call"), 3);
+        let limiter = RateLimiter::new(config);
+        let no_params = Params::None;
+        let full_block = Params::Array(vec![Value::from("latest"), Value::Bool(true)]);
+        let block = Params::Array(vec![Value::from("latest"), Value::Bool(false)]);
+
+        assert_eq!(1, limiter.cost("eth_blockNumber", &no_params));
+        assert_eq!(3, limiter.cost("eth_call", &no_params));
+        assert_eq!(10, limiter.cost("eth_getLogs", &no_params));
+        assert_eq!(5, limiter.cost("eth_getBlockByNumber", &full_block));
+        assert_eq!(1, limiter.cost("eth_getBlockByNumber", &block));
+    }
+
+    #[test]
+    fn identify_clients() {
+        let limiter = RateLimiter::new(RateLimitConfig::default());
+        let credentials = Credentials::anonymous().with_address(Some(String::from("192.0.2.1")));
+        assert_eq!(
+            "credential:dashboard",
+            limiter.client_key(Some("dashboard"), &credentials)
+        );
+        assert_eq!("address:192.0.2.1", limiter.client_key(None, &credentials));
+        assert_eq!(
+            "anonymous",
+            limiter.client_key(None, &Credentials::anonymous())
+        );
+    }
+
+    #[test]
+    fn reject_costs_above_burst() {
+        let mut config = RateLimitConfig::default();
+        config.costs.insert(String::from("eth_getLogs"), 1000);
+        assert!(config.validate().is_err());
+        assert!(RateLimitConfig::default().validate().is_ok());
+    }
+}
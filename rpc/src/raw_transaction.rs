@@ -0,0 +1,197 @@
+/*
+ * Copyright 2019 Cargill Incorporated
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! Signed Ethereum transactions, as sent by wallets to `eth_sendRawTransaction`. Both legacy
+//! transactions and EIP-155 transactions, which also sign the chain id, are supported.
+
+use ethereum_types::{H256, U256};
+use evm::{self, Address};
+use rlp::{DecoderError, Rlp, RlpStream};
+use std::error::Error as StdError;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use tiny_keccak;
+
+/// Half of the order of the secp256k1 curve. Like Ethereum, signatures with a larger `s` are
+/// rejected so that each transaction only has one valid signature.
+const SECP256K1_HALF_ORDER: [u8; 32] = [
+    0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+    0x5d, 0x57, 0x6e, 0x73, 0x57, 0xa4, 0x50, 0x1d, 0xdf, 0xe9, 0x2f, 0x46, 0x68, 0x1b, 0x20, 0xa0,
+];
+
+#[derive(Debug)]
+pub enum Error {
+    ParseError(String),
+    InvalidSignature,
+}
+
+impl StdError for Error {
+    fn description(&self) -> &str {
+        match *self {
+            Error::ParseError(ref msg) => msg,
+            Error::InvalidSignature => "Invalid signature",
+        }
+    }
+
+    fn cause(&self) -> Option<&dyn StdError> {
+        None
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match *self {
+            Error::ParseError(ref msg) => write!(f, "ParseError: {}", msg),
+            Error::InvalidSignature => write!(f, "InvalidSignature"),
+        }
+    }
+}
+
+impl From<DecoderError> for Error {
+    fn from(error: DecoderError) -> Self {
+        Error::ParseError(format!("Invalid RLP: {}", error))
+    }
+}
+
+pub struct RawTransaction {
+    pub nonce: u64,
+    pub gas_price: U256,
+    pub gas: u64,
+    /// The called contract, or `None` for a contract creation
+    pub to: Option<Address>,
+    pub value: U256,
+    pub data: Vec<u8>,
+    /// The chain the transaction was signed for, which legacy transactions don't have
+    pub chain_id: Option<u64>,
+    /// The Ethereum address of the key that signed the transaction
+    pub from: Address,
+    /// The Ethereum transaction hash, which is the hash of the encoded transaction
+    pub hash: H256,
+}
+
+impl RawTransaction {
+    /// Decodes a signed transaction and recovers the address that signed it
+    pub fn decode(bytes: &[u8]) -> Result<Self, Error> {
+        let rlp = Rlp::new(bytes);
+        if !rlp.is_list() || rlp.item_count()? != 9 {
+            return Err(Error::ParseError(String::from(
+                "Transaction must be a list of 9 items",
+            )));
+        }
+
+        let to = match rlp.at(3)?.data()? {
+            to if to.is_empty() => None,
+            to if to.len() == 20 => Some(Address::from_slice(to)),
+            _ => return Err(Error::ParseError(String::from("Invalid `to` address"))),
+        };
+
+        // EIP-155 transactions encode the chain id in `v`, as `chain_id * 2 + 35 + recovery_id`
+        let v: u64 = rlp.val_at(6)?;
+        let (chain_id, recovery_id) = match v {
+            27 | 28 => (None, v - 27),
+            v if v >= 35 => (Some((v - 35) / 2), (v - 35) % 2),
+            _ => return Err(Error::InvalidSignature),
+        };
+        let r = word_at(&rlp, 7)?;
+        let s = word_at(&rlp, 8)?;
+        if s > SECP256K1_HALF_ORDER {
+            return Err(Error::InvalidSignature);
+        }
+
+        // The signed message is the transaction without the signature, followed by the chain id
+        // and two empty values for EIP-155 transactions
+        let mut message = RlpStream::new_list(if chain_id.is_some() { 9 } else { 6 });
+        for index in 0..6 {
+            message.append_raw(rlp.at(index)?.as_raw(), 1);
+        }
+        if let Some(chain_id) = chain_id {
+            message.append(&chain_id);
+            message.append_empty_data();
+            message.append_empty_data();
+        }
+        let message_hash = tiny_keccak::keccak256(&message.out());
+
+        let mut v_word = [0u8; 32];
+        v_word[31] = 27 + recovery_id as u8;
+        let mut rs = [0u8; 64];
+        rs[..32].copy_from_slice(&r);
+        rs[32..].copy_from_slice(&s);
+        let from =
+            evm::recover_address(&message_hash, &v_word, &rs).ok_or(Error::InvalidSignature)?;
+
+        Ok(RawTransaction {
+            nonce: rlp.val_at(0)?,
+            gas_price: U256::from_big_endian(&word_at(&rlp, 1)?),
+            gas: rlp.val_at(2)?,
+            to,
+            value: U256::from_big_endian(&word_at(&rlp, 4)?),
+            data: rlp.at(5)?.data()?.to_vec(),
+            chain_id,
+            from,
+            hash: H256::from(tiny_keccak::keccak256(bytes)),
+        })
+    }
+}
+
+/// Returns the item at `index` as a big-endian word
+fn word_at(rlp: &Rlp, index: usize) -> Result<[u8; 32], Error> {
+    let data = rlp.at(index)?.data()?;
+    if data.len() > 32 {
+        return Err(Error::ParseError(format!("Item {} is too long", index)));
+    }
+    let mut word = [0u8; 32];
+    word[32 - data.len()..].copy_from_slice(data);
+    Ok(word)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RawTransaction;
+    use ethereum_types::U256;
+    use transform;
+
+    #[test]
+    fn decode_eip155_transaction() {
+        // The example transaction from EIP-155
+        let bytes = transform::hex_str_to_bytes(
+            "f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a7640000\
+             8025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f\
+             761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83",
+        )
+        .unwrap();
+        let txn = RawTransaction::decode(&bytes).unwrap();
+
+        assert_eq!(9, txn.nonce);
+        assert_eq!(U256::from(20_000_000_000u64), txn.gas_price);
+        assert_eq!(21000, txn.gas);
+        assert_eq!(
+            "3535353535353535353535353535353535353535",
+            transform::bytes_to_hex_str(txn.to.unwrap().as_bytes())
+        );
+        assert_eq!(U256::from(1_000_000_000_000_000_000u64), txn.value);
+        assert_eq!(Some(1), txn.chain_id);
+        assert_eq!(
+            "9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f",
+            transform::bytes_to_hex_str(txn.from.as_bytes())
+        );
+    }
+
+    #[test]
+    fn reject_invalid_transactions() {
+        assert!(RawTransaction::decode(&[0xc0]).is_err());
+        assert!(RawTransaction::decode(&[0x01, 0x02]).is_err());
+    }
+}
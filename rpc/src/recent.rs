@@ -0,0 +1,92 @@
+/*
+ * Copyright 2019 Cargill Incorporated
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! Remembers what seth-rpc learned about the transactions it submitted most recently, such as
+//! their batch ids, without growing for as long as seth-rpc runs.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, RwLock};
+
+#[derive(Debug, Default)]
+struct Entries {
+    values: HashMap<String, String>,
+    /// The keys, oldest first
+    order: VecDeque<String>,
+}
+
+/// A map from transaction ids or hashes to strings, which forgets the oldest entries once it
+/// holds `capacity` of them
+#[derive(Debug, Clone)]
+pub struct RecentTransactions {
+    entries: Arc<RwLock<Entries>>,
+    capacity: usize,
+}
+
+impl RecentTransactions {
+    pub fn new(capacity: usize) -> Self {
+        RecentTransactions {
+            entries: Arc::new(RwLock::new(Entries::default())),
+            capacity,
+        }
+    }
+
+    pub fn insert(&self, key: String, value: String) {
+        let mut entries = self.entries.write().unwrap();
+        if entries.values.insert(key.clone(), value).is_none() {
+            entries.order.push_back(key);
+        }
+        while entries.order.len() > self.capacity {
+            if let Some(oldest) = entries.order.pop_front() {
+                entries.values.remove(&oldest);
+            }
+        }
+    }
+
+    pub fn extend<I: IntoIterator<Item = (String, String)>>(&self, iter: I) {
+        for (key, value) in iter {
+            self.insert(key, value);
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.entries.read().unwrap().values.get(key).cloned()
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.entries.read().unwrap().values.contains_key(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RecentTransactions;
+
+    #[test]
+    fn forget_oldest_entries() {
+        let recent = RecentTransactions::new(2);
+        recent.insert(String::from("a"), String::from("1"));
+        recent.insert(String::from("b"), String::from("2"));
+        // Replacing an entry doesn't make room
+        recent.insert(String::from("a"), String::from("3"));
+        assert_eq!(Some(String::from("3")), recent.get("a"));
+
+        recent.insert(String::from("c"), String::from("4"));
+        assert!(!recent.contains_key("a"));
+        assert_eq!(Some(String::from("2")), recent.get("b"));
+        assert_eq!(Some(String::from("4")), recent.get("c"));
+    }
+}
@@ -15,8 +15,12 @@
  * ------------------------------------------------------------------------------
  */
 
+use super::auth::{AccessControl, Credentials};
 use super::client::ValidatorClient;
+use super::metrics;
+use super::rate_limit::RateLimiter;
 use futures_cpupool::CpuPool;
+use jsonrpc_core::futures::future;
 use jsonrpc_core::{BoxFuture, Error, Params, Value};
 use sawtooth_sdk::messaging::stream::*;
 
@@ -26,18 +30,70 @@ pub type RequestHandler<T> = fn(Params, ValidatorClient<T>) -> Result<Value, Err
 pub struct RequestExecutor<T: MessageSender + Clone + Sync + Send + 'static> {
     pool: CpuPool,
     client: ValidatorClient<T>,
+    access: AccessControl,
+    limiter: Option<RateLimiter>,
 }
 
 impl<T: MessageSender + Clone + Sync + Send + 'static> RequestExecutor<T> {
-    pub fn new(client: ValidatorClient<T>) -> Self {
+    /// Creates an executor that runs requests on `pool_size` threads, or one per CPU if it isn't
+    /// set
+    pub fn new(
+        client: ValidatorClient<T>,
+        pool_size: Option<usize>,
+        access: AccessControl,
+        limiter: Option<RateLimiter>,
+    ) -> Self {
         RequestExecutor {
-            pool: CpuPool::new_num_cpus(),
+            pool: pool_size.map_or_else(CpuPool::new_num_cpus, CpuPool::new),
             client,
+            access,
+            limiter,
         }
     }
 
-    pub fn run(&self, params: Params, handler: RequestHandler<T>) -> BoxFuture<Value> {
+    /// Returns an error if the credentials of the request may not call the method or its client
+    /// has run out of requests, and takes the cost of the request from the client otherwise
+    pub fn admit(
+        &self,
+        method: &str,
+        params: &Params,
+        credentials: &Credentials,
+    ) -> Result<(), Error> {
+        let result = self
+            .access
+            .check(method, credentials)
+            .and_then(|name| match self.limiter {
+                Some(ref limiter) => {
+                    let client = limiter.client_key(name.as_ref().map(String::as_str), credentials);
+                    limiter.acquire(&client, limiter.cost(method, params))
+                }
+                None => Ok(()),
+            });
+        if result.is_err() {
+            metrics::request_done(method, metrics::REJECTED);
+        }
+        result
+    }
+
+    /// Runs the handler of the method, if the credentials of the request may call it and its
+    /// client hasn't run out of requests
+    pub fn run(
+        &self,
+        method: &str,
+        params: Params,
+        credentials: &Credentials,
+        handler: RequestHandler<T>,
+    ) -> BoxFuture<Value> {
+        if let Err(error) = self.admit(method, &params, credentials) {
+            return Box::new(future::err(error));
+        }
         let client = self.client.clone();
-        Box::new(self.pool.spawn_fn(move || handler(params, client)))
+        let method = String::from(method);
+        Box::new(self.pool.spawn_fn(move || {
+            let _timer = metrics::request_timer(&method);
+            let result = handler(params, client);
+            metrics::request_done(&method, if result.is_ok() { "ok" } else { "error" });
+            result
+        }))
     }
 }
@@ -0,0 +1,107 @@
+/*
+ * Copyright 2019 Cargill Incorporated
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+use filters::LogFilter;
+use jsonrpc_core::futures::Future;
+use jsonrpc_core::{Params, Value};
+use jsonrpc_pubsub::{Sink, SubscriptionId};
+use serde_json::Map;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// The kinds of events a WebSocket client can subscribe to with `eth_subscribe`
+#[derive(Debug, Clone)]
+pub enum Subscription {
+    NewHeads,
+    Logs(LogFilter),
+    NewPendingTransactions,
+}
+
+#[derive(Debug, Clone)]
+pub struct SubscriptionEntry {
+    pub subscription: Subscription,
+    sink: Sink,
+}
+
+/// Keeps track of the subscriptions of all connected WebSocket clients.
+///
+/// Subscriptions are removed when the client calls `eth_unsubscribe`, when its connection is
+/// closed, or when a notification can't be delivered to it anymore.
+#[derive(Debug, Clone)]
+pub struct SubscriptionManager {
+    id_ctr: Arc<AtomicUsize>,
+    subscriptions: Arc<Mutex<HashMap<SubscriptionId, SubscriptionEntry>>>,
+}
+
+impl SubscriptionManager {
+    pub fn new() -> Self {
+        SubscriptionManager {
+            id_ctr: Arc::new(AtomicUsize::new(1)),
+            subscriptions: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Reserves a new, unique subscription id
+    pub fn next_id(&self) -> SubscriptionId {
+        let id = self.id_ctr.fetch_add(1, Ordering::SeqCst);
+        SubscriptionId::String(format!("{:#x}", id))
+    }
+
+    pub fn add_subscription(&self, id: SubscriptionId, subscription: Subscription, sink: Sink) {
+        let entry = SubscriptionEntry { subscription, sink };
+        self.subscriptions.lock().unwrap().insert(id, entry);
+    }
+
+    pub fn remove_subscription(&self, id: &SubscriptionId) -> Option<SubscriptionEntry> {
+        self.subscriptions.lock().unwrap().remove(id)
+    }
+
+    /// Returns a snapshot of the currently active subscriptions
+    pub fn get_subscriptions(&self) -> Vec<(SubscriptionId, Subscription)> {
+        self.subscriptions
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(id, entry)| (id.clone(), entry.subscription.clone()))
+            .collect()
+    }
+
+    /// Sends an `eth_subscription` notification with the given result to the subscriber. If the
+    /// notification can't be delivered, the client has gone away and the subscription is
+    /// removed.
+    pub fn notify(&self, id: &SubscriptionId, result: Value) -> bool {
+        // Don't hold the lock while the notification is being sent
+        let sink = match self.subscriptions.lock().unwrap().get(id) {
+            Some(entry) => entry.sink.clone(),
+            None => return false,
+        };
+
+        let mut params = Map::new();
+        params.insert(String::from("subscription"), Value::from(id.clone()));
+        params.insert(String::from("result"), result);
+
+        match sink.notify(Params::Map(params)).wait() {
+            Ok(_) => true,
+            Err(error) => {
+                debug!("Dropping subscription {:?}: {:?}", id, error);
+                self.remove_subscription(id);
+                false
+            }
+        }
+    }
+}
@@ -0,0 +1,564 @@
+/*
+ * Copyright 2019 Cargill Incorporated
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * ------------------------------------------------------------------------------
+ */
+
+//! Serves HTTPS and WSS. The JSON-RPC servers don't support TLS themselves, so they listen on a
+//! local address, and each TLS connection is decrypted and forwarded to them. Each connection
+//! takes a thread, so their number is limited, and connections that stay idle are closed.
+
+use config::{read_error, Error};
+use rustls::internal::pemfile;
+use rustls::{
+    AllowAnyAuthenticatedClient, Certificate, NoClientAuth, PrivateKey, RootCertStore,
+    ServerConfig, ServerSession, Session, StreamOwned,
+};
+use std::fs::File;
+use std::io::{BufReader, Error as IoError, ErrorKind, Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpListener, TcpStream};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use uuid;
+
+/// Loads the certificate chain and key of the server from PEM files. If a CA file is given, only
+/// clients with a certificate signed by one of its CAs can connect.
+pub fn load_server_config(
+    cert_file: &Path,
+    key_file: &Path,
+    client_ca_file: Option<&Path>,
+) -> Result<Arc<ServerConfig>, Error> {
+    let client_auth = match client_ca_file {
+        Some(client_ca_file) => {
+            let mut roots = RootCertStore::empty();
+            let (valid, _) = roots
+                .add_pem_file(&mut BufReader::new(open(client_ca_file)?))
+                .map_err(|_| invalid_file("client CA", client_ca_file))?;
+            if valid == 0 {
+                return Err(invalid_file("client CA", client_ca_file));
+            }
+            AllowAnyAuthenticatedClient::new(roots)
+        }
+        None => NoClientAuth::new(),
+    };
+
+    let mut config = ServerConfig::new(client_auth);
+    config
+        .set_single_cert(load_certs(cert_file)?, load_private_key(key_file)?)
+        .map_err(|error| Error::ParseError(format!("Invalid certificate or key: {}", error)))?;
+    Ok(Arc::new(config))
+}
+
+pub fn load_certs(cert_file: &Path) -> Result<Vec<Certificate>, Error> {
+    let certs = pemfile::certs(&mut BufReader::new(open(cert_file)?))
+        .map_err(|_| invalid_file("certificate", cert_file))?;
+    if certs.is_empty() {
+        return Err(invalid_file("certificate", cert_file));
+    }
+    Ok(certs)
+}
+
+/// Loads a PKCS #8 or RSA private key
+pub fn load_private_key(key_file: &Path) -> Result<PrivateKey, Error> {
+    let mut keys = pemfile::pkcs8_private_keys(&mut BufReader::new(open(key_file)?))
+        .map_err(|_| invalid_file("key", key_file))?;
+    if keys.is_empty() {
+        keys = pemfile::rsa_private_keys(&mut BufReader::new(open(key_file)?))
+            .map_err(|_| invalid_file("key", key_file))?;
+    }
+    keys.into_iter()
+        .next()
+        .ok_or_else(|| invalid_file("key", key_file))
+}
+
+fn open(file: &Path) -> Result<File, Error> {
+    File::open(file).map_err(|error| read_error(file, error))
+}
+
+fn invalid_file(kind: &str, file: &Path) -> Error {
+    Error::ParseError(format!("No valid {} in {}", kind, file.display()))
+}
+
+/// The header that the proxy sets on the requests it forwards, holding the token that the local
+/// servers require. Any other process on the host can connect to them, but without the token it
+/// can't bypass the TLS settings, such as the client certificate check.
+pub const TOKEN_HEADER: &str = "x-seth-proxy-token";
+
+/// How many seconds a client has to complete the TLS handshake
+pub const HANDSHAKE_TIMEOUT_SECS: u64 = 10;
+
+/// The shortest and longest wait for data from one side of a connection before checking the other
+/// side. The wait doubles while neither side sends anything.
+const MIN_POLL_INTERVAL_MS: u64 = 5;
+const MAX_POLL_INTERVAL_MS: u64 = 200;
+
+/// The longest request head that is forwarded
+const MAX_HEAD_LEN: usize = 64 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct ProxyConfig {
+    /// How many connections are forwarded at once. Further connections are closed right away.
+    pub max_connections: usize,
+    /// How long a client has to complete the TLS handshake
+    pub handshake_timeout: Duration,
+    /// How long a connection is kept open while neither side sends anything
+    pub idle_timeout: Duration,
+    /// The value of the token header
+    pub token: String,
+    /// Append the address of the client to the `X-Forwarded-For` header it sent, since a trusted
+    /// proxy in front of seth-rpc set it, instead of replacing the header
+    pub append_forwarded_for: bool,
+}
+
+impl ProxyConfig {
+    /// Returns the settings of a proxy with a new random token
+    pub fn new(max_connections: usize, idle_timeout: Duration, append_forwarded_for: bool) -> Self {
+        ProxyConfig {
+            max_connections,
+            handshake_timeout: Duration::from_secs(HANDSHAKE_TIMEOUT_SECS),
+            idle_timeout,
+            token: uuid::Uuid::new_v4().to_string(),
+            append_forwarded_for,
+        }
+    }
+}
+
+/// Returns whether the value of the token header of a request to a local server is the token of
+/// the proxy. Every request passes if there is no proxy.
+pub fn is_forwarded(token: Option<&str>, header: Option<&[u8]>) -> bool {
+    match token {
+        Some(token) => header == Some(token.as_bytes()),
+        None => true,
+    }
+}
+
+/// Accepts TLS connections on the listener in a new thread, and forwards each one to the server
+/// listening on `backend`
+pub fn serve(
+    listener: TcpListener,
+    backend: SocketAddr,
+    tls: Arc<ServerConfig>,
+    config: ProxyConfig,
+) {
+    let config = Arc::new(config);
+    let connections = Arc::new(AtomicUsize::new(0));
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    if connections.fetch_add(1, Ordering::SeqCst) >= config.max_connections {
+                        connections.fetch_sub(1, Ordering::SeqCst);
+                        warn!("Too many TLS connections, closing new connection");
+                        continue;
+                    }
+                    let tls = tls.clone();
+                    let config = config.clone();
+                    let slot = ConnectionSlot(connections.clone());
+                    thread::spawn(move || {
+                        let _slot = slot;
+                        if let Err(error) = forward(stream, backend, &tls, &config) {
+                            debug!("TLS connection closed: {}", error);
+                        }
+                    });
+                }
+                Err(error) => warn!("Failed to accept TLS connection: {}", error),
+            }
+        }
+    });
+}
+
+/// A connection counted towards `max_connections`, which stops being counted when dropped, even
+/// if the thread forwarding it panics
+struct ConnectionSlot(Arc<AtomicUsize>);
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Forwards what the client sends to the backend and back, until either side closes the
+/// connection or it is idle for too long. Only the first request of a connection is forwarded,
+/// unless it upgrades the connection to a WebSocket.
+fn forward(
+    client: TcpStream,
+    backend: SocketAddr,
+    tls: &Arc<ServerConfig>,
+    config: &ProxyConfig,
+) -> Result<(), IoError> {
+    let peer = client.peer_addr()?.ip();
+    let mut client = StreamOwned::new(ServerSession::new(tls), client);
+    handshake(&mut client.sess, &mut client.sock, config.handshake_timeout)?;
+
+    let idle_timeout = Some(config.idle_timeout);
+    client.sock.set_read_timeout(idle_timeout)?;
+    client.sock.set_write_timeout(idle_timeout)?;
+    let (head, rest) = read_head(&mut client)?;
+    let head = rewrite_head(&head, peer, config)?;
+    let mut server = TcpStream::connect(backend)?;
+    server.set_write_timeout(idle_timeout)?;
+    server.write_all(head.as_bytes())?;
+    server.write_all(&rest)?;
+
+    let min_poll_interval = Duration::from_millis(MIN_POLL_INTERVAL_MS);
+    let mut poll_interval = min_poll_interval;
+    let mut last_active = Instant::now();
+    let mut buffer = [0u8; 16 * 1024];
+    loop {
+        client.sock.set_read_timeout(Some(poll_interval))?;
+        server.set_read_timeout(Some(poll_interval))?;
+        let mut active = false;
+        match client.read(&mut buffer) {
+            Ok(0) => return Ok(()),
+            Ok(n) => {
+                server.write_all(&buffer[..n])?;
+                active = true;
+            }
+            Err(ref error) if timed_out(error) => {}
+            Err(error) => return Err(error),
+        }
+        match server.read(&mut buffer) {
+            Ok(0) => {
+                client.sess.send_close_notify();
+                return client.flush();
+            }
+            Ok(n) => {
+                client.write_all(&buffer[..n])?;
+                client.flush()?;
+                active = true;
+            }
+            Err(ref error) if timed_out(error) => {}
+            Err(error) => return Err(error),
+        }
+
+        if active {
+            poll_interval = min_poll_interval;
+            last_active = Instant::now();
+        } else if last_active.elapsed() >= config.idle_timeout {
+            return Err(IoError::new(ErrorKind::TimedOut, "Connection idle"));
+        } else {
+            poll_interval = (poll_interval * 2).min(Duration::from_millis(MAX_POLL_INTERVAL_MS));
+        }
+    }
+}
+
+/// Completes the TLS handshake, failing if it takes longer than `timeout`
+fn handshake(
+    session: &mut ServerSession,
+    socket: &mut TcpStream,
+    timeout: Duration,
+) -> Result<(), IoError> {
+    let deadline = Instant::now() + timeout;
+    while session.is_handshaking() {
+        let now = Instant::now();
+        if now >= deadline {
+            return Err(IoError::new(ErrorKind::TimedOut, "TLS handshake timed out"));
+        }
+        socket.set_read_timeout(Some(deadline - now))?;
+        socket.set_write_timeout(Some(deadline - now))?;
+        if session.wants_write() {
+            session.write_tls(socket)?;
+        } else if session.read_tls(socket)? == 0 {
+            return Err(IoError::from(ErrorKind::UnexpectedEof));
+        } else if let Err(error) = session.process_new_packets() {
+            // Tell the client why, if possible
+            let _ = session.write_tls(socket);
+            return Err(IoError::new(ErrorKind::InvalidData, error));
+        }
+    }
+    Ok(())
+}
+
+/// Reads the head of the first request, up to the empty line. Returns the head without the empty
+/// line, and what was read after it.
+fn read_head<R: Read>(client: &mut R) -> Result<(String, Vec<u8>), IoError> {
+    let mut data = Vec::new();
+    let mut buffer = [0u8; 4 * 1024];
+    loop {
+        if let Some(end) = data.windows(4).position(|window| window == b"\r\n\r\n") {
+            let rest = data.split_off(end + 4);
+            data.truncate(end);
+            let head = String::from_utf8(data)
+                .map_err(|_| IoError::new(ErrorKind::InvalidData, "Invalid request head"))?;
+            return Ok((head, rest));
+        }
+        if data.len() > MAX_HEAD_LEN {
+            return Err(IoError::new(
+                ErrorKind::InvalidData,
+                "Request head too long",
+            ));
+        }
+        match client.read(&mut buffer)? {
+            0 => return Err(IoError::from(ErrorKind::UnexpectedEof)),
+            n => data.extend_from_slice(&buffer[..n]),
+        }
+    }
+}
+
+/// Sets the token header and the `X-Forwarded-For` header of a request head, replacing any value
+/// the client sent. Unless the request upgrades the connection, the backend is asked to close the
+/// connection after responding, since later requests on it wouldn't carry these headers. A header
+/// line without a colon is rejected.
+fn rewrite_head(head: &str, peer: IpAddr, config: &ProxyConfig) -> Result<String, IoError> {
+    let mut lines = head.split("\r\n");
+    let mut rewritten = lines
+        .next()
+        .filter(|request_line| !request_line.is_empty())
+        .map(String::from)
+        .ok_or_else(|| IoError::new(ErrorKind::InvalidData, "Missing request line"))?;
+    let headers = lines
+        .map(|line| match line.find(':') {
+            Some(colon) => Ok((
+                line[..colon].trim().to_lowercase(),
+                &line[colon + 1..],
+                line,
+            )),
+            None => Err(IoError::new(ErrorKind::InvalidData, "Invalid header line")),
+        })
+        .collect::<Result<Vec<(String, &str, &str)>, IoError>>()?;
+    let upgrade = headers.iter().any(|(name, _, _)| name == "upgrade");
+    let mut forwarded_for: Vec<&str> = Vec::new();
+
+    for (name, value, line) in headers {
+        if name == "x-forwarded-for" {
+            if config.append_forwarded_for {
+                forwarded_for.push(value.trim());
+            }
+            continue;
+        }
+        if name == TOKEN_HEADER || (name == "connection" && !upgrade) {
+            continue;
+        }
+        rewritten.push_str("\r\n");
+        rewritten.push_str(line);
+    }
+    if !upgrade {
+        rewritten.push_str("\r\nConnection: close");
+    }
+    let peer = peer.to_string();
+    forwarded_for.push(&peer);
+    rewritten.push_str(&format!(
+        "\r\nX-Forwarded-For: {}\r\n{}: {}\r\n\r\n",
+        forwarded_for.join(", "),
+        TOKEN_HEADER,
+        config.token
+    ));
+    Ok(rewritten)
+}
+
+fn timed_out(error: &IoError) -> bool {
+    error.kind() == ErrorKind::WouldBlock || error.kind() == ErrorKind::TimedOut
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        load_certs, load_private_key, load_server_config, rewrite_head, serve, ProxyConfig,
+    };
+    use rustls::{ClientConfig, ClientSession, StreamOwned};
+    use std::fs::File;
+    use std::io::{BufReader, ErrorKind, Read, Write};
+    use std::net::{SocketAddr, TcpListener, TcpStream};
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+    use webpki::DNSNameRef;
+
+    const REQUEST: &[u8] = b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: keep-alive\r\n\
+                             X-Forwarded-For: 192.0.2.1\r\nX-Seth-Proxy-Token: forged\r\n\r\n";
+
+    fn data_file(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/data/tls")
+            .join(name)
+    }
+
+    fn proxy_config() -> ProxyConfig {
+        ProxyConfig {
+            max_connections: 10,
+            handshake_timeout: Duration::from_secs(10),
+            idle_timeout: Duration::from_secs(10),
+            token: String::from("secret"),
+            append_forwarded_for: false,
+        }
+    }
+
+    /// Starts a server that answers a single request by echoing its head back
+    fn start_echo_server() -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut head = Vec::new();
+            let mut byte = [0u8; 1];
+            while !head.ends_with(b"\r\n\r\n") {
+                stream.read_exact(&mut byte).unwrap();
+                head.push(byte[0]);
+            }
+            stream.write_all(&head).unwrap();
+        });
+        address
+    }
+
+    fn start_proxy(client_ca: Option<&Path>, config: ProxyConfig) -> SocketAddr {
+        let tls = load_server_config(
+            &data_file("server.pem"),
+            &data_file("server.key"),
+            client_ca,
+        )
+        .unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        serve(listener, start_echo_server(), tls, config);
+        address
+    }
+
+    fn send(
+        proxy: SocketAddr,
+        client_cert: bool,
+        request: &[u8],
+    ) -> Result<String, ::std::io::Error> {
+        let mut config = ClientConfig::new();
+        config
+            .root_store
+            .add_pem_file(&mut BufReader::new(
+                File::open(data_file("ca.pem")).unwrap(),
+            ))
+            .unwrap();
+        if client_cert {
+            config.set_single_client_cert(
+                load_certs(&data_file("client.pem")).unwrap(),
+                load_private_key(&data_file("client.key")).unwrap(),
+            );
+        }
+        let session = ClientSession::new(
+            &Arc::new(config),
+            DNSNameRef::try_from_ascii_str("localhost").unwrap(),
+        );
+        let mut stream = StreamOwned::new(session, TcpStream::connect(proxy).unwrap());
+
+        stream.write_all(request)?;
+        let mut response = Vec::new();
+        match stream.read_to_end(&mut response) {
+            // rustls reports the end of the connection as an error
+            Err(ref error) if error.kind() == ErrorKind::ConnectionAborted => {}
+            result => {
+                result?;
+            }
+        }
+        Ok(String::from_utf8(response).unwrap())
+    }
+
+    /// Returns whether the proxy closes the connection without the client sending anything
+    fn closed_by_proxy(proxy: SocketAddr) -> bool {
+        let mut stream = TcpStream::connect(proxy).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let mut buffer = [0u8; 1];
+        stream.read(&mut buffer).ok() == Some(0)
+    }
+
+    #[test]
+    fn forward_decrypted_connection() {
+        let proxy = start_proxy(None, proxy_config());
+        assert_eq!(
+            "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\
+             X-Forwarded-For: 127.0.0.1\r\nx-seth-proxy-token: secret\r\n\r\n",
+            send(proxy, false, REQUEST).unwrap()
+        );
+    }
+
+    #[test]
+    fn append_forwarded_for() {
+        let proxy = start_proxy(
+            None,
+            ProxyConfig {
+                append_forwarded_for: true,
+                ..proxy_config()
+            },
+        );
+        assert_eq!(
+            "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\
+             X-Forwarded-For: 192.0.2.1, 127.0.0.1\r\nx-seth-proxy-token: secret\r\n\r\n",
+            send(proxy, false, REQUEST).unwrap()
+        );
+    }
+
+    #[test]
+    fn keep_upgraded_connection() {
+        let proxy = start_proxy(None, proxy_config());
+        assert_eq!(
+            "GET / HTTP/1.1\r\nConnection: Upgrade\r\nUpgrade: websocket\r\n\
+             X-Forwarded-For: 127.0.0.1\r\nx-seth-proxy-token: secret\r\n\r\n",
+            send(
+                proxy,
+                false,
+                b"GET / HTTP/1.1\r\nConnection: Upgrade\r\nUpgrade: websocket\r\n\r\n"
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn reject_header_lines_without_colon() {
+        let peer = "127.0.0.1".parse().unwrap();
+        assert!(rewrite_head("GET / HTTP/1.1\r\nHost", peer, &proxy_config()).is_err());
+        assert!(rewrite_head("GET / HTTP/1.1\r\nHost: a:1", peer, &proxy_config()).is_ok());
+    }
+
+    #[test]
+    fn require_client_certificate() {
+        let ca = data_file("ca.pem");
+        assert!(send(start_proxy(Some(&ca), proxy_config()), false, REQUEST).is_err());
+        assert!(send(start_proxy(Some(&ca), proxy_config()), true, REQUEST).is_ok());
+    }
+
+    #[test]
+    fn close_slow_handshakes() {
+        let proxy = start_proxy(
+            None,
+            ProxyConfig {
+                handshake_timeout: Duration::from_millis(100),
+                ..proxy_config()
+            },
+        );
+        assert!(closed_by_proxy(proxy));
+    }
+
+    #[test]
+    fn limit_connections() {
+        let proxy = start_proxy(
+            None,
+            ProxyConfig {
+                max_connections: 1,
+                ..proxy_config()
+            },
+        );
+        let _first = TcpStream::connect(proxy).unwrap();
+        assert!(closed_by_proxy(proxy));
+    }
+
+    #[test]
+    fn reject_invalid_files() {
+        assert!(
+            load_server_config(&data_file("server.key"), &data_file("server.key"), None).is_err()
+        );
+        assert!(load_certs(&data_file("missing.pem")).is_err());
+    }
+}
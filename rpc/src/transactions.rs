@@ -16,6 +16,7 @@
  */
 #![allow(unknown_lints)]
 
+use ethereum_types::U256;
 use protobuf;
 
 use messages::seth::{
@@ -41,21 +42,69 @@ pub enum SethTransaction {
 }
 
 impl SethTransaction {
+    /// Returns the transaction in the payload, or `None` if the payload is malformed
     pub fn try_from(mut txn: SethTrasactionPb) -> Option<Self> {
-        match txn.transaction_type {
-            SethTransaction_TransactionType::CREATE_EXTERNAL_ACCOUNT => Some(
-                SethTransaction::CreateExternalAccount(txn.take_create_external_account()),
-            ),
-            SethTransaction_TransactionType::CREATE_CONTRACT_ACCOUNT => Some(
-                SethTransaction::CreateContractAccount(txn.take_create_contract_account()),
-            ),
+        let txn = match txn.transaction_type {
+            SethTransaction_TransactionType::CREATE_EXTERNAL_ACCOUNT => {
+                SethTransaction::CreateExternalAccount(txn.take_create_external_account())
+            }
+            SethTransaction_TransactionType::CREATE_CONTRACT_ACCOUNT => {
+                SethTransaction::CreateContractAccount(txn.take_create_contract_account())
+            }
             SethTransaction_TransactionType::MESSAGE_CALL => {
-                Some(SethTransaction::MessageCall(txn.take_message_call()))
+                SethTransaction::MessageCall(txn.take_message_call())
             }
             SethTransaction_TransactionType::SET_PERMISSIONS => {
-                Some(SethTransaction::SetPermissions(txn.take_set_permissions()))
+                SethTransaction::SetPermissions(txn.take_set_permissions())
+            }
+            _ => return None,
+        };
+        Some(txn).filter(SethTransaction::has_valid_quantities)
+    }
+
+    pub fn nonce(&self) -> u64 {
+        match *self {
+            SethTransaction::CreateExternalAccount(ref txn) => txn.nonce,
+            SethTransaction::CreateContractAccount(ref txn) => txn.nonce,
+            SethTransaction::MessageCall(ref txn) => txn.nonce,
+            SethTransaction::SetPermissions(ref txn) => txn.nonce,
+        }
+    }
+
+    /// Returns the gas price of a message call or contract creation
+    pub fn gas_price(&self) -> Option<U256> {
+        match *self {
+            SethTransaction::CreateContractAccount(ref txn) => {
+                transform::u256_from_fields(&txn.gas_price_u256, txn.gas_price)
+            }
+            SethTransaction::MessageCall(ref txn) => {
+                transform::u256_from_fields(&txn.gas_price_u256, txn.gas_price)
+            }
+            SethTransaction::CreateExternalAccount(_) | SethTransaction::SetPermissions(_) => None,
+        }
+    }
+
+    /// Returns the value transferred by a message call or contract creation
+    pub fn value(&self) -> Option<U256> {
+        match *self {
+            SethTransaction::CreateContractAccount(ref txn) => {
+                transform::u256_from_fields(&txn.value_u256, txn.value)
+            }
+            SethTransaction::MessageCall(ref txn) => {
+                transform::u256_from_fields(&txn.value_u256, txn.value)
+            }
+            SethTransaction::CreateExternalAccount(_) | SethTransaction::SetPermissions(_) => None,
+        }
+    }
+
+    /// Returns whether the 256-bit quantities of a message call or contract creation fit in 256
+    /// bits
+    fn has_valid_quantities(&self) -> bool {
+        match *self {
+            SethTransaction::CreateContractAccount(_) | SethTransaction::MessageCall(_) => {
+                self.gas_price().is_some() && self.value().is_some()
             }
-            _ => None,
+            SethTransaction::CreateExternalAccount(_) | SethTransaction::SetPermissions(_) => true,
         }
     }
 
@@ -119,12 +168,15 @@ impl Transaction {
     }
 
     pub fn nonce(&self) -> u64 {
-        match self.inner {
-            SethTransaction::CreateExternalAccount(ref txn) => txn.nonce,
-            SethTransaction::CreateContractAccount(ref txn) => txn.nonce,
-            SethTransaction::MessageCall(ref txn) => txn.nonce,
-            SethTransaction::SetPermissions(ref txn) => txn.nonce,
-        }
+        self.inner.nonce()
+    }
+
+    pub fn gas_price(&self) -> Option<U256> {
+        self.inner.gas_price()
+    }
+
+    pub fn value(&self) -> Option<U256> {
+        self.inner.value()
     }
 
     pub fn gas_limit(&self) -> Option<u64> {
@@ -152,6 +204,10 @@ impl Transaction {
         }
     }
 
+    pub fn seth_transaction(&self) -> &SethTransaction {
+        &self.inner
+    }
+
     pub fn data(&self) -> Option<String> {
         match self.inner {
             SethTransaction::CreateExternalAccount(_) => None,
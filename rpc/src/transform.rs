@@ -15,6 +15,7 @@
  * ------------------------------------------------------------------------------
  */
 
+use ethereum_types::U256;
 use jsonrpc_core::{Error, Value};
 use serde_json::Map;
 use std::fmt::LowerHex;
@@ -96,6 +97,50 @@ pub fn u64_from_hex_value(value: &Value) -> Result<u64, Error> {
     })
 }
 
+/// Parses a quantity of up to 256 bits, such as a value, balance, gas price or storage position
+pub fn u256_from_hex_value(value: &Value) -> Result<U256, Error> {
+    from_hex_value_then(value, |s| {
+        if s.is_empty() || s.len() > 64 {
+            return Err(Error::invalid_params(format!(
+                "Not a 256-bit quantity: 0x{}",
+                s
+            )));
+        }
+        let padded = if s.len() % 2 == 0 {
+            String::from(s)
+        } else {
+            format!("0{}", s)
+        };
+        hex_str_to_bytes(&padded)
+            .map(|bytes| U256::from_big_endian(&bytes))
+            .ok_or_else(|| Error::invalid_params(String::from("Not valid hex")))
+    })
+}
+
+/// Narrows a quantity to the 64 bits that Seth transactions can hold, failing with a range error
+/// that names the field if it doesn't fit
+pub fn u256_to_u64(value: U256, key: &str) -> Result<u64, Error> {
+    if value > U256::from(u64::max_value()) {
+        return Err(Error::invalid_params(format!(
+            "`{}` is out of range: Seth transactions support values up to {:#x}",
+            key,
+            u64::max_value()
+        )));
+    }
+    Ok(value.as_u64())
+}
+
+/// Returns a quantity that protobuf messages hold both in a 64-bit field and in a wider bytes
+/// field, which takes precedence when it is set. Returns `None` if the wider field is longer than
+/// 32 bytes, which makes the message malformed.
+pub fn u256_from_fields(wide: &[u8], narrow: u64) -> Option<U256> {
+    match wide.len() {
+        0 => Some(U256::from(narrow)),
+        1..=32 => Some(U256::from_big_endian(wide)),
+        _ => None,
+    }
+}
+
 pub fn bytes_from_hex_value(value: &Value) -> Result<Vec<u8>, Error> {
     from_hex_value_then(value, |s| {
         hex_str_to_bytes(s).ok_or_else(|| Error::invalid_params(String::from("Not valid hex")))
@@ -126,6 +171,10 @@ pub fn get_u64_from_map(map: &Map<String, Value>, key: &str) -> Result<Option<u6
     get_hex_value_from_map_then(map, key, u64_from_hex_value)
 }
 
+pub fn get_u256_from_map(map: &Map<String, Value>, key: &str) -> Result<Option<U256>, Error> {
+    get_hex_value_from_map_then(map, key, u256_from_hex_value)
+}
+
 pub fn get_bytes_from_map(map: &Map<String, Value>, key: &str) -> Result<Option<Vec<u8>>, Error> {
     get_hex_value_from_map_then(map, key, bytes_from_hex_value)
 }
@@ -187,6 +236,26 @@ pub fn make_txn_receipt_obj(
                 .collect(),
         ),
     );
+    // Transactions the processor rejects are never committed, so any committed one succeeded
+    map.insert(String::from("status"), num_to_hex(&1));
+    Value::Object(map)
+}
+
+/// Makes a receipt for a transaction that was in a batch the validator found invalid. Since the
+/// transaction is never committed, it has no block and used no gas.
+pub fn make_invalid_txn_receipt_obj(txn_id: &str, message: &str) -> Value {
+    let mut map = Map::new();
+    map.insert(String::from("transactionHash"), hex_prefix(txn_id));
+    map.insert(String::from("transactionIndex"), Value::Null);
+    map.insert(String::from("blockHash"), Value::Null);
+    map.insert(String::from("blockNumber"), Value::Null);
+    map.insert(String::from("cumulativeGasUsed"), num_to_hex(&0));
+    map.insert(String::from("gasUsed"), num_to_hex(&0));
+    map.insert(String::from("contractAddress"), Value::Null);
+    map.insert(String::from("returnValue"), hex_prefix(""));
+    map.insert(String::from("logs"), Value::Array(Vec::new()));
+    map.insert(String::from("status"), num_to_hex(&0));
+    map.insert(String::from("error"), Value::String(String::from(message)));
     Value::Object(map)
 }
 
@@ -241,8 +310,16 @@ pub fn make_txn_obj_no_block(txn: &Transaction) -> Value {
     };
     map.insert(String::from("to"), to);
 
-    map.insert(String::from("value"), zerobytes(0));
-    map.insert(String::from("gasPrice"), zerobytes(0));
+    let value = match txn.value() {
+        Some(value) => num_to_hex(&value),
+        None => zerobytes(0),
+    };
+    map.insert(String::from("value"), value);
+    let gas_price = match txn.gas_price() {
+        Some(gas_price) => num_to_hex(&gas_price),
+        None => zerobytes(0),
+    };
+    map.insert(String::from("gasPrice"), gas_price);
 
     let gas = match txn.gas_limit() {
         Some(g) => num_to_hex(&g),
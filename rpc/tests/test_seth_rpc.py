@@ -13,8 +13,12 @@
 # limitations under the License.
 # ------------------------------------------------------------------------------
 
+import threading
+import time
 import unittest
 
+import requests
+
 from rpc_client import RpcClient
 from mock_validator import MockValidator
 
@@ -37,16 +41,25 @@ from sawtooth_sdk.protobuf.client_batch_submit_pb2 import \
     ClientBatchSubmitRequest
 from sawtooth_sdk.protobuf.client_batch_submit_pb2 import \
     ClientBatchSubmitResponse
+from sawtooth_sdk.protobuf.client_batch_submit_pb2 import \
+    ClientBatchStatusRequest
+from sawtooth_sdk.protobuf.client_batch_submit_pb2 import \
+    ClientBatchStatusResponse
+from sawtooth_sdk.protobuf.client_batch_submit_pb2 import ClientBatchStatus
 from sawtooth_sdk.protobuf.block_pb2 import Block
 from sawtooth_sdk.protobuf.block_pb2 import BlockHeader
 from sawtooth_sdk.protobuf.batch_pb2 import Batch
 from sawtooth_sdk.protobuf.batch_pb2 import BatchHeader
+from sawtooth_sdk.protobuf.batch_pb2 import BatchList
 from sawtooth_sdk.protobuf.transaction_pb2 import Transaction
 from sawtooth_sdk.protobuf.transaction_pb2 import TransactionHeader
 from sawtooth_sdk.protobuf.client_receipt_pb2 import ClientReceiptGetRequest
 from sawtooth_sdk.protobuf.client_receipt_pb2 import ClientReceiptGetResponse
 from sawtooth_sdk.protobuf.transaction_receipt_pb2 import TransactionReceipt
+from sawtooth_sdk.protobuf.client_event_pb2 import \
+    ClientEventsSubscribeResponse
 from sawtooth_sdk.protobuf.events_pb2 import Event
+from sawtooth_sdk.protobuf.events_pb2 import EventList
 from protobuf.seth_pb2 import SethTransactionReceipt
 from protobuf.seth_pb2 import EvmEntry
 from protobuf.seth_pb2 import EvmStateAccount
@@ -83,10 +96,10 @@ class SethRpcTest(unittest.TestCase):
         cls.contract_address_b = bytes([0xff] * 20)
         cls.contract_init_s = "0" * 60 * 2
         cls.contract_init_b = bytes([0x0] * 60)
-        cls.contract_init_txn_id = "de0a7299e732f04cdf18f098f44d70574512a5f4ef037105b028223195c781c424a48cadaf1a7de26f41b085d57cf40e15f0ebe24cca2bc36114abd679a95d4a"
+        cls.contract_init_txn_id = "b7b50dcd1e25889a00e5f0a1484b9f9bc91865d25e4137a9da5d1c617090e86800b9a46bdef84948e4e8d67751052d5dc20fe6cc6c5aec766a0a52542276e0fc"
         cls.contract_call_s = "0" * 30 * 2
         cls.contract_call_b = bytes([0x0] * 30)
-        cls.contract_call_txn_id = "057be5cc3860362022178b0d05012c7a8f1073c75d7f1f695d8091e8a18112b07fdcd3403dfc3728dabaf04ea0000e3d7a212abf3b60dbff44155bf8ed237e43"
+        cls.contract_call_txn_id = "ea610b23cbd3da9042832ff11b12d8bae90cde131d962494a896f6dd3615fafc00d29cb1dc6f2eb1a8da322ac98f9ff79e79f72f1fba98d45d870c6042a256db"
         cls.balance = 123
         cls.nonce = 456
         cls.code_b = bytes([0xab, 0xcd, 0xef])
@@ -104,6 +117,29 @@ class SethRpcTest(unittest.TestCase):
         cls.return_value_s = "2a"
         cls.return_value_b = bytes([0x2a])
 
+        # seth-rpc subscribes to block commits when it starts, and reads the
+        # transactions its relayer relayed from the chain, which is just the
+        # genesis block
+        for _ in range(2):
+            msg = cls.validator.receive()
+            if msg.message_type == Message.CLIENT_EVENTS_SUBSCRIBE_REQUEST:
+                cls.validator.respond(
+                    Message.CLIENT_EVENTS_SUBSCRIBE_RESPONSE,
+                    ClientEventsSubscribeResponse(
+                        status=ClientEventsSubscribeResponse.OK),
+                    msg)
+            else:
+                assert msg.message_type == Message.CLIENT_BLOCK_LIST_REQUEST
+                cls.validator.respond(
+                    Message.CLIENT_BLOCK_LIST_RESPONSE,
+                    ClientBlockListResponse(
+                        status=ClientBlockListResponse.OK,
+                        blocks=[Block(
+                            header=BlockHeader(
+                                block_num=0).SerializeToString(),
+                        )]),
+                    msg)
+
         assert cls.rpc.call("personal_unlockAccount", [cls.account_address, None, None])
 
     # -- Network tests -- #
@@ -111,6 +147,114 @@ class SethRpcTest(unittest.TestCase):
         """Test that the network id 19 is returned."""
         self.assertEqual("19", self.rpc.call("net_version"))
 
+    def test_chain_id(self):
+        """Test that the chain id is the network id, as a quantity."""
+        self.assertEqual("0x13", self.rpc.call("eth_chainId"))
+
+    # -- Server tests -- #
+    def _post(self, headers):
+        request = {"jsonrpc": "2.0", "method": "net_version", "id": 1}
+        return requests.post(self.url, json=request, headers=headers)
+
+    def test_cors_allowed_origin(self):
+        """Test that pages from an allowed origin may read responses."""
+        origin = "http://dapp.example"
+        response = self._post({"Origin": origin})
+        self.assertEqual(200, response.status_code)
+        self.assertEqual(
+            origin, response.headers["Access-Control-Allow-Origin"])
+        self.assertEqual("19", response.json()["result"])
+
+    def test_cors_other_origin(self):
+        """Test that requests from pages of other origins are rejected."""
+        response = self._post({"Origin": "http://evil.example"})
+        self.assertEqual(403, response.status_code)
+
+    def test_other_host(self):
+        """Test that requests for hosts that aren't allowed are rejected,
+        which keeps pages that rebind a domain to seth-rpc from calling it."""
+        response = self._post({"Host": "evil.example"})
+        self.assertEqual(403, response.status_code)
+
+    def test_metrics(self):
+        """Test that the requests of each method are counted."""
+        self.rpc.call("net_version")
+        response = requests.get(self.url + "metrics")
+        self.assertEqual(200, response.status_code)
+        self.assertIn(
+            'seth_rpc_requests_total{method="net_version",result="ok"}',
+            response.text)
+        self.assertIn("seth_rpc_unlocked_accounts 1", response.text)
+
+    def test_health(self):
+        """Test that the health endpoint answers without the validator."""
+        response = requests.get(self.url + "health")
+        self.assertEqual(200, response.status_code)
+        self.assertEqual({"healthy": True}, response.json())
+
+    def _answer_readiness_check(self, block_list_response):
+        msg = self.validator.receive()
+        self.assertEqual(msg.message_type, Message.CLIENT_BLOCK_LIST_REQUEST)
+        self.validator.respond(
+            Message.CLIENT_BLOCK_LIST_RESPONSE, block_list_response, msg)
+
+    def _check_ready(self, block_list_response):
+        """Gets /ready after the validator answered a readiness check with
+           the response. /ready reports the last check and starts the next
+           one, which gets the same response."""
+        requests.get(self.url + "ready")
+        self._answer_readiness_check(block_list_response)
+        time.sleep(0.5)
+        response = requests.get(self.url + "ready")
+        self._answer_readiness_check(block_list_response)
+        return response
+
+    def test_ready(self):
+        """Test that readiness reports the head block of the validator."""
+        response = self._check_ready(ClientBlockListResponse(
+            status=ClientBlockListResponse.OK,
+            blocks=[Block(
+                header=BlockHeader(block_num=15).SerializeToString(),
+            )]))
+        self.assertEqual(200, response.status_code)
+        report = response.json()
+        self.assertTrue(report["ready"])
+        self.assertEqual(15, report["head_block_number"])
+
+    def test_not_ready(self):
+        """Test that seth-rpc isn't ready while the validator isn't."""
+        response = self._check_ready(ClientBlockListResponse(
+            status=ClientBlockListResponse.NOT_READY))
+        self.assertEqual(503, response.status_code)
+        self.assertFalse(response.json()["ready"])
+
+    def test_retry_read(self):
+        """Test that a read the validator doesn't respond to is retried."""
+        self.rpc.acall("net_peerCount")
+        self.validator.receive()
+        # No response, so the request is sent again after the timeout
+        msg = self.validator.receive()
+        self.assertEqual(msg.message_type, Message.CLIENT_PEERS_GET_REQUEST)
+        self.validator.respond(
+            Message.CLIENT_PEERS_GET_RESPONSE,
+            ClientPeersGetResponse(
+                status=ClientPeersGetResponse.OK,
+                peers=["test_peer"]),
+            msg)
+        self.assertEqual("0x1", self.rpc.get_result())
+
+    def test_submission_not_retried(self):
+        """Test that a batch submission the validator doesn't respond to
+           fails without being sent again."""
+        batch_list = self._make_raw_batch_list("seth", "1.0")
+        self.rpc.acall(
+            "seth_sendRawBatch", ["0x" + batch_list.SerializeToString().hex()])
+        msg = self.validator.receive()
+        self.assertEqual(msg.message_type, Message.CLIENT_BATCH_SUBMIT_REQUEST)
+        result = self.rpc.get_result()
+        self.assertEqual(-32002, result["error"]["code"])
+        self.assertEqual("Validator unavailable", result["error"]["message"])
+
     def test_net_peerCount(self):
         """Test that 1 is returned as hex."""
         self.rpc.acall("net_peerCount")
@@ -319,6 +463,58 @@ class SethRpcTest(unittest.TestCase):
         result = self.rpc.get_result()
         self.assertEqual(hex(self.balance), result)
 
+    def test_get_balance_u256(self):
+        """Test that a balance that doesn't fit in 64 bits is retrieved from
+           the wide balance field."""
+        balance = 2 ** 200 + 1
+        self.rpc.acall(
+            "eth_getBalance", ["0x" + self.account_address, "latest"])
+
+        msg, request = self._receive_state_request()
+        self.validator.respond(
+            Message.CLIENT_STATE_GET_RESPONSE,
+            ClientStateGetResponse(
+                status=ClientStateGetResponse.OK,
+                value=EvmEntry(
+                    account=EvmStateAccount(
+                        balance=self.balance,
+                        balance_u256=balance.to_bytes(32, "big")),
+                ).SerializeToString()),
+            msg)
+
+        result = self.rpc.get_result()
+        self.assertEqual(hex(balance), result)
+
+    def test_get_transaction_count_pending(self):
+        """Test that the pending transaction count includes transactions that
+           were submitted but aren't committed yet."""
+        self.rpc.acall(
+            "eth_sendTransaction", [{
+                "from": "0x" + self.account_address,
+                "data": "0x" + self.contract_call_s,
+                "to": "0x" + self.contract_address,
+        }])
+        msg, txn = self._receive_state_request()
+        self._send_state_response(msg)
+        self._exchange_block_info_config()
+        msg, txn = self._receive_submit_request()
+        batch_id = self._get_submitted_batch_id(msg)
+        self._send_submit_response(msg)
+        self.rpc.get_result()
+
+        self.rpc.acall(
+            "eth_getTransactionCount", ["0x" + self.account_address, "pending"])
+        msg, request = self._receive_state_request()
+        self.assertEqual(request.address,
+            "a68b06" + self.account_address + "0" * 24)
+        self._send_state_response(msg)
+        msg, request = self._receive_batch_status_request(batch_id)
+        self._send_batch_status_response(
+            msg, batch_id, ClientBatchStatus.PENDING)
+
+        result = self.rpc.get_result()
+        self.assertEqual(hex(self.nonce + 1), result)
+
     def test_get_balance_bad_input(self):
         """Test that the correct error message is returned if no input is given
            to eth_getBalance
@@ -397,6 +593,29 @@ class SethRpcTest(unittest.TestCase):
         result = self.rpc.get_result()
         self.assertEqual("0x" + self.stored_s, result)
 
+    def test_get_storage_at_quantity(self):
+        """Test that the storage position is compared as a number, so that it
+           doesn't need leading zeros."""
+        self.rpc.acall(
+            "eth_getStorageAt",
+            ["0x" + self.account_address,
+             hex(int(self.position_s, 16)),
+             "latest"])
+
+        msg, request = self._receive_state_request()
+        self._send_state_response(msg)
+        result = self.rpc.get_result()
+        self.assertEqual("0x" + self.stored_s, result)
+
+    def test_get_storage_at_position_too_large(self):
+        """Test that a storage position wider than 256 bits is rejected."""
+        result = self.rpc.call(
+            "eth_getStorageAt",
+            ["0x" + self.account_address, "0x1" + "0" * 64, "latest"])
+        self.assertEqual(
+            result["error"]["message"],
+            "Invalid storage position: Not a 256-bit quantity: 0x1" + "0" * 64)
+
     def test_get_storage_at_bad_input(self):
         """Test that the correct error message is returned if no input is given
            to eth_getStorageAt.
@@ -704,6 +923,7 @@ class SethRpcTest(unittest.TestCase):
         msg, txn = self._receive_state_request()
         self._send_state_response(msg)
 
+        self._exchange_block_info_config()
         msg, txn = self._receive_submit_request()
 
         seth_txn = SethTransaction()
@@ -720,6 +940,78 @@ class SethRpcTest(unittest.TestCase):
         self.assertEqual(
             "0x" + self.contract_init_txn_id, self.rpc.get_result())
 
+    def test_send_transaction_permissions(self):
+        """Tests that the permissions of a created contract are set."""
+        self.rpc.acall(
+            "eth_sendTransaction", [{
+                "from": "0x" + self.account_address,
+                "data": "0x" + self.contract_init_s,
+                "permissions": "+contract,-root",
+        }])
+
+        msg, txn = self._receive_state_request()
+        self._send_state_response(msg)
+        self._exchange_block_info_config()
+        msg, txn = self._receive_submit_request()
+
+        seth_txn = SethTransaction()
+        seth_txn.ParseFromString(txn.payload)
+        self.assertEqual(
+            seth_txn.create_contract_account.permissions.perms, 8)
+
+        self._send_submit_response(msg)
+        self.rpc.get_result()
+
+    def test_send_transaction_chaining(self):
+        """Tests that a txn with chaining enabled can access the whole Seth
+           namespace."""
+        self.rpc.acall(
+            "eth_sendTransaction", [{
+                "from": "0x" + self.account_address,
+                "data": "0x" + self.contract_call_s,
+                "to": "0x" + self.contract_address,
+                "chaining": True,
+        }])
+
+        msg, txn = self._receive_state_request()
+        self._send_state_response(msg)
+        msg, txn = self._receive_submit_request()
+
+        txn_header = TransactionHeader()
+        txn_header.ParseFromString(txn.header)
+        self.assertEqual(list(txn_header.inputs), ["a68b06", "00b10c"])
+        self.assertEqual(list(txn_header.outputs), ["a68b06", "00b10c"])
+
+        self._send_submit_response(msg)
+        self.rpc.get_result()
+
+    def test_send_transaction_unknown_field(self):
+        """Tests that a txn with a field seth-rpc doesn't know is rejected."""
+        result = self.rpc.call(
+            "eth_sendTransaction", [{
+                "from": "0x" + self.account_address,
+                "data": "0x" + self.contract_call_s,
+                "to": "0x" + self.contract_address,
+                "chainning": True,
+        }])
+        self.assertEqual(
+            result["error"]["message"], "Unknown transaction field `chainning`")
+
+    def test_send_transaction_value_out_of_range(self):
+        """Tests that a value Seth transactions can't hold is rejected with a
+           range error."""
+        result = self.rpc.call(
+            "eth_sendTransaction", [{
+                "from": "0x" + self.account_address,
+                "data": "0x" + self.contract_call_s,
+                "to": "0x" + self.contract_address,
+                "value": hex(2 ** 64),
+        }])
+        self.assertEqual(
+            result["error"]["message"],
+            "`value` is out of range: Seth transactions support values up to "
+            "0xffffffffffffffff")
+
     def test_send_transaction_message_call(self):
         """Tests that a message call txn is submitted correctly."""
         self.rpc.acall(
@@ -732,6 +1024,7 @@ class SethRpcTest(unittest.TestCase):
         msg, txn = self._receive_state_request()
         self._send_state_response(msg)
 
+        self._exchange_block_info_config()
         msg, txn = self._receive_submit_request()
 
         seth_txn = SethTransaction()
@@ -748,6 +1041,432 @@ class SethRpcTest(unittest.TestCase):
         self.assertEqual(
             "0x" + self.contract_call_txn_id, self.rpc.get_result())
 
+    def test_send_transaction_eip1559_fields(self):
+        """Tests that a txn with the data in `input` and the fee fields of
+           EIP-1559 is submitted with its fee cap as the gas price."""
+        self.rpc.acall(
+            "eth_sendTransaction", [{
+                "from": "0x" + self.account_address,
+                "input": "0x" + self.contract_call_s,
+                "to": "0x" + self.contract_address,
+                "type": "0x2",
+                "maxFeePerGas": "0x3e8",
+                "maxPriorityFeePerGas": "0x1",
+        }])
+
+        msg, txn = self._receive_state_request()
+        self._send_state_response(msg)
+        self._exchange_block_info_config()
+        msg, txn = self._receive_submit_request()
+
+        seth_txn = SethTransaction()
+        seth_txn.ParseFromString(txn.payload)
+        call = seth_txn.message_call
+        self.assertEqual(call.data, self.contract_call_b)
+        self.assertEqual(call.gas_price, 1000)
+
+        self._send_submit_response(msg)
+        self.rpc.get_result()
+
+    def test_send_transaction_invalid_eip1559_fields(self):
+        """Tests that txns with an unsupported type, or with fields that
+           contradict each other, are rejected."""
+        txn = {
+            "from": "0x" + self.account_address,
+            "data": "0x" + self.contract_call_s,
+            "to": "0x" + self.contract_address,
+        }
+        for fields, message in [
+                ({"type": "0x3"}, "Unsupported transaction type 0x3"),
+                ({"input": "0x00"}, "`data` and `input` differ"),
+                ({"gasPrice": "0x1", "maxFeePerGas": "0x1"},
+                 "`gasPrice` can't be set with `maxFeePerGas` or "
+                 "`maxPriorityFeePerGas`"),
+        ]:
+            result = self.rpc.call(
+                "eth_sendTransaction", [dict(txn, **fields)])
+            self.assertEqual(result["error"]["message"], message)
+
+    def test_send_transaction_access_list(self):
+        """Tests that a txn declares the entries it accesses as its inputs
+           and outputs, including the ones in its access list."""
+        other_address = "e" * 20 * 2
+        self.rpc.acall(
+            "eth_sendTransaction", [{
+                "from": "0x" + self.account_address,
+                "data": "0x" + self.contract_call_s,
+                "to": "0x" + self.contract_address,
+                "accessList": [{
+                    "address": "0x" + other_address,
+                    "storageKeys": [],
+                }],
+        }])
+
+        msg, txn = self._receive_state_request()
+        self._send_state_response(msg)
+        self._exchange_block_info_config()
+        msg, txn = self._receive_submit_request()
+
+        txn_header = TransactionHeader()
+        txn_header.ParseFromString(txn.header)
+        accounts = sorted([
+            "a68b06" + address + "0" * 24
+            for address in [
+                self.account_address, self.contract_address, other_address]
+        ])
+        self.assertEqual(list(txn_header.outputs), accounts)
+        self.assertEqual(
+            list(txn_header.inputs),
+            sorted(accounts + [
+                "a68b06" + "0" * 64,
+                "00b10c01" + "0" * 62,
+                # The info of blocks 0 to 255
+                "00b10c00" + "0" * 60,
+                # The info of blocks 256 to 511
+                "00b10c00" + "0" * 59 + "1",
+            ]))
+
+        self._send_submit_response(msg)
+        self.rpc.get_result()
+
+    def test_send_transaction_dependencies(self):
+        """Tests that a txn depends on the in-flight txn from the same account
+           with the previous nonce and on the txns it lists."""
+        txn = {
+            "from": "0x" + self.account_address,
+            "data": "0x" + self.contract_call_s,
+            "to": "0x" + self.contract_address,
+        }
+        self.rpc.acall("eth_sendTransaction", [txn])
+        msg, _ = self._receive_state_request()
+        self._send_state_response(msg)
+        self._exchange_block_info_config()
+        msg, first_txn = self._receive_submit_request()
+        first_batch_id = self._get_submitted_batch_id(msg)
+        self._send_submit_response(msg)
+        self.rpc.get_result()
+
+        dependency = "b" * 128
+        self.rpc.acall("eth_sendTransaction", [
+            dict(txn, dependencies=["0x" + dependency])])
+        msg, _ = self._receive_state_request()
+        self._send_state_response(msg)
+        msg, _ = self._receive_batch_status_request(first_batch_id)
+        self._send_batch_status_response(
+            msg, first_batch_id, ClientBatchStatus.PENDING)
+        self._exchange_block_info_config()
+        msg, second_txn = self._receive_submit_request()
+
+        seth_txn = SethTransaction()
+        seth_txn.ParseFromString(second_txn.payload)
+        self.assertEqual(seth_txn.message_call.nonce, self.nonce + 1)
+        txn_header = TransactionHeader()
+        txn_header.ParseFromString(second_txn.header)
+        self.assertEqual(
+            list(txn_header.dependencies),
+            [dependency, first_txn.header_signature])
+
+        self._send_submit_response(msg)
+        self.rpc.get_result()
+
+    def test_send_transaction_invalid_dependencies(self):
+        """Tests that dependencies that aren't transaction ids with 0x are
+           rejected."""
+        for dependency in ["b" * 128, "0x" + "b" * 64, "0x" + "g" * 128]:
+            result = self.rpc.call(
+                "eth_sendTransaction", [{
+                    "from": "0x" + self.account_address,
+                    "data": "0x" + self.contract_call_s,
+                    "to": "0x" + self.contract_address,
+                    "dependencies": [dependency],
+            }])
+            self.assertEqual(
+                result["error"]["message"],
+                "Dependencies must be transaction ids of 128 hex characters "
+                "with 0x")
+
+    def test_send_transaction_wait_too_long(self):
+        """Tests that a txn asking to wait longer than the maximum is
+           rejected."""
+        result = self.rpc.call(
+            "eth_sendTransaction", [{
+                "from": "0x" + self.account_address,
+                "data": "0x" + self.contract_call_s,
+                "to": "0x" + self.contract_address,
+                "wait": hex(301),
+        }])
+        self.assertEqual(result["error"]["code"], -32602)
+        self.assertEqual(
+            result["error"]["message"],
+            "`wait` of 301 seconds is longer than the maximum of 300")
+
+    def test_send_transaction_wait(self):
+        """Tests that a txn with `wait` set waits for its batch to commit and
+           returns the receipt."""
+        self.rpc.acall(
+            "eth_sendTransaction", [{
+                "from": "0x" + self.account_address,
+                "data": "0x" + self.contract_call_s,
+                "to": "0x" + self.contract_address,
+                "wait": "0x5",
+        }])
+
+        msg, txn = self._receive_state_request()
+        self._send_state_response(msg)
+        self._exchange_block_info_config()
+        msg, txn = self._receive_submit_request()
+        batch_id = self._get_submitted_batch_id(msg)
+        self._send_submit_response(msg)
+
+        msg, request = self._receive_batch_status_request(batch_id)
+        self.assertTrue(request.wait)
+        self.assertEqual(request.timeout, 5)
+        self._send_batch_status_response(
+            msg, batch_id, ClientBatchStatus.COMMITTED)
+
+        msg, request = self._receive_receipt_request()
+        self.assertEqual(
+            request.transaction_ids[0], self.contract_call_txn_id)
+        self._send_receipts_back(msg, [TransactionReceipt(
+            data=[SethTransactionReceipt(
+                gas_used=self.gas).SerializeToString()],
+            transaction_id=self.contract_call_txn_id)])
+        msg, request = self._receive_transaction_request()
+        self._send_transaction_response(msg)
+        msg, request = self._receive_block_request_transaction()
+        self._send_block_back(msg, Block(
+            header=BlockHeader(block_num=self.block_num).SerializeToString(),
+            header_signature=self.block_id,
+            batches=[Batch(transactions=[
+                Transaction(header_signature=self.contract_call_txn_id)])]))
+
+        result = self.rpc.get_result()
+        self.assertEqual(
+            result["transactionHash"], "0x" + self.contract_call_txn_id)
+        self.assertEqual(result["blockHash"], "0x" + self.block_id)
+        self.assertEqual(result["gasUsed"], hex(self.gas))
+        self.assertEqual(result["status"], "0x1")
+
+    def test_send_transaction_and_wait_invalid(self):
+        """Tests that seth_sendTransactionAndWait returns a failed receipt
+           when the txn is invalid."""
+        self.rpc.acall(
+            "seth_sendTransactionAndWait", [{
+                "from": "0x" + self.account_address,
+                "data": "0x" + self.contract_call_s,
+                "to": "0x" + self.contract_address,
+        }])
+
+        msg, txn = self._receive_state_request()
+        self._send_state_response(msg)
+        self._exchange_block_info_config()
+        msg, txn = self._receive_submit_request()
+        batch_id = self._get_submitted_batch_id(msg)
+        self._send_submit_response(msg)
+
+        msg, request = self._receive_batch_status_request(batch_id)
+        self.assertTrue(request.wait)
+        self.assertEqual(request.timeout, 60)
+        self._send_batch_status_response(
+            msg, batch_id, ClientBatchStatus.INVALID,
+            [ClientBatchStatus.InvalidTransaction(
+                transaction_id=self.contract_call_txn_id,
+                message="Out of gas")])
+
+        result = self.rpc.get_result()
+        self.assertEqual(
+            result["transactionHash"], "0x" + self.contract_call_txn_id)
+        self.assertEqual(result["status"], "0x0")
+        self.assertEqual(result["error"], "Out of gas")
+
+    def test_send_batch(self):
+        """Tests that several txns are submitted in one batch with
+           consecutive nonces."""
+        self.rpc.acall(
+            "seth_sendBatch", [[{
+                "from": "0x" + self.account_address,
+                "data": "0x" + self.contract_init_s,
+            }, {
+                "from": "0x" + self.account_address,
+                "data": "0x" + self.contract_call_s,
+                "to": "0x" + self.contract_address,
+            }]])
+
+        msg, txn = self._receive_state_request()
+        self._send_state_response(msg)
+        # Both txns execute code
+        self._exchange_block_info_config()
+        self._exchange_block_info_config()
+        msg, txn = self._receive_submit_request()
+
+        request = ClientBatchSubmitRequest()
+        request.ParseFromString(msg.content)
+        self.assertEqual(len(request.batches), 1)
+        batch = request.batches[0]
+        self.assertEqual(len(batch.transactions), 2)
+        create, call = [SethTransaction() for _ in batch.transactions]
+        create.ParseFromString(batch.transactions[0].payload)
+        call.ParseFromString(batch.transactions[1].payload)
+        self.assertEqual(
+            create.transaction_type, SethTransaction.CREATE_CONTRACT_ACCOUNT)
+        self.assertEqual(create.create_contract_account.nonce, self.nonce)
+        self.assertEqual(call.transaction_type, SethTransaction.MESSAGE_CALL)
+        self.assertEqual(call.message_call.nonce, self.nonce + 1)
+
+        self._send_submit_response(msg)
+        result = self.rpc.get_result()
+        self.assertEqual(result["transactionIds"], [
+            "0x" + txn.header_signature for txn in batch.transactions])
+        self.assertEqual(result["batchId"], "0x" + batch.header_signature)
+
+    def test_send_batch_different_senders(self):
+        """Tests that a batch with txns from different accounts is
+           rejected."""
+        result = self.rpc.call(
+            "seth_sendBatch", [[{
+                "from": "0x" + self.account_address,
+                "data": "0x" + self.contract_init_s,
+            }, {
+                "from": "0x" + self.contract_address,
+                "data": "0x" + self.contract_init_s,
+            }]])
+        # Invalid params, rather than method not found
+        self.assertEqual(result["error"]["code"], -32602)
+        self.assertEqual(
+            result["error"]["message"],
+            "All transactions must be from the same account")
+
+    def test_send_empty_batch(self):
+        """Tests that seth_sendBatch is served and rejects an empty batch."""
+        result = self.rpc.call("seth_sendBatch", [[]])
+        self.assertEqual(result["error"]["code"], -32602)
+        self.assertEqual(
+            result["error"]["message"], "Batch has no transactions")
+
+    def test_send_raw_transaction_wrong_chain(self):
+        """Tests that a raw transaction signed for another chain is rejected
+           before anything is sent to the validator."""
+        # The example transaction from EIP-155, which is signed for chain 1
+        raw_txn = (
+            "0xf86c098504a817c800825208943535353535353535353535353535353535353535"
+            "880de0b6b3a76400008025a028ef61340bd939bc2195fe537567866003e1a15d3c"
+            "71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc"
+            "64214b297fb1966a3b6d83")
+        result = self.rpc.call("eth_sendRawTransaction", [raw_txn])
+        self.assertEqual(
+            result["error"]["message"], "Invalid chain id 1, expected 19")
+
+    def test_send_raw_batch(self):
+        """Tests that a batch list signed by the client is submitted as is and
+           that the ids of its transactions are returned."""
+        batch_list = self._make_raw_batch_list("seth", "1.0")
+        self.rpc.acall(
+            "seth_sendRawBatch", ["0x" + batch_list.SerializeToString().hex()])
+
+        msg = self.validator.receive()
+        self.assertEqual(msg.message_type, Message.CLIENT_BATCH_SUBMIT_REQUEST)
+        request = ClientBatchSubmitRequest()
+        request.ParseFromString(msg.content)
+        self.assertEqual(list(request.batches), list(batch_list.batches))
+
+        self._send_submit_response(msg)
+        self.assertEqual(["0x" + self.txn_id], self.rpc.get_result())
+
+    def test_send_raw_batch_wrong_family(self):
+        """Tests that a batch list with transactions of another family is
+           rejected before anything is sent to the validator."""
+        batch_list = self._make_raw_batch_list("intkey", "1.0")
+        result = self.rpc.call(
+            "seth_sendRawBatch", ["0x" + batch_list.SerializeToString().hex()])
+        self.assertEqual(
+            result["error"]["message"],
+            "Transaction {} is not a seth 1.0 transaction".format(self.txn_id))
+
+    def test_get_invalid_transaction_receipt(self):
+        """Tests that a transaction submitted in a batch the validator found
+           invalid gets a failed receipt with the validator's message."""
+        txn_id = "b" * 128
+        batch_id = "a" * 128
+        self._submit_raw_batch(txn_id, batch_id)
+
+        self.rpc.acall("eth_getTransactionReceipt", ["0x" + txn_id])
+
+        msg, request = self._receive_receipt_request()
+        self.assertEqual(request.transaction_ids[0], txn_id)
+        self.validator.respond(
+            Message.CLIENT_RECEIPT_GET_RESPONSE,
+            ClientReceiptGetResponse(
+                status=ClientReceiptGetResponse.NO_RESOURCE),
+            msg)
+
+        msg, request = self._receive_batch_status_request(batch_id)
+        self._send_batch_status_response(
+            msg, batch_id, ClientBatchStatus.INVALID,
+            [ClientBatchStatus.InvalidTransaction(
+                transaction_id=txn_id, message="Nonce mismatch")])
+
+        result = self.rpc.get_result()
+        self.assertEqual(result["transactionHash"], "0x" + txn_id)
+        self.assertEqual(result["blockHash"], None)
+        self.assertEqual(result["gasUsed"], "0x0")
+        self.assertEqual(result["status"], "0x0")
+        self.assertEqual(result["error"], "Nonce mismatch")
+
+    def test_get_transaction_status_committed(self):
+        """Tests that a committed transaction's status has its batch and
+           block."""
+        batch_id = "9" * 128
+        self.rpc.acall("seth_getTransactionStatus", ["0x" + self.txn_id])
+
+        msg, request = self._receive_transaction_request()
+        self.assertEqual(request.transaction_id, self.txn_id)
+        self._send_transaction_response(msg)
+        msg, request = self._receive_block_request_transaction()
+        block = Block(
+            header=BlockHeader(block_num=self.block_num).SerializeToString(),
+            header_signature=self.block_id,
+            batches=[Batch(
+                header_signature=batch_id,
+                transactions=[Transaction(header_signature=self.txn_id)])])
+        self._send_block_back(msg, block)
+
+        result = self.rpc.get_result()
+        self.assertEqual(result["transactionId"], "0x" + self.txn_id)
+        self.assertEqual(result["batchId"], "0x" + batch_id)
+        self.assertEqual(result["status"], "COMMITTED")
+        self.assertEqual(result["error"], None)
+        self.assertEqual(result["blockHash"], "0x" + self.block_id)
+        self.assertEqual(result["blockNumber"], hex(self.block_num))
+
+    def test_get_transaction_status_pending(self):
+        """Tests that the status of a transaction that isn't committed yet
+           comes from the status of its batch."""
+        txn_id = "8" * 128
+        batch_id = "7" * 128
+        self._submit_raw_batch(txn_id, batch_id)
+
+        self.rpc.acall("seth_getTransactionStatus", ["0x" + txn_id])
+
+        msg, request = self._receive_transaction_request()
+        self.validator.respond(
+            Message.CLIENT_TRANSACTION_GET_RESPONSE,
+            ClientTransactionGetResponse(
+                status=ClientTransactionGetResponse.NO_RESOURCE),
+            msg)
+        msg, request = self._receive_block_request_transaction()
+        self._send_block_no_resource(msg)
+
+        msg, request = self._receive_batch_status_request(batch_id)
+        self._send_batch_status_response(
+            msg, batch_id, ClientBatchStatus.PENDING)
+
+        result = self.rpc.get_result()
+        self.assertEqual(result["transactionId"], "0x" + txn_id)
+        self.assertEqual(result["batchId"], "0x" + batch_id)
+        self.assertEqual(result["status"], "PENDING")
+        self.assertEqual(result["error"], None)
+        self.assertEqual(result["blockHash"], None)
+
     def test_get_transaction_receipt(self):
         """Tests that a transaction receipt is retrieved correctly."""
         self.rpc.acall(
@@ -777,6 +1496,7 @@ class SethRpcTest(unittest.TestCase):
         self.assertEqual(result["returnValue"], "0x" + self.return_value_s)
         self.assertEqual(
             result["contractAddress"], "0x" + self.contract_address)
+        self.assertEqual(result["status"], "0x1")
 
         log = result["logs"][0]
         self.assertEqual(log["removed"], False)
@@ -806,6 +1526,102 @@ class SethRpcTest(unittest.TestCase):
             "28a02d85497c9b26203c31f028f31fa0ae9b944aa219ae6ecf7655b2e2428d" +\
             "d6904f")
 
+    def test_call(self):
+        """Tests that a contract call is simulated against the state of the
+           latest block."""
+        # PUSH1 42, PUSH1 0, MSTORE, PUSH1 32, PUSH1 0, RETURN
+        code = bytes.fromhex("602a60005260206000f3")
+        self.rpc.acall("eth_call", [{
+            "to": "0x" + self.contract_address,
+            "data": "0x" + self.contract_call_s,
+        }, "latest"])
+
+        self._block_list_exchange()
+        self._state_entry_exchange(None)
+        self._state_entry_exchange(
+            EvmEntry(account=EvmStateAccount(code=code)),
+            "a68b06" + self.contract_address + "0" * 24)
+
+        result = self.rpc.get_result()
+        self.assertEqual(result, "0x" + "00" * 31 + "2a")
+
+    def test_call_revert(self):
+        """Tests that the revert data is returned when a call reverts."""
+        # PUSH1 42, PUSH1 0, MSTORE, PUSH1 32, PUSH1 0, REVERT
+        code = bytes.fromhex("602a60005260206000fd")
+        self.rpc.acall("eth_call", [{"to": "0x" + self.contract_address}])
+
+        self._block_list_exchange()
+        self._state_entry_exchange(None)
+        self._state_entry_exchange(
+            EvmEntry(account=EvmStateAccount(code=code)))
+
+        result = self.rpc.get_result()
+        self.assertEqual(result["error"]["message"], "execution reverted")
+        self.assertEqual(result["error"]["data"], "0x" + "00" * 31 + "2a")
+
+    def test_estimate_gas(self):
+        """Tests that the gas needed by a contract call is estimated by
+           simulating it."""
+        # PUSH1 42, PUSH1 0, SSTORE
+        code = bytes.fromhex("602a600055")
+        self.rpc.acall("eth_estimateGas", [{"to": "0x" + self.contract_address}])
+
+        self._block_list_exchange()
+        self._state_entry_exchange(None)
+        self._state_entry_exchange(
+            EvmEntry(account=EvmStateAccount(code=code)))
+
+        # Only storage updates are charged for
+        self.assertEqual(self.rpc.get_result(), "0x1")
+
+    def test_trace_call(self):
+        """Tests that a contract call is traced instruction by instruction."""
+        # PUSH1 42, PUSH1 0, SSTORE
+        code = bytes.fromhex("602a600055")
+        self.rpc.acall("debug_traceCall", [
+            {"to": "0x" + self.contract_address}, "latest", {}])
+
+        self._block_list_exchange()
+        self._state_entry_exchange(None)
+        self._state_entry_exchange(
+            EvmEntry(account=EvmStateAccount(code=code)))
+
+        result = self.rpc.get_result()
+        self.assertEqual(result["gas"], 1)
+        self.assertFalse(result["failed"])
+        self.assertEqual(
+            [log["op"] for log in result["structLogs"]],
+            ["PUSH1", "PUSH1", "SSTORE"])
+        self.assertEqual(
+            result["structLogs"][2]["stack"],
+            ["00" * 31 + "2a", "00" * 32])
+        self.assertEqual(
+            result["structLogs"][2]["storage"],
+            {"00" * 32: "00" * 31 + "2a"})
+
+    def test_trace_call_with_call_tracer(self):
+        """Tests that the calls made by a contract call are traced with the
+           call tracer."""
+        code = bytes.fromhex("602a600055")
+        self.rpc.acall("debug_traceCall", [
+            {"to": "0x" + self.contract_address, "data": "0x1234"},
+            "latest",
+            {"tracer": "callTracer"}])
+
+        self._block_list_exchange()
+        self._state_entry_exchange(None)
+        self._state_entry_exchange(
+            EvmEntry(account=EvmStateAccount(code=code)))
+
+        result = self.rpc.get_result()
+        self.assertEqual(result["type"], "CALL")
+        self.assertEqual(result["to"], "0x" + self.contract_address)
+        self.assertEqual(result["input"], "0x1234")
+        self.assertEqual(result["gasUsed"], "0x1")
+        self.assertEqual(result["output"], "0x")
+        self.assertNotIn("calls", result)
+
     # -- Log tests -- #
     def test_new_filter(self):
         """Test that new log filters are created sequentially and that nothing
@@ -823,7 +1639,6 @@ class SethRpcTest(unittest.TestCase):
                 ,]
             ]
         }])
-        self._block_list_exchange()
         result = self.rpc.get_result()
         n = int(result, 16)
         self.rpc.acall("eth_newFilter", [{
@@ -833,44 +1648,43 @@ class SethRpcTest(unittest.TestCase):
             ],
             "topics": [],
         }])
-        self._block_list_exchange()
         result = self.rpc.get_result()
         n_plus_1 = int(result, 16)
         self.assertEqual(n + 1, n_plus_1)
+        self._uninstall_filters([hex(n), hex(n_plus_1)])
 
     def test_new_block_filter(self):
         """Test that new block filters are created sequentially and that
         nothing breaks while creating them."""
         self.rpc.acall("eth_newBlockFilter")
-        self._block_list_exchange()
         result = self.rpc.get_result()
         n = int(result, 16)
         self.rpc.acall("eth_newBlockFilter")
-        self._block_list_exchange()
         result = self.rpc.get_result()
         n_plus_1 = int(result, 16)
         self.assertEqual(n + 1, n_plus_1)
+        self._uninstall_filters([hex(n), hex(n_plus_1)])
 
     def test_new_transaction_filter(self):
         """Test that new transaction filters are created sequentially and that
         nothing breaks while creating them."""
         self.rpc.acall("eth_newPendingTransactionFilter")
-        self._block_list_exchange()
         result = self.rpc.get_result()
         n = int(result, 16)
         self.rpc.acall("eth_newPendingTransactionFilter")
-        self._block_list_exchange()
         result = self.rpc.get_result()
         n_plus_1 = int(result, 16)
         self.assertEqual(n + 1, n_plus_1)
+        self._uninstall_filters([hex(n), hex(n_plus_1)])
 
     def test_uninstall_filter(self):
         """Test that uninstalling a filter works"""
         self.rpc.acall("eth_newBlockFilter")
-        self._block_list_exchange()
         filter_id = self.rpc.get_result()
         self.assertEqual(
             True, self.rpc.call("eth_uninstallFilter", [filter_id]))
+        self.assertEqual(
+            False, self.rpc.call("eth_uninstallFilter", [filter_id]))
 
     def test_get_logs(self):
         """Test that getting logs works."""
@@ -923,7 +1737,6 @@ class SethRpcTest(unittest.TestCase):
         }
 
         self.rpc.acall("eth_newFilter", [log_filter])
-        self._block_list_exchange()
         filter_id = self.rpc.get_result()
 
         self.rpc.acall("eth_getFilterLogs", [filter_id])
@@ -952,68 +1765,57 @@ class SethRpcTest(unittest.TestCase):
         topic1, topic2 = log["topics"]
         self.assertEqual(topic1, "0x" + self.topic1_s)
         self.assertEqual(topic2, "0x" + self.topic2_s)
+        self._uninstall_filters([filter_id])
 
     def test_get_block_filter_changes(self):
         """Tests that getting block filter changes works."""
         self.rpc.acall("eth_newBlockFilter")
-        self._block_list_exchange()
         filter_id = self.rpc.get_result()
 
+        # Block ids are taken from the events, so no blocks are requested
         block_id_plus_1 = "e" * 128
         block_id_plus_2 = "d" * 128
-        self.rpc.acall("eth_getFilterChanges", [filter_id])
-        self._block_list_exchange(blocks=[Block(
-            header=BlockHeader(
-                block_num=self.block_num+2,
-            ).SerializeToString(),
-            header_signature=block_id_plus_2,
-        )])
-        self._block_get_exchange(block=Block(
-            header=BlockHeader(
-                block_num=self.block_num+1,
-            ).SerializeToString(),
-            header_signature=block_id_plus_1,
-        ))
-        result = self.rpc.get_result()
+        self._send_block_commit(block_id_plus_1, self.block_num + 1)
+        self._send_block_commit(block_id_plus_2, self.block_num + 2)
+
+        result = self._poll_filter_changes(filter_id, 2)
         self.assertEqual(len(result), 2)
         self.assertEqual(result[0], "0x" + block_id_plus_1)
         self.assertEqual(result[1], "0x" + block_id_plus_2)
 
+        # Changes are only returned once
+        self.assertEqual([], self.rpc.call("eth_getFilterChanges", [filter_id]))
+        self._uninstall_filters([filter_id])
+
     def test_get_transaction_filter_changes(self):
         """Tests that getting transaction filter changes works."""
         self.rpc.acall("eth_newPendingTransactionFilter")
-        self._block_list_exchange()
         filter_id = self.rpc.get_result()
 
-        txn_id_1 = "e" * 128
-        txn_id_2 = "d" * 128
-        self.rpc.acall("eth_getFilterChanges", [filter_id])
-        self._block_list_exchange(blocks=[Block(
-            header=BlockHeader(
-                block_num=self.block_num+2,
-            ).SerializeToString(),
-            batches=[Batch(transactions=[Transaction(
-                header=TransactionHeader(
-                    family_name="seth",
-                ).SerializeToString(),
-                header_signature=txn_id_2,
-            )])],
-        )])
-        self._block_get_exchange(block=Block(
-            header=BlockHeader(
-                block_num=self.block_num+1,
-            ).SerializeToString(),
-            batches=[Batch(transactions=[Transaction(
-                header=TransactionHeader(
-                    family_name="seth",
+        block_ids = ["e" * 128, "d" * 128]
+        txn_ids = ["e" * 128, "d" * 128]
+        for i, (block_id, txn_id) in enumerate(zip(block_ids, txn_ids)):
+            self._send_block_commit(block_id, self.block_num + i + 1)
+            msg, request = self._receive_block_request_id()
+            self.assertEqual(request.block_id, block_id)
+            self._send_block_back(msg, block=Block(
+                header=BlockHeader(
+                    block_num=self.block_num + i + 1,
                 ).SerializeToString(),
-                header_signature=txn_id_1,
-            )])],
-        ))
-        result = self.rpc.get_result()
+                header_signature=block_id,
+                batches=[Batch(transactions=[Transaction(
+                    header=TransactionHeader(
+                        family_name="seth",
+                    ).SerializeToString(),
+                    header_signature=txn_id,
+                )])],
+            ))
+
+        result = self._poll_filter_changes(filter_id, 2)
         self.assertEqual(len(result), 2)
-        self.assertEqual(result[0], "0x" + txn_id_1)
-        self.assertEqual(result[1], "0x" + txn_id_2)
+        self.assertEqual(result[0], "0x" + txn_ids[0])
+        self.assertEqual(result[1], "0x" + txn_ids[1])
+        self._uninstall_filters([filter_id])
 
     def test_get_log_filter_changes(self):
         """Tests that getting log filter changes works."""
@@ -1033,86 +1835,51 @@ class SethRpcTest(unittest.TestCase):
                 ["0x" + t for t in topics],
             ]
         }])
-        self._block_list_exchange()
         filter_id = self.rpc.get_result()
 
-        # Request changes
-        self.rpc.acall("eth_getFilterChanges", [filter_id])
+        for i, txn_id in enumerate(txn_ids):
+            log_event = Event(
+                event_type="seth_log_event",
+                attributes=[
+                    Event.Attribute(key="address", value=self.contract_address),
+                    Event.Attribute(key="topic1", value=topics[i]),
+                ],
+                data=self.log_data_b,
+            )
 
-        # Exchange blocks
-        self._block_list_exchange(blocks=[Block(
-            header=BlockHeader(
-                block_num=self.block_num+2,
-            ).SerializeToString(),
-            header_signature=self.block_id,
-            batches=[Batch(transactions=[Transaction(
-                header=TransactionHeader(
-                    family_name="seth",
-                ).SerializeToString(),
-                header_signature=txn_ids[1],
-            )])],
-        )])
-        self._block_get_exchange(block=Block(
-            header=BlockHeader(
-                block_num=self.block_num+1,
-            ).SerializeToString(),
-            header_signature=self.block_id,
-            batches=[Batch(transactions=[Transaction(
-                header=TransactionHeader(
-                    family_name="seth",
+            # Exchange the committed block
+            self._send_block_commit(
+                self.block_id, self.block_num + i + 1, events=[log_event])
+            msg, request = self._receive_block_request_id()
+            self.assertEqual(request.block_id, self.block_id)
+            self._send_block_back(msg, block=Block(
+                header=BlockHeader(
+                    block_num=self.block_num + i + 1,
                 ).SerializeToString(),
-                header_signature=txn_ids[0],
-            )])],
-        ))
-
-        receipts = [
-            TransactionReceipt(
-                data=[SethTransactionReceipt(
-                    gas_used=self.gas,
-                    return_value=self.return_value_b,
-                    contract_address=self.contract_address_b,
+                header_signature=self.block_id,
+                batches=[Batch(transactions=[Transaction(
+                    header=TransactionHeader(
+                        family_name="seth",
                     ).SerializeToString(),
-                ],
-                events=[Event(
-                    event_type="seth_log_event",
-                    attributes=[
-                        Event.Attribute(key="address", value=self.contract_address),
-                        Event.Attribute(key="topic1", value=topics[0]),
-                    ],
-                    data=self.log_data_b,
-                )],
-                transaction_id=txn_ids[0],
-            ),
-            TransactionReceipt(
+                    header_signature=txn_id,
+                )])],
+            ))
+
+            # Exchange its receipts
+            msg, request = self._receive_receipt_request()
+            self.assertEqual(request.transaction_ids[0], txn_id)
+            self._send_receipts_back(msg, [TransactionReceipt(
                 data=[SethTransactionReceipt(
                     gas_used=self.gas,
                     return_value=self.return_value_b,
                     contract_address=self.contract_address_b,
                     ).SerializeToString(),
                 ],
-                events=[Event(
-                    event_type="seth_log_event",
-                    attributes=[
-                        Event.Attribute(key="address", value=self.contract_address),
-                        Event.Attribute(key="topic1", value=topics[1]),
-                    ],
-                    data=self.log_data_b,
-                )],
-                transaction_id=txn_ids[1],
-            ),
-        ]
-
-        # Exchange receipts for block 1
-        msg, request = self._receive_receipt_request()
-        self.assertEqual(request.transaction_ids[0], txn_ids[0])
-        self._send_receipts_back(msg, [receipts[0]])
-
-        # Exchange receipts for block 2
-        msg, request = self._receive_receipt_request()
-        self.assertEqual(request.transaction_ids[0], txn_ids[1])
-        self._send_receipts_back(msg, [receipts[1]])
+                events=[log_event],
+                transaction_id=txn_id,
+            )])
 
-        result = self.rpc.get_result()
+        result = self._poll_filter_changes(filter_id, 2)
 
         self.assertEqual(len(result), 2)
         for i, log in enumerate(result):
@@ -1127,6 +1894,7 @@ class SethRpcTest(unittest.TestCase):
 
             topic1 = log["topics"][0]
             self.assertEqual(topic1, "0x" + topics[i])
+        self._uninstall_filters([filter_id])
 
     # -- Utilities -- #
     def _send_block_back(self, msg, block=None):
@@ -1187,6 +1955,13 @@ class SethRpcTest(unittest.TestCase):
             ),
             msg)
 
+    def _exchange_block_info_config(self):
+        """Answers the read of the block info config that a txn which
+           executes code makes, as if there was no block info."""
+        msg, request = self._receive_state_request()
+        self.assertEqual(request.address, "00b10c01" + "0" * 62)
+        self._send_state_no_resource(msg)
+
     def _send_state_no_resource(self, msg):
         self.validator.respond(
             Message.CLIENT_STATE_GET_RESPONSE,
@@ -1316,8 +2091,78 @@ class SethRpcTest(unittest.TestCase):
         request.ParseFromString(msg.content)
         return msg, request
 
+    def _get_submitted_batch_id(self, msg):
+        request = ClientBatchSubmitRequest()
+        request.ParseFromString(msg.content)
+        return request.batches[0].header_signature
+
+    def _receive_batch_status_request(self, batch_id):
+        msg = self.validator.receive()
+        self.assertEqual(msg.message_type, Message.CLIENT_BATCH_STATUS_REQUEST)
+        request = ClientBatchStatusRequest()
+        request.ParseFromString(msg.content)
+        self.assertEqual(list(request.batch_ids), [batch_id])
+        return msg, request
+
+    def _send_batch_status_response(self, msg, batch_id, status,
+                                    invalid_transactions=None):
+        self.validator.respond(
+            Message.CLIENT_BATCH_STATUS_RESPONSE,
+            ClientBatchStatusResponse(
+                status=ClientBatchStatusResponse.OK,
+                batch_statuses=[ClientBatchStatus(
+                    batch_id=batch_id,
+                    status=status,
+                    invalid_transactions=invalid_transactions or [])]),
+            msg)
+
+    def _submit_raw_batch(self, txn_id, batch_id):
+        batch_list = self._make_raw_batch_list(
+            "seth", "1.0", txn_id=txn_id, batch_id=batch_id)
+        self.rpc.acall(
+            "seth_sendRawBatch", ["0x" + batch_list.SerializeToString().hex()])
+        msg = self.validator.receive()
+        self.assertEqual(msg.message_type, Message.CLIENT_BATCH_SUBMIT_REQUEST)
+        self._send_submit_response(msg)
+        self.rpc.get_result()
+
+    def _make_raw_batch_list(self, family_name, family_version,
+                             txn_id=None, batch_id=None):
+        txn_id = txn_id or self.txn_id
+        batch_id = batch_id or self.block_id
+        payload = SethTransaction(
+            transaction_type=SethTransaction.MESSAGE_CALL,
+            message_call=MessageCallTxn(
+                to=bytes.fromhex(self.contract_address),
+                data=self.contract_call_b,
+                gas_limit=90000,
+                nonce=1)).SerializeToString()
+        txn = Transaction(
+            header=TransactionHeader(
+                family_name=family_name,
+                family_version=family_version,
+                signer_public_key=self.public_key,
+                batcher_public_key=self.public_key).SerializeToString(),
+            header_signature=txn_id,
+            payload=payload)
+        batch = Batch(
+            header=BatchHeader(
+                signer_public_key=self.public_key,
+                transaction_ids=[txn_id]).SerializeToString(),
+            header_signature=batch_id,
+            transactions=[txn])
+        return BatchList(batches=[batch])
+
     def _receive_submit_request(self):
         msg = self.validator.receive()
+        # As far as seth-rpc knows, transactions sent by earlier tests are
+        # still in flight, so it checks that they weren't lost
+        while msg.message_type == Message.CLIENT_BATCH_STATUS_REQUEST:
+            request = ClientBatchStatusRequest()
+            request.ParseFromString(msg.content)
+            self._send_batch_status_response(
+                msg, request.batch_ids[0], ClientBatchStatus.UNKNOWN)
+            msg = self.validator.receive()
         self.assertEqual(msg.message_type, Message.CLIENT_BATCH_SUBMIT_REQUEST)
         request = ClientBatchSubmitRequest()
         request.ParseFromString(msg.content)
@@ -1336,6 +2181,23 @@ class SethRpcTest(unittest.TestCase):
 
         return msg, txn
 
+    def _state_entry_exchange(self, entry, address=None):
+        """Responds to a state request at the state root of the latest block,
+           with no resource if the entry is None."""
+        msg, request = self._receive_state_request()
+        self.assertEqual(request.state_root, self.state_root)
+        if address is not None:
+            self.assertEqual(request.address, address)
+        if entry is None:
+            self._send_state_no_resource(msg)
+        else:
+            self.validator.respond(
+                Message.CLIENT_STATE_GET_RESPONSE,
+                ClientStateGetResponse(
+                    status=ClientStateGetResponse.OK,
+                    value=entry.SerializeToString()),
+                msg)
+
     def _block_get_exchange(self, block=None):
         msg, _ = self._receive_block_request_num()
         self._send_block_back(msg, block)
@@ -1344,6 +2206,38 @@ class SethRpcTest(unittest.TestCase):
         msg, _ = self._receive_block_list_request()
         self._send_block_list_back(msg, blocks)
 
+    def _send_block_commit(self, block_id, block_num, events=None):
+        """Sends the events the validator publishes when a block is
+        committed."""
+        block_commit = Event(
+            event_type="sawtooth/block-commit",
+            attributes=[
+                Event.Attribute(key="block_id", value=block_id),
+                Event.Attribute(key="block_num", value=str(block_num)),
+            ])
+        self.validator.send(
+            Message.CLIENT_EVENTS,
+            EventList(events=[block_commit] + (events or [])))
+
+    def _poll_filter_changes(self, filter_id, count):
+        """Collects filter changes until `count` have been returned, since the
+        block commits are processed in the background."""
+        changes = []
+        for _ in range(50):
+            changes.extend(
+                self.rpc.call("eth_getFilterChanges", [filter_id]))
+            if len(changes) >= count:
+                break
+            time.sleep(0.1)
+        return changes
+
+    def _uninstall_filters(self, filter_ids):
+        """Uninstalls filters, so that later tests don't have to exchange
+        the blocks they watch."""
+        for filter_id in filter_ids:
+            self.assertTrue(
+                self.rpc.call("eth_uninstallFilter", [filter_id]))
+
     def _make_multi_txn_block(self, txn_ids):
         gas = self.gas
         nonce = self.nonce